@@ -1,6 +1,9 @@
+use crate::bot::Action;
+use crate::glyphs::{circled_char, count_char, WRAP_EDGES};
 use crate::minefield;
 use minefield::client::{CellState, GameState};
 use minefield::field::Cell;
+use minefield::grid::Topology;
 use std::io;
 use std::str;
 
@@ -12,56 +15,80 @@ fn to_char_mono(c: &CellState) -> String {
     match c {
         CellState::Hidden => String::from("█"),
         CellState::Flagged => String::from("▓"),
+        CellState::Flags(k) => circled_char(*k, true).to_string(),
         CellState::Revealed(r) => match r {
             Cell::Bomb => String::from("*"),
+            Cell::Mines(k) => circled_char(*k, false).to_string(),
+            Cell::AntiMine => String::from("⊖"),
             Cell::Clean(0) => String::from(" "),
-            Cell::Clean(i) => format!("{}", i),
+            Cell::Clean(i) => count_char(*i).to_string(),
         },
         CellState::Marked => String::from("▟"),
+        CellState::Exploded(_) => String::from("✹"),
     }
 }
 
-#[derive(Copy, Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 enum Command {
     None,
     Exit,
-    Query(usize, usize),
-    Flag(usize, usize),
+    Query(Vec<usize>),
+    Flag(Vec<usize>),
     Submit,
 }
 
+pub fn parse_action(input: &str) -> Result<Option<Action>, String> {
+    //! Move typed at the prompt (`da3`, `fb2`, `s`...), or `None` for `q`
+    match AsciiClient::parse_input(input.trim()) {
+        Command::Query(coords) => Ok(Some(Action::Dig(coords))),
+        Command::Flag(coords) => Ok(Some(Action::Flag(coords))),
+        Command::Submit => Ok(Some(Action::Submit)),
+        Command::Exit => Ok(None),
+        Command::None => Err(format!("Invalid command: {}", input.trim())),
+    }
+}
+
 impl AsciiClient {
     pub fn new(c: minefield::client::Client) -> AsciiClient {
         AsciiClient { client: c }
     }
 
     fn check_coordinates(&self, c: Command) -> Command {
-        match c {
-            Command::Query(i, j) | Command::Flag(i, j) => {
-                if let Some(_) = self.client.get_state().index(i, j) {
+        match &c {
+            Command::Query(coords) | Command::Flag(coords) => {
+                if self.client.get_state().index(coords).is_some() {
                     // valid coordinates
                     c
                 } else {
                     Command::None
                 }
             }
-            other => other,
+            _ => c,
         }
     }
 
-    fn parse_coordinate(input: &str) -> Option<(usize, usize)> {
-        let mut chars = input.chars();
-        let col_char = chars.next().unwrap();
+    fn parse_coordinate(input: &str) -> Option<Vec<usize>> {
+        //! Reads 'xY' (column letter, row digit), or 'Lxy' with a layer digit
+        //! on three-dimensional boards
+        let mut chars = input.chars().peekable();
+        let layer = match chars.peek() {
+            Some(c) if c.is_ascii_digit() => chars.next()?.to_digit(10),
+            _ => None,
+        };
+        let col_char = chars.next()?;
         if !col_char.is_ascii_alphabetic() {
             return None;
         }
         let col = col_char.to_digit(36).unwrap() as usize - 10;
-        let row_char = chars.next().unwrap();
-        if !row_char.is_digit(10) {
+        let row_char = chars.next()?;
+        if !row_char.is_ascii_digit() || chars.next().is_some() {
             return None;
         }
         let row = row_char.to_digit(10).unwrap() as usize;
-        Some((row, col))
+        match layer {
+            Some(layer) => Some(vec![layer as usize, row, col]),
+            None => Some(vec![row, col]),
+        }
     }
 
     fn parse_input(input: &str) -> Command {
@@ -70,15 +97,17 @@ impl AsciiClient {
             _ if !low_i.is_ascii() => Command::None,
             "q" => Command::Exit,
             "s" => Command::Submit,
-            _ if low_i.len() == 2 => match Self::parse_coordinate(&low_i) {
-                Some((row, col)) => Command::Query(row, col),
-                None => Command::None,
-            },
-            _ if low_i.len() == 3 => {
-                if let Some((row, col)) = Self::parse_coordinate(&low_i[1..]) {
+            _ if low_i.len() == 2 || low_i.starts_with(|c: char| c.is_ascii_digit()) => {
+                match Self::parse_coordinate(&low_i) {
+                    Some(coords) => Command::Query(coords),
+                    None => Command::None,
+                }
+            }
+            _ if low_i.len() >= 3 => {
+                if let Some(coords) = Self::parse_coordinate(&low_i[1..]) {
                     match &low_i[..1] {
-                        "f" => Command::Flag(row, col),
-                        "d" => Command::Query(row, col),
+                        "f" => Command::Flag(coords),
+                        "d" => Command::Query(coords),
                         _ => Command::None,
                     }
                 } else {
@@ -98,7 +127,11 @@ impl AsciiClient {
             // read line
             current_command = Command::None;
             while current_command == Command::None {
-                println!("Enter a command: 'xY' or 'dxY' to dig, 'Q' to exit, 'fXY' to flag. E.g.: 'da3'.");
+                if self.client.get_state().num_layers() > 1 {
+                    println!("Enter a command: 'Lxy' or 'dLxy' to dig in layer L, 'Q' to exit, 'fLxy' to flag. E.g.: 'd2a3'.");
+                } else {
+                    println!("Enter a command: 'xY' or 'dxY' to dig, 'Q' to exit, 'fXY' to flag. E.g.: 'da3'.");
+                }
                 input.clear();
                 match io::stdin().read_line(&mut input) {
                     Ok(_) => {
@@ -113,14 +146,14 @@ impl AsciiClient {
                 Command::Exit => {
                     break;
                 }
-                Command::Query(row, col) => {
-                    self.client.query_smart(row, col);
+                Command::Query(coords) => {
+                    self.client.query_smart(&coords);
                 }
                 Command::Submit => {
                     self.client.submit().unwrap();
                 }
-                Command::Flag(row, col) => {
-                    self.client.flag(row, col);
+                Command::Flag(coords) => {
+                    self.client.flag(&coords);
                 }
                 _ => (),
             }
@@ -137,22 +170,83 @@ impl std::fmt::Display for CellState {
     }
 }
 
-impl std::fmt::Display for AsciiClient {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl AsciiClient {
+    fn fmt_layer(&self, f: &mut std::fmt::Formatter<'_>, layer: usize) -> std::fmt::Result {
         let alphabet = "abcdefghijklmnopqrstuvwxyz";
         let state = self.client.get_state();
-        writeln!(f, "state: {:?}", self.client.get_game_state())?;
-        let (h, w) = self.client.get_state().shape();
-        write!(f, "   {}\n", &alphabet[..w])?;
-        write!(f, "  ┏{}┓\n", "━".repeat(w))?;
+        let (h, w) = state.layer_shape();
+        // holes are left blank
+        let cell = |i, j| {
+            let coords = state.layer_coords(layer, i, j).unwrap();
+            if state.exists(&coords) {
+                state.get(&coords).unwrap().to_string()
+            } else {
+                String::from(" ")
+            }
+        };
+        // dashed borders show edges that wrap around
+        let (line, side) = if state.wraps() {
+            (WRAP_EDGES.0.to_string(), WRAP_EDGES.1)
+        } else {
+            (String::from("━"), '┃')
+        };
+        if state.topology() == Topology::Hex {
+            // cells are separated by a space, odd rows are shifted by half a cell
+            let header: Vec<String> = alphabet[..w].chars().map(String::from).collect();
+            writeln!(f, "   {}", header.join(" "))?;
+            writeln!(f, "  ┏{}┓", line.repeat(2 * w))?;
+            for i in 0..h {
+                let cells: Vec<String> = (0..w).map(|j| cell(i, j)).collect();
+                let (left, right) = if i % 2 == 1 { (" ", "") } else { ("", " ") };
+                writeln!(
+                    f,
+                    "{:2}{}{}{}{}{}",
+                    i,
+                    side,
+                    left,
+                    cells.join(" "),
+                    right,
+                    side
+                )?;
+            }
+            return writeln!(f, "  ┗{}┛", line.repeat(2 * w));
+        }
+        writeln!(f, "   {}", &alphabet[..w])?;
+        writeln!(f, "  ┏{}┓", line.repeat(w))?;
         for i in 0..h {
-            write!(f, "{:2}┃", i)?;
+            write!(f, "{:2}{}", i, side)?;
             for j in 0..w {
-                write!(f, "{}", state.get(i, j).unwrap())?;
+                write!(f, "{}", cell(i, j))?;
             }
-            write!(f, "┃\n")?;
+            writeln!(f, "{}", side)?;
+        }
+        writeln!(f, "  ┗{}┛", line.repeat(w))
+    }
+}
+
+impl std::fmt::Display for AsciiClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "state: {:?}", self.client.get_game_state())?;
+        if self.client.lives() > 1 {
+            writeln!(
+                f,
+                "lives: {}/{}",
+                self.client.lives_left(),
+                self.client.lives()
+            )?;
+        }
+        if let Some(variant) = self.client.minefield.variant() {
+            writeln!(f, "variant: {}", variant)?;
+        }
+        let num_layers = self.client.get_state().num_layers();
+        if num_layers == 1 {
+            return self.fmt_layer(f, 0);
         }
-        write!(f, "  ┗{}┛\n", "━".repeat(w))
+        for layer in 0..num_layers {
+            writeln!(f, "layer {}", layer)?;
+            self.fmt_layer(f, layer)?;
+        }
+        Ok(())
     }
 }
 
@@ -161,14 +255,31 @@ mod test {
     use super::*;
     #[test]
     fn input() {
-        assert_eq!(AsciiClient::parse_input("a1"), Command::Query(1, 0));
-        assert_eq!(AsciiClient::parse_input("e8"), Command::Query(8, 4));
+        assert_eq!(AsciiClient::parse_input("a1"), Command::Query(vec![1, 0]));
+        assert_eq!(AsciiClient::parse_input("e8"), Command::Query(vec![8, 4]));
         assert_eq!(AsciiClient::parse_input("q"), Command::Exit);
         assert_eq!(AsciiClient::parse_input("s"), Command::Submit);
         assert_eq!(AsciiClient::parse_input("nn"), Command::None);
         assert_eq!(AsciiClient::parse_input("55"), Command::None);
-        assert_eq!(AsciiClient::parse_input("fa4"), Command::Flag(4, 0));
-        assert_eq!(AsciiClient::parse_input("dz4"), Command::Query(4, 25));
+        assert_eq!(AsciiClient::parse_input("fa4"), Command::Flag(vec![4, 0]));
+        assert_eq!(AsciiClient::parse_input("dz4"), Command::Query(vec![4, 25]));
+        // layered boards
+        assert_eq!(
+            AsciiClient::parse_input("2b3"),
+            Command::Query(vec![2, 3, 1])
+        );
+        assert_eq!(
+            AsciiClient::parse_input("f0a1"),
+            Command::Flag(vec![0, 1, 0])
+        );
+        assert_eq!(AsciiClient::parse_input("d1a"), Command::None);
+        assert_eq!(AsciiClient::parse_input("fa45"), Command::None);
+        assert_eq!(parse_action(" fb2\n"), Ok(Some(Action::Flag(vec![2, 1]))));
+        assert_eq!(parse_action("Q"), Ok(None));
+        assert_eq!(
+            parse_action("dig"),
+            Err(String::from("Invalid command: dig"))
+        );
     }
     use minefield::field::test::generate_test_minefield;
     #[test]
@@ -196,7 +307,7 @@ mod test {
         let client = minefield::client::Client::from_minefield(field);
         let mut a_client = AsciiClient::new(client);
 
-        a_client.client.flag(0, 0);
+        a_client.client.flag(&[0, 0]);
         let flagged_state = format!("{}", a_client);
         assert_eq!(
             flagged_state,
@@ -209,6 +320,129 @@ mod test {
   ┗━━━━━┛\n"
         );
     }
+    #[test]
+    fn display_hex() {
+        let (field, _bomb_locations) = generate_test_minefield();
+        let layout = field.layout(()).with_topology(Topology::Hex);
+        let field = minefield::field::Minefield::from_layout(&layout, 0).unwrap();
+        let client = minefield::client::Client::from_minefield(field);
+        let mut a_client = AsciiClient::new(client);
+
+        a_client.client.flag(&[1, 0]);
+        assert_eq!(
+            format!("{}", a_client),
+            "state: Running
+   a b c d e
+  ┏━━━━━━━━━━┓
+ 0┃█ █ █ █ █ ┃
+ 1┃ ▓ █ █ █ █┃
+ 2┃█ █ █ █ █ ┃
+  ┗━━━━━━━━━━┛\n"
+        );
+    }
+
+    #[test]
+    fn display_wrap() {
+        let (field, _bomb_locations) = generate_test_minefield();
+        let layout = field.layout(()).with_wrap(true).unwrap();
+        let field = minefield::field::Minefield::from_layout(&layout, 0).unwrap();
+        let a_client = AsciiClient::new(minefield::client::Client::from_minefield(field));
+        assert_eq!(
+            format!("{}", a_client),
+            "state: Running
+   abcde
+  ┏┅┅┅┅┅┓
+ 0┇█████┇
+ 1┇█████┇
+ 2┇█████┇
+  ┗┅┅┅┅┅┛\n"
+        );
+    }
+
+    #[test]
+    fn display_3d() {
+        let layout = minefield::grid::Grid::with_dims(&[2, 2, 3], vec![(); 12]).unwrap();
+        let field = minefield::field::Minefield::from_layout(&layout, 0).unwrap();
+        let mut a_client = AsciiClient::new(minefield::client::Client::from_minefield(field));
+        a_client.client.flag(&[1, 0, 2]);
+        assert_eq!(
+            format!("{}", a_client),
+            "state: Running
+layer 0
+   abc
+  ┏━━━┓
+ 0┃███┃
+ 1┃███┃
+  ┗━━━┛
+layer 1
+   abc
+  ┏━━━┓
+ 0┃██▓┃
+ 1┃███┃
+  ┗━━━┛\n"
+        );
+    }
+
+    #[test]
+    fn display_holes() {
+        let layout = minefield::grid::Grid::new(2, 3, vec![(); 6])
+            .unwrap()
+            .with_mask(&[false, true, false, true, true, true])
+            .unwrap();
+        let field = minefield::field::Minefield::from_layout(&layout, 0).unwrap();
+        let a_client = AsciiClient::new(minefield::client::Client::from_minefield(field));
+        assert_eq!(
+            format!("{}", a_client),
+            "state: Running
+   abc
+  ┏━━━┓
+ 0┃ █ ┃
+ 1┃███┃
+  ┗━━━┛\n"
+        );
+    }
+
+    #[test]
+    fn display_anti_mines() {
+        let layout = minefield::grid::Grid::new(1, 5, vec![(); 5]).unwrap();
+        let field = minefield::field::Minefield::from_layout(&layout, 0)
+            .unwrap()
+            .with_bombs(&[vec![0, 0]])
+            .unwrap()
+            .with_anti_mines(&[vec![0, 2]])
+            .unwrap();
+        let mut a_client = AsciiClient::new(minefield::client::Client::from_minefield(field));
+        a_client.client.reveal(true);
+        assert_eq!(
+            format!("{}", a_client),
+            "state: Lost
+variant: 1 anti-mines
+   abcde
+  ┏━━━━━┓
+ 0┃* ⊖⑴ ┃
+  ┗━━━━━┛\n"
+        );
+    }
+
+    #[test]
+    fn display_lives() {
+        let (field, _bomb_locations) = generate_test_minefield();
+        let client = minefield::client::Client::from_minefield(field).with_lives(3);
+        let mut a_client = AsciiClient::new(client);
+        a_client.client.query_smart(&[0, 0]);
+        assert_eq!(
+            format!("{}", a_client),
+            "state: Running
+lives: 2/3
+   abcde
+  ┏━━━━━┓
+ 0┃✹████┃
+ 1┃█████┃
+ 2┃█████┃
+  ┗━━━━━┛\n"
+        );
+    }
+
     #[test]
     fn display_revealed() {
         let (field, _bomb_locations) = generate_test_minefield();
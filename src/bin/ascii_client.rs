@@ -1,14 +1,30 @@
-
 extern crate minesweeper;
 
-use minesweeper::minefield;
 use minesweeper::ascii_client;
+use minesweeper::minefield::mode::{Game, Mode};
+use minesweeper::options::GameOptions;
+use minesweeper::scores::{self, ScoreStore};
 
 fn main() {
-
-    let (height, width) = (12,30);
-    let num_bombs = 20;
-    let c = minefield::client::Client::new_random(height, width, num_bombs);
-    let mut tc = ascii_client::AsciiClient {client: c};
+    let options = GameOptions::from_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    if options.mode != Mode::Classic {
+        eprintln!("Game modes are only available in tui_client");
+        std::process::exit(1);
+    }
+    let c = options.new_client().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    });
+    let mut tc = ascii_client::AsciiClient { client: c };
     tc.mainloop();
-}
\ No newline at end of file
+    let game = Game::from_client(tc.client);
+    let saved = ScoreStore::open().and_then(|store| scores::save_game(&store, &options, &game));
+    match saved {
+        Ok(Some(summary)) => println!("{}", summary),
+        Ok(None) => (),
+        Err(e) => eprintln!("Cannot save the score: {}", e),
+    }
+}
@@ -0,0 +1,39 @@
+extern crate minesweeper;
+
+use minesweeper::bot::{self, Bot, RandomBot, Solver};
+use minesweeper::minefield::mode::Mode;
+use minesweeper::options::{exit_on_error, take_option, GameOptions};
+
+fn main() {
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // --games is the number of boards, --bot the bots to compare (solver,
+    // random or all)
+    let games =
+        exit_on_error(take_option(&mut args, "--games")).unwrap_or_else(|| String::from("100"));
+    let games: usize = exit_on_error(
+        games
+            .parse()
+            .map_err(|_| format!("Invalid number of games: {}", games)),
+    );
+    let bots =
+        exit_on_error(take_option(&mut args, "--bot")).unwrap_or_else(|| String::from("all"));
+    let options = exit_on_error(GameOptions::parse(args.iter().cloned()));
+    if options.mode != Mode::Classic {
+        eprintln!("Game modes are only available in tui_client");
+        std::process::exit(1);
+    }
+    let seed = options.seed.unwrap_or(0);
+    let mut bots: Vec<Box<dyn Bot>> = match &bots[..] {
+        "solver" => vec![Box::new(Solver::new(seed))],
+        "random" => vec![Box::new(RandomBot::new(seed))],
+        "all" => vec![Box::new(Solver::new(seed)), Box::new(RandomBot::new(seed))],
+        _ => {
+            eprintln!("Unknown bot: {}", bots);
+            std::process::exit(1);
+        }
+    };
+    for bot in bots.iter_mut() {
+        let report = exit_on_error(bot::benchmark(bot.as_mut(), &options, games));
+        println!("{}", report);
+    }
+}
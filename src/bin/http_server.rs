@@ -0,0 +1,49 @@
+extern crate minesweeper;
+
+use minesweeper::http_api;
+use minesweeper::registry::Registry;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+const USAGE: &str = "Usage: http_server [--port N] [--ttl SECONDS]
+
+Hosts minesweeper games over HTTP on 127.0.0.1 (default port: 8080), with
+JSON bodies. Games left alone for --ttl seconds are dropped (default: 1800).
+    POST /games             start a game, e.g. {\"height\": 9, \"seed\": 42}
+    GET /games/ID           show the board
+    POST /games/ID/dig      dig a cell: {\"coords\": [row, col]}
+    POST /games/ID/flag     toggle the flag of a cell
+    POST /games/ID/chord    dig around a number whose flags are all placed
+    POST /games/ID/submit   submit the flagged cells";
+
+fn main() {
+    let mut port: u16 = 8080;
+    let mut ttl: u64 = 1800;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let value = args.next();
+        match (&arg[..], value.as_ref().map(|v| v.parse::<u64>())) {
+            ("--port", Some(Ok(p))) if p <= u16::MAX as u64 => port = p as u16,
+            ("--ttl", Some(Ok(t))) if t > 0 => ttl = t,
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(1);
+            }
+        }
+    }
+    // local players and bots only
+    let server = tiny_http::Server::http(("127.0.0.1", port)).unwrap_or_else(|e| {
+        eprintln!("Cannot listen on port {}: {}", port, e);
+        std::process::exit(1);
+    });
+    println!("Listening on http://{}", server.server_addr());
+    let ttl = Duration::from_secs(ttl);
+    let registry = Arc::new(Registry::new(Some(ttl)));
+    let sweeper = registry.clone();
+    // idle games are also dropped when nobody makes requests
+    std::thread::spawn(move || loop {
+        std::thread::sleep(ttl);
+        sweeper.expire(Instant::now());
+    });
+    http_api::serve(server, registry);
+}
@@ -0,0 +1,77 @@
+extern crate minesweeper;
+
+use minesweeper::minefield::client::GameState;
+use minesweeper::minefield::mode::Mode;
+use minesweeper::options::{exit_on_error, take_option, GameOptions};
+use minesweeper::referee::Referee;
+use std::time::Duration;
+
+fn number(value: Option<String>, default: u64, what: &str) -> u64 {
+    match value {
+        Some(value) => exit_on_error(
+            value
+                .parse()
+                .map_err(|_| format!("Invalid {}: {}", what, value)),
+        ),
+        None => default,
+    }
+}
+
+fn main() {
+    // the command of the bot comes after `--`
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let command = match args.iter().position(|arg| arg == "--") {
+        Some(i) => args.split_off(i).split_off(1),
+        None => vec![],
+    };
+    if command.is_empty() {
+        eprintln!("Usage: referee [--games N] [--move-time MS] [board options] -- BOT [ARGS...]");
+        std::process::exit(1);
+    }
+    // --games is the number of boards, --move-time the time limit of a move
+    let games = number(
+        exit_on_error(take_option(&mut args, "--games")),
+        1,
+        "number of games",
+    );
+    let move_time = number(
+        exit_on_error(take_option(&mut args, "--move-time")),
+        1000,
+        "move time",
+    );
+    let options = exit_on_error(GameOptions::parse(args.iter().cloned()));
+    if options.mode != Mode::Classic {
+        eprintln!("Game modes are only available in tui_client");
+        std::process::exit(1);
+    }
+    let referee = Referee::new(command).with_move_time(Duration::from_millis(move_time));
+    let first_seed = options.seed.unwrap_or(0);
+    let mut wins = 0;
+    let mut moves = 0;
+    let mut time = Duration::from_secs(0);
+    for game in 0..games {
+        // consecutive seeds, so that every bot plays the same boards
+        let mut options = options.clone();
+        options.seed = Some(first_seed.wrapping_add(game));
+        options.daily = None;
+        let outcome = exit_on_error(referee.referee(&options));
+        println!(
+            "Game {} (seed {}): {}",
+            game + 1,
+            options.seed.unwrap(),
+            outcome
+        );
+        if outcome.game_state == GameState::Won {
+            wins += 1;
+        }
+        moves += outcome.moves;
+        time += outcome.time;
+    }
+    println!(
+        "{}: {} games, {:.1}% won, {:.2?} per move",
+        referee.name(),
+        games,
+        100.0 * wins as f64 / games.max(1) as f64,
+        time / moves.max(1) as u32
+    );
+}
@@ -0,0 +1,67 @@
+extern crate minesweeper;
+
+use minesweeper::registry::Registry;
+use minesweeper::server;
+use std::net::TcpListener;
+use std::sync::Arc;
+
+const USAGE: &str = "Usage: server [--port N]
+
+Hosts minesweeper games on 127.0.0.1 (default port: 7878). Requests are lines:
+    new [OPTIONS]       start a game, with the options of the clients
+    dig ID ROW,COL [SEEN]
+                        dig a cell (LAYER,ROW,COL on 3D boards); refused if
+                        the cell is no longer as SEEN (e.g. h for hidden)
+    flag ID ROW,COL [SEEN]
+                        toggle the flag of a cell
+    chord ID ROW,COL [SEEN]
+                        dig around a number whose flags are all placed
+    submit ID           submit the flagged cells
+    state ID            show the board
+    join ID NAME [TOKEN]
+                        play the game as a named player, with other players;
+                        the token of the answer plays as them again
+    cursor ID ROW,COL   show the other players where you look
+    players ID          list the players and their cursors
+    owners ID           show who last changed each cell
+    options ID          show the options the game was started with
+    race [OPTIONS]      start a race between two players on the same board
+    enter RACE NAME [TOKEN]
+                        enter a race, and get the game to play; the token of
+                        the answer enters again
+    standings RACE      show the progress of the racers
+    quit                close the connection";
+
+fn main() {
+    let mut port = 7878;
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (&arg[..], args.next().map(|p| p.parse())) {
+            ("--port", Some(Ok(p))) => port = p,
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(1);
+            }
+        }
+    }
+    // local players and bots only
+    let listener = TcpListener::bind(("127.0.0.1", port)).unwrap_or_else(|e| {
+        eprintln!("Cannot listen on port {}: {}", port, e);
+        std::process::exit(1);
+    });
+    println!("Listening on {}", listener.local_addr().unwrap());
+    let registry = Arc::new(Registry::new(None));
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let registry = registry.clone();
+                std::thread::spawn(move || {
+                    if let Err(e) = server::serve(stream, registry) {
+                        eprintln!("Connection closed: {}", e);
+                    }
+                });
+            }
+            Err(e) => eprintln!("Connection failed: {}", e),
+        }
+    }
+}
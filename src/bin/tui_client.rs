@@ -1,13 +1,100 @@
 extern crate minesweeper;
 
-use minesweeper::minefield;
+use minesweeper::options::{exit_on_error, take_option, GameOptions};
+use minesweeper::scores::{self, ScoreStore};
+use minesweeper::spectate::{self, Broadcaster};
 use minesweeper::tui_client;
+use minesweeper::tui_client::backend::Backend;
+use minesweeper::tui_client::remote::RemoteBackend;
+use minesweeper::tui_client::spectator::SpectatorBackend;
+use std::time::Duration;
 
+fn broadcast<B: Backend>(
+    tc: &mut tui_client::TuiClient<B>,
+    port: Option<String>,
+    delay: Option<String>,
+) {
+    //! Streams the game to spectators on a local port, late by `delay` seconds
+    let port = match port {
+        Some(port) => exit_on_error(port.parse().map_err(|_| format!("Invalid port: {}", port))),
+        None => return,
+    };
+    let delay = match delay {
+        Some(delay) => exit_on_error(
+            delay
+                .parse()
+                .map_err(|_| format!("Invalid delay: {}", delay)),
+        ),
+        None => 0,
+    };
+    let board = spectate::board_line(tc.backend().state(), tc.backend().lives());
+    let broadcaster = Broadcaster::bind(port, board, Duration::from_secs(delay))
+        .map_err(|e| format!("Cannot listen on port {}: {}", port, e));
+    tc.set_broadcaster(exit_on_error(broadcaster));
+}
 
 fn main() {
-    let (height, width) = (12,30);
-    let num_bombs = 20;
-    let c = minefield::client::Client::new_random(height, width, num_bombs);
-    let mut tc = tui_client::TuiClient::new(c);
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // --server plays on a game server instead of locally, --name shares the
+    // game with other players, --join plays the game of another player, and
+    // --race races another player ("new" to start the race)
+    let server = exit_on_error(take_option(&mut args, "--server"));
+    let name = exit_on_error(take_option(&mut args, "--name"));
+    let join = exit_on_error(take_option(&mut args, "--join"));
+    let race = exit_on_error(take_option(&mut args, "--race"));
+    // --broadcast streams the game to spectators, who --watch it
+    let port = exit_on_error(take_option(&mut args, "--broadcast"));
+    let delay = exit_on_error(take_option(&mut args, "--delay"));
+    let watch = exit_on_error(take_option(&mut args, "--watch"));
+    let options = exit_on_error(GameOptions::parse(args.iter().cloned()));
+    let keymap = match tui_client::keymap::Keymap::load() {
+        Ok(keymap) => keymap,
+        Err(e) => {
+            eprintln!("Invalid keymap configuration: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if let Some(address) = watch {
+        let backend = exit_on_error(SpectatorBackend::watch(&address));
+        let mut tc = tui_client::TuiClient::from_backend(backend);
+        tc.set_keymap(keymap);
+        tc.mainloop();
+        return;
+    }
+    if let Some(address) = server {
+        let shared = name.is_some() || join.is_some() || race.is_some();
+        let name = name
+            .or_else(|| std::env::var("USER").ok())
+            .unwrap_or_else(|| String::from("player"));
+        let backend = match (join, race) {
+            (Some(_), Some(_)) => Err(String::from("Cannot join a game and race at once")),
+            (None, Some(race)) if race == "new" => RemoteBackend::race(&address, &args, &name),
+            (None, Some(race)) => match race.parse() {
+                Ok(race) => RemoteBackend::enter(&address, race, &name),
+                Err(_) => Err(format!("Invalid race id: {}", race)),
+            },
+            (Some(id), None) => match id.parse() {
+                Ok(id) => RemoteBackend::join(&address, id, &name),
+                Err(_) => Err(format!("Invalid game id: {}", id)),
+            },
+            (None, None) if shared => RemoteBackend::host(&address, &args, &name),
+            (None, None) => RemoteBackend::connect(&address, &args),
+        };
+        let mut tc = tui_client::TuiClient::from_backend(exit_on_error(backend));
+        tc.set_keymap(keymap);
+        broadcast(&mut tc, port, delay);
+        tc.mainloop();
+        return;
+    }
+    let game = exit_on_error(options.new_game());
+    let mut tc = tui_client::TuiClient::from_game(game);
+    tc.set_keymap(keymap);
+    broadcast(&mut tc, port, delay);
     tc.mainloop();
-}
\ No newline at end of file
+    let saved = ScoreStore::open().and_then(|store| scores::save_game(&store, &options, tc.game()));
+    match saved {
+        Ok(Some(summary)) => println!("{}", summary),
+        Ok(None) => (),
+        Err(e) => eprintln!("Cannot save the score: {}", e),
+    }
+}
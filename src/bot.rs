@@ -0,0 +1,358 @@
+use crate::minefield::client::{CellState, Client, GameState};
+use crate::minefield::field::Cell;
+use crate::minefield::grid::Grid;
+use crate::options::GameOptions;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::time::{Duration, Instant};
+
+/// Move of a bot
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    Dig(Vec<usize>),
+    Flag(Vec<usize>),
+    Chord(Vec<usize>),
+    Submit,
+}
+
+/// Move of a bot, with the reason it was chosen
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step {
+    pub action: Action,
+    /// Deduction behind the move, e.g. "b3 shows 1 and already has 1 flag"
+    pub reason: String,
+    /// Whether the move could dig a mine
+    pub guess: bool,
+}
+
+/// Player that only sees what a human player sees
+pub trait Bot {
+    fn name(&self) -> String;
+    /// Next move on a running game
+    fn step(&mut self, state: &Grid<CellState>, num_bombs: usize) -> Step;
+}
+
+pub fn cell_name(coords: &[usize]) -> String {
+    //! Name of a cell as typed in ascii_client: column letter and row digit
+    //! (`b3`), after the layer on 3D boards (`1b3`); coordinates otherwise
+    match coords {
+        [.., row, col] if *row < 10 && *col < 26 => {
+            let layer = match coords {
+                [layer, _, _] if *layer < 10 => layer.to_string(),
+                [_, _] => String::new(),
+                _ => return crate::protocol::format_coords(coords),
+            };
+            format!("{}{}{}", layer, (b'a' + *col as u8) as char, row)
+        }
+        _ => crate::protocol::format_coords(coords),
+    }
+}
+
+fn hidden_cells(state: &Grid<CellState>) -> Vec<Vec<usize>> {
+    state
+        .iter()
+        .filter(|(coords, &cell_state)| state.exists(coords) && cell_state == CellState::Hidden)
+        .map(|(coords, _)| coords)
+        .collect()
+}
+
+fn guess<R: Rng>(state: &Grid<CellState>, rng: &mut R) -> Option<Step> {
+    //! Digs a hidden cell at random
+    let hidden = hidden_cells(state);
+    if hidden.is_empty() {
+        return None;
+    }
+    let coords = hidden[rng.gen_range(0, hidden.len())].clone();
+    Some(Step {
+        reason: format!("No safe cell: guessing {}", cell_name(&coords)),
+        action: Action::Dig(coords),
+        guess: true,
+    })
+}
+
+/// Digs at random, then submits: the baseline of the benchmarks
+pub struct RandomBot {
+    rng: StdRng,
+}
+
+impl RandomBot {
+    pub fn new(seed: u64) -> RandomBot {
+        RandomBot {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+}
+
+impl Bot for RandomBot {
+    fn name(&self) -> String {
+        String::from("random")
+    }
+
+    fn step(&mut self, state: &Grid<CellState>, _num_bombs: usize) -> Step {
+        guess(state, &mut self.rng).unwrap_or(Step {
+            action: Action::Submit,
+            reason: String::from("Nothing left to dig"),
+            guess: true,
+        })
+    }
+}
+
+/// Plays the moves that follow from one number at a time, and guesses when
+/// there are none
+///
+/// Deductions assume that numbers count the mines around them, one per cell:
+/// the solver gets lost on liars, anti-mines and stacks.
+pub struct Solver {
+    rng: StdRng,
+}
+
+impl Solver {
+    pub fn new(seed: u64) -> Solver {
+        Solver {
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn deduce(state: &Grid<CellState>, num_bombs: usize) -> Option<Step> {
+        //! Move that is sure to be right, if any
+        let num_flags: usize = state
+            .data()
+            .iter()
+            .map(|cell_state| cell_state.num_flags() as usize)
+            .sum();
+        if num_flags == num_bombs {
+            return Some(Step {
+                action: Action::Submit,
+                reason: format!("All {} mines are flagged", num_bombs),
+                guess: false,
+            });
+        }
+        for (coords, &cell_state) in state.iter() {
+            let count = match cell_state {
+                CellState::Revealed(Cell::Clean(count)) if state.exists(&coords) => count,
+                _ => continue,
+            };
+            let neighbours = state.neighbours(&coords);
+            let hidden: Vec<&Vec<usize>> = neighbours
+                .iter()
+                .filter(|n| state.get(n) == Some(CellState::Hidden))
+                .collect();
+            let flags: i16 = neighbours
+                .iter()
+                .map(|n| state.get(n).unwrap().num_flags() as i16)
+                .sum();
+            let plural = |n, word| match n {
+                1 => format!("1 {}", word),
+                n => format!("{} {}s", n, word),
+            };
+            if hidden.is_empty() {
+                continue;
+            }
+            if count == flags {
+                return Some(Step {
+                    action: Action::Dig(hidden[0].clone()),
+                    reason: format!(
+                        "{} shows {} and already has {}",
+                        cell_name(&coords),
+                        count,
+                        plural(flags, "flag")
+                    ),
+                    guess: false,
+                });
+            }
+            if count - flags == hidden.len() as i16 {
+                return Some(Step {
+                    action: Action::Flag(hidden[0].clone()),
+                    reason: format!(
+                        "{} shows {} and has {} left",
+                        cell_name(&coords),
+                        count,
+                        plural(count - flags, "hidden neighbour")
+                    ),
+                    guess: false,
+                });
+            }
+        }
+        let hidden = hidden_cells(state);
+        match hidden.first() {
+            Some(coords) if hidden.len() + num_flags == num_bombs => Some(Step {
+                action: Action::Flag(coords.clone()),
+                reason: format!(
+                    "Every hidden cell left is a mine, {} too",
+                    cell_name(coords)
+                ),
+                guess: false,
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Bot for Solver {
+    fn name(&self) -> String {
+        String::from("solver")
+    }
+
+    fn step(&mut self, state: &Grid<CellState>, num_bombs: usize) -> Step {
+        Solver::deduce(state, num_bombs)
+            .or_else(|| guess(state, &mut self.rng))
+            .unwrap_or(Step {
+                action: Action::Submit,
+                reason: String::from("Nothing left to dig"),
+                guess: true,
+            })
+    }
+}
+
+pub fn play(client: &mut Client, action: &Action) {
+    //! Applies the move of a bot
+    match action {
+        Action::Dig(coords) => {
+            client.query_smart(coords);
+        }
+        Action::Flag(coords) => {
+            client.flag(coords);
+        }
+        Action::Chord(coords) => {
+            client.chord(coords);
+        }
+        Action::Submit => {
+            let _ = client.submit();
+        }
+    }
+}
+
+/// Results of a bot over several games
+#[derive(Debug, Clone, PartialEq)]
+pub struct Report {
+    pub bot: String,
+    pub games: usize,
+    pub wins: usize,
+    /// Time spent playing, over all games
+    pub time: Duration,
+    pub guesses: usize,
+}
+
+impl Report {
+    pub fn win_rate(&self) -> f64 {
+        self.wins as f64 / self.games.max(1) as f64
+    }
+
+    pub fn average_time(&self) -> Duration {
+        self.time / self.games.max(1) as u32
+    }
+
+    pub fn average_guesses(&self) -> f64 {
+        self.guesses as f64 / self.games.max(1) as f64
+    }
+}
+
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(
+            f,
+            "{}: {} games, {:.1}% won, {:.2?} per game, {:.1} guesses per game",
+            self.bot,
+            self.games,
+            100.0 * self.win_rate(),
+            self.average_time(),
+            self.average_guesses()
+        )
+    }
+}
+
+pub fn benchmark(bot: &mut dyn Bot, options: &GameOptions, games: usize) -> Result<Report, String> {
+    //! Plays `games` games with the options, on the boards of consecutive
+    //! seeds (from `options.seed`, 0 by default), so that every bot plays the
+    //! same boards
+    //!
+    //! Games where the bot does not finish after one move per cell and flag
+    //! are lost.
+    let first_seed = options.seed.unwrap_or(0);
+    let mut report = Report {
+        bot: bot.name(),
+        games,
+        wins: 0,
+        time: Duration::from_secs(0),
+        guesses: 0,
+    };
+    for game in 0..games {
+        let mut options = options.clone();
+        options.seed = Some(first_seed.wrapping_add(game as u64));
+        options.daily = None;
+        let mut client = options.new_client()?;
+        let max_moves = 2 * client.get_state().len() * options.max_per_cell as usize + 1;
+        let start = Instant::now();
+        for _ in 0..max_moves {
+            if client.get_game_state() != GameState::Running {
+                break;
+            }
+            let step = bot.step(client.get_state(), client.num_bombs());
+            if step.guess {
+                report.guesses += 1;
+            }
+            play(&mut client, &step.action);
+        }
+        report.time += start.elapsed();
+        if client.get_game_state() == GameState::Won {
+            report.wins += 1;
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::minefield::field::test::generate_test_minefield;
+
+    fn options(line: &str) -> GameOptions {
+        GameOptions::parse(line.split_whitespace().map(String::from)).unwrap()
+    }
+
+    #[test]
+    fn solver() {
+        assert_eq!(cell_name(&[3, 1]), "b3");
+        assert_eq!(cell_name(&[2, 3, 1]), "2b3");
+        assert_eq!(cell_name(&[12, 1]), "12,1");
+        // X22X2
+        // 2X33X
+        // 12X21
+        let (field, _) = generate_test_minefield();
+        let mut client = Client::from_minefield(field);
+        client.query_smart(&[2, 0]);
+        assert_eq!(Solver::deduce(client.get_state(), 5), None);
+        client.query_smart(&[1, 0]);
+        client.query_smart(&[2, 1]);
+        let step = Solver::deduce(client.get_state(), 5).unwrap();
+        assert_eq!(step.action, Action::Flag(vec![1, 1]));
+        assert_eq!(step.reason, "a2 shows 1 and has 1 hidden neighbour left");
+        assert!(!step.guess);
+        play(&mut client, &step.action);
+        assert_eq!(Solver::deduce(client.get_state(), 5), None);
+        client.query_smart(&[0, 1]);
+        let step = Solver::deduce(client.get_state(), 5).unwrap();
+        assert_eq!(step.action, Action::Flag(vec![0, 0]));
+        play(&mut client, &step.action);
+        let step = Solver::deduce(client.get_state(), 5).unwrap();
+        assert_eq!(step.action, Action::Dig(vec![0, 2]));
+        assert_eq!(step.reason, "b0 shows 2 and already has 2 flags");
+    }
+
+    #[test]
+    fn benchmarks() {
+        let options = options("--height 6 --width 6 --bombs 5 --seed 3");
+        let mut solver = Solver::new(1);
+        let report = benchmark(&mut solver, &options, 20).unwrap();
+        // the same boards and the same guesses give the same results
+        let mut again = Solver::new(1);
+        let replay = benchmark(&mut again, &options, 20).unwrap();
+        assert_eq!((report.wins, report.guesses), (replay.wins, replay.guesses));
+        assert!(report.guesses >= 20);
+        let random = benchmark(&mut RandomBot::new(1), &options, 20).unwrap();
+        assert!(report.wins > random.wins);
+        assert!(report.to_string().starts_with("solver: 20 games, "));
+        let empty = benchmark(&mut RandomBot::new(1), &options, 0).unwrap();
+        assert_eq!(empty.win_rate(), 0.0);
+    }
+}
@@ -0,0 +1,121 @@
+/// Calendar day, in UTC so that everyone plays the same board on the same day
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn today() -> Date {
+        let secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Date::from_days((secs / 86400) as i64)
+    }
+
+    pub fn from_days(days: i64) -> Date {
+        //! Date `days` after 1970-01-01
+        // civil calendar from days, counting eras of 400 years from March 0000
+        let z = days + 719_468;
+        let era = z.div_euclid(146_097);
+        let doe = z.rem_euclid(146_097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+        Date { year, month, day }
+    }
+
+    pub fn days(&self) -> i64 {
+        //! Number of days since 1970-01-01
+        let year = if self.month <= 2 {
+            self.year - 1
+        } else {
+            self.year
+        };
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let month = self.month as i64;
+        let doy =
+            (153 * if month > 2 { month - 3 } else { month + 9 } + 2) / 5 + self.day as i64 - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146_097 + doe - 719_468
+    }
+
+    pub fn parse(text: &str) -> Result<Date, String> {
+        //! Reads a date written as YYYY-MM-DD
+        let invalid = || format!("Invalid date (expected YYYY-MM-DD): {}", text);
+        let parts: Vec<&str> = text.split('-').collect();
+        if parts.len() != 3 {
+            return Err(invalid());
+        }
+        let date = Date {
+            year: parts[0].parse().map_err(|_| invalid())?,
+            month: parts[1].parse().map_err(|_| invalid())?,
+            day: parts[2].parse().map_err(|_| invalid())?,
+        };
+        // days that do not exist come back as another date
+        if Date::from_days(date.days()) != date {
+            return Err(invalid());
+        }
+        Ok(date)
+    }
+}
+
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+pub fn seed(date: Date, preset: &str) -> u64 {
+    //! Seed of the board of the day for a preset
+    //!
+    //! FNV-1a hash, which unlike the standard hasher stays the same across
+    //! builds and platforms
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in format!("{} {}", date, preset).bytes() {
+        hash ^= byte as u64;
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    hash
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        let date = Date::parse("2024-02-29").unwrap();
+        assert_eq!(
+            date,
+            Date {
+                year: 2024,
+                month: 2,
+                day: 29
+            }
+        );
+        assert_eq!(date.to_string(), "2024-02-29");
+        assert_eq!(Date::from_days(0).to_string(), "1970-01-01");
+        assert_eq!(date.days(), 19782);
+        assert_eq!(Date::from_days(date.days() + 1).to_string(), "2024-03-01");
+        assert!(Date::parse("2023-02-29").is_err());
+        assert!(Date::parse("2024-13-01").is_err());
+        assert!(Date::parse("yesterday").is_err());
+        assert!(Date::today() > date);
+    }
+
+    #[test]
+    fn seeds() {
+        let date = Date::parse("2024-02-29").unwrap();
+        assert_eq!(seed(date, "beginner"), seed(date, "beginner"));
+        assert_ne!(seed(date, "beginner"), seed(date, "expert"));
+        let next_day = Date::from_days(date.days() + 1);
+        assert_ne!(seed(date, "beginner"), seed(next_day, "beginner"));
+    }
+}
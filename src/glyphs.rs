@@ -0,0 +1,50 @@
+/// Dashed horizontal and vertical borders of boards whose edges wrap around
+pub const WRAP_EDGES: (char, char) = ('┅', '┇');
+
+pub fn count_char(count: i16) -> char {
+    //! Single character for the number of a clean cell
+    //!
+    //! Large neighbourhoods go beyond 9: letters follow the digits (a=10,
+    //! b=11...), and '+' stands for more than 35.
+    //! Negative numbers are parenthesized ('⑴' is -1), and '-' stands for
+    //! less than -20.
+    if count < 0 {
+        return match -count {
+            k if k <= 20 => std::char::from_u32('⑴' as u32 + k as u32 - 1).unwrap(),
+            _ => '-',
+        };
+    }
+    std::char::from_digit(count as u32, 36).unwrap_or('+')
+}
+
+pub fn circled_char(count: u8, negative: bool) -> char {
+    //! Single character for a stack of mines ('②') or of flags ('❷')
+    let (first, max) = if negative { ('❶', 10) } else { ('①', 20) };
+    if count == 0 || count > max {
+        return '#';
+    }
+    std::char::from_u32(first as u32 + count as u32 - 1).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn counts() {
+        assert_eq!(count_char(7), '7');
+        // large neighbourhoods
+        assert_eq!(count_char(24), 'o');
+        assert_eq!(count_char(124), '+');
+        // anti-mines
+        assert_eq!(count_char(-1), '⑴');
+        assert_eq!(count_char(-21), '-');
+    }
+
+    #[test]
+    fn stacks() {
+        assert_eq!(circled_char(2, false), '②');
+        assert_eq!(circled_char(10, true), '❿');
+        assert_eq!(circled_char(11, true), '#');
+    }
+}
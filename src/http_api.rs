@@ -0,0 +1,351 @@
+use crate::minefield::client::Client;
+use crate::protocol::{self, Request};
+use crate::registry::Registry;
+use serde_json::{json, Map, Value};
+use std::io::Read;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Fields of `POST /games`, with the command line option they stand for
+const NUMBER_FIELDS: &[(&str, &str)] = &[
+    ("height", "--height"),
+    ("width", "--width"),
+    ("bombs", "--bombs"),
+    ("layers", "--layers"),
+    ("stack", "--stack"),
+    ("anti_mines", "--anti-mines"),
+    ("lives", "--lives"),
+    ("seed", "--seed"),
+];
+const FLAG_FIELDS: &[(&str, &str)] = &[("liar", "--liar"), ("hex", "--hex"), ("wrap", "--wrap")];
+const NAME_FIELDS: &[(&str, &str)] = &[("shape", "--shape"), ("neighbours", "--neighbours")];
+/// Largest request body, in bytes
+const MAX_BODY: u64 = 4096;
+
+fn error(status: u16, message: &str) -> (u16, Value) {
+    (status, json!({ "error": message }))
+}
+
+fn parse_body(body: &str) -> Result<Map<String, Value>, String> {
+    //! JSON object of a request; an empty body is an empty object
+    if body.trim().is_empty() {
+        return Ok(Map::new());
+    }
+    match serde_json::from_str(body) {
+        Ok(Value::Object(fields)) => Ok(fields),
+        Ok(_) => Err(String::from("The body must be a JSON object")),
+        Err(e) => Err(format!("Invalid JSON: {}", e)),
+    }
+}
+
+fn game_args(fields: &Map<String, Value>) -> Result<Vec<String>, String> {
+    //! Command line options of a new game, e.g. `{"height": 9}` gives
+    //! `--height 9`
+    let mut args = vec![];
+    for (key, value) in fields {
+        let invalid = || format!("Invalid value for {}: {}", key, value);
+        let find = |fields: &[(&str, &'static str)]| {
+            fields.iter().find(|(name, _)| name == key).map(|f| f.1)
+        };
+        if let Some(option) = find(NUMBER_FIELDS) {
+            let number = value.as_u64().ok_or_else(invalid)?;
+            args.push(option.to_string());
+            args.push(number.to_string());
+        } else if let Some(option) = find(FLAG_FIELDS) {
+            if value.as_bool().ok_or_else(invalid)? {
+                args.push(option.to_string());
+            }
+        } else if let Some(option) = find(NAME_FIELDS) {
+            let name = value.as_str().ok_or_else(invalid)?;
+            args.push(option.to_string());
+            args.push(name.to_string());
+        } else {
+            return Err(format!("Unknown field: {}", key));
+        }
+    }
+    Ok(args)
+}
+
+fn parse_coords(fields: &Map<String, Value>) -> Result<Vec<usize>, String> {
+    //! `{"coords": [row, col]}` (`[layer, row, col]` in 3D)
+    let invalid = || String::from("Expected coordinates as \"coords\": [row, col]");
+    let coords = fields.get("coords").and_then(Value::as_array);
+    coords
+        .ok_or_else(invalid)?
+        .iter()
+        .map(|c| c.as_u64().map(|c| c as usize).ok_or_else(invalid))
+        .collect()
+}
+
+fn nest(cells: &[Value], shape: &[usize]) -> Value {
+    //! Splits cells in row order into nested arrays, one level per dimension
+    match shape.split_first() {
+        Some((_, rest)) if !rest.is_empty() => {
+            let size = rest.iter().product::<usize>().max(1);
+            Value::Array(cells.chunks(size).map(|c| nest(c, rest)).collect())
+        }
+        _ => Value::Array(cells.to_vec()),
+    }
+}
+
+pub fn game_json(id: u64, client: &Client) -> Value {
+    //! Visible state of a game
+    //!
+    //! Cells are the tokens of the line protocol (`h`, `f`, `3`, `!x`...),
+    //! nested by rows (and layers), with `null` for holes. The mines of
+    //! finished games are shown.
+    let state = &client.shown_state();
+    let cells: Vec<Value> = state
+        .iter()
+        .map(|(coords, &cell_state)| {
+            if state.exists(&coords) {
+                Value::String(protocol::cellstate_token(cell_state))
+            } else {
+                Value::Null
+            }
+        })
+        .collect();
+    json!({
+        "id": id,
+        "state": format!("{:?}", client.get_game_state()),
+        "bombs": client.num_bombs(),
+        "lives": client.lives_left(),
+        "shape": state.shape(),
+        "cells": nest(&cells, state.shape()),
+    })
+}
+
+pub fn route(registry: &Registry, method: &str, url: &str, body: &str) -> (u16, Value) {
+    //! Answers a request with a status code and a JSON body
+    //!
+    //! - `POST /games` starts a game, e.g. `{"height": 9, "seed": 42}`
+    //! - `GET /games/{id}` shows it
+    //! - `POST /games/{id}/dig`, `/flag` and `/chord` play a move, with
+    //!   `{"coords": [row, col]}`
+    //! - `POST /games/{id}/submit` submits the flags
+    //!
+    //! Idle games are dropped first, so every request sees the same games.
+    registry.expire(Instant::now());
+    let path = url.split('?').next().unwrap_or_default();
+    let parts: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let fields = match parse_body(body) {
+        Ok(fields) => fields,
+        Err(e) => return error(400, &e),
+    };
+    if parts == ["games"] {
+        if method != "POST" {
+            return error(405, "Use POST to start a game");
+        }
+        let created = game_args(&fields).and_then(|args| registry.create(&args));
+        return match created {
+            Ok(id) => match registry.play(&Request::State(id), |id, s| game_json(id, &s.client)) {
+                Ok(game) => (201, game),
+                Err(e) => error(404, &e),
+            },
+            Err(e) => error(400, &e),
+        };
+    }
+    let id = match parts.as_slice() {
+        ["games", id] | ["games", id, _] => match id.parse() {
+            Ok(id) => id,
+            Err(_) => return error(404, &format!("Invalid game id: {}", id)),
+        },
+        _ => return error(404, &format!("Not found: {}", path)),
+    };
+    let request = match (method, parts.get(2)) {
+        ("GET", None) => Ok(Request::State(id)),
+        ("POST", Some(&"dig")) => parse_coords(&fields).map(|c| Request::Dig(id, c, None)),
+        ("POST", Some(&"flag")) => parse_coords(&fields).map(|c| Request::Flag(id, c, None)),
+        ("POST", Some(&"chord")) => parse_coords(&fields).map(|c| Request::Chord(id, c, None)),
+        ("POST", Some(&"submit")) => Ok(Request::Submit(id)),
+        (_, None)
+        | (_, Some(&"dig"))
+        | (_, Some(&"flag"))
+        | (_, Some(&"chord"))
+        | (_, Some(&"submit")) => return error(405, &format!("Method not allowed: {}", method)),
+        (_, Some(_)) => return error(404, &format!("Not found: {}", path)),
+    };
+    if !registry.contains(id) {
+        return error(404, &format!("Unknown game: {}", id));
+    }
+    match request.and_then(|request| registry.play(&request, |id, s| game_json(id, &s.client))) {
+        Ok(game) => (200, game),
+        Err(e) => error(400, &e),
+    }
+}
+
+pub fn serve(server: tiny_http::Server, registry: Arc<Registry>) {
+    //! Answers HTTP requests, each in its own thread, until the server is
+    //! dropped
+    //!
+    //! Bodies longer than `MAX_BODY` bytes are refused with 413.
+    for mut request in server.incoming_requests() {
+        let registry = registry.clone();
+        std::thread::spawn(move || {
+            let mut body = String::new();
+            let read = request
+                .as_reader()
+                .take(MAX_BODY + 1)
+                .read_to_string(&mut body);
+            let (status, json) = match read {
+                Ok(len) if len as u64 > MAX_BODY => error(413, "The body is too large"),
+                Ok(_) => route(&registry, request.method().as_str(), request.url(), &body),
+                Err(e) => error(400, &format!("Cannot read the body: {}", e)),
+            };
+            let header =
+                tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..])
+                    .unwrap();
+            let response = tiny_http::Response::from_string(json.to_string())
+                .with_status_code(status)
+                .with_header(header);
+            if let Err(e) = request.respond(response) {
+                eprintln!("Cannot answer: {}", e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    #[test]
+    fn routes() {
+        let registry = Registry::new(None);
+        let (status, game) = route(
+            &registry,
+            "POST",
+            "/games",
+            r#"{"height": 2, "width": 3, "bombs": 0, "seed": 7, "wrap": false}"#,
+        );
+        assert_eq!(status, 201);
+        assert_eq!(
+            game,
+            json!({
+                "id": 1,
+                "state": "Running",
+                "bombs": 0,
+                "lives": 1,
+                "shape": [2, 3],
+                "cells": [["h", "h", "h"], ["h", "h", "h"]],
+            })
+        );
+        let (status, game) = route(&registry, "POST", "/games/1/flag", r#"{"coords": [0, 1]}"#);
+        assert_eq!(status, 200);
+        assert_eq!(game["cells"], json!([["h", "f", "h"], ["h", "h", "h"]]));
+        let (_, game) = route(&registry, "POST", "/games/1/dig", r#"{"coords": [1, 2]}"#);
+        assert_eq!(game["cells"], json!([["0", "f", "0"], ["0", "0", "0"]]));
+        let (_, game) = route(&registry, "POST", "/games/1/submit", "");
+        assert_eq!(game["state"], "Lost");
+        assert_eq!(route(&registry, "GET", "/games/1", "").1, game);
+        assert_eq!(
+            route(&registry, "POST", "/games/1/dig", r#"{"coords": [0, 0]}"#),
+            error(400, "Game 1 is over")
+        );
+
+        let (_, cube) = route(
+            &registry,
+            "POST",
+            "/games",
+            r#"{"layers": 2, "height": 1, "width": 2, "bombs": 0}"#,
+        );
+        assert_eq!(cube["cells"], json!([[["h", "h"]], [["h", "h"]]]));
+        assert_eq!(
+            route(&registry, "POST", "/games/2/dig", r#"{"coords": [0, 0]}"#).0,
+            400
+        );
+        assert_eq!(route(&registry, "POST", "/games/2/dig", "{}").0, 400);
+        assert_eq!(
+            route(&registry, "POST", "/games", r#"{"height": -1}"#).0,
+            400
+        );
+        assert_eq!(
+            route(&registry, "POST", "/games", r#"{"colour": "red"}"#).0,
+            400
+        );
+        assert_eq!(
+            route(&registry, "POST", "/games", r#"{"shape": "blob"}"#).0,
+            400
+        );
+        let huge = r#"{"height": 4294967296, "width": 4294967297}"#;
+        assert_eq!(route(&registry, "POST", "/games", huge).0, 400);
+        let (status, error) = route(&registry, "POST", "/games", r#"{"bombs": 10001}"#);
+        assert_eq!(status, 400);
+        assert_eq!(
+            error["error"],
+            "Boards have at most 100 rows, 100 columns, 10 layers and 10000 bombs"
+        );
+        let file = r#"{"shape": "--shape-file", "neighbours": "/etc/passwd"}"#;
+        assert_eq!(route(&registry, "POST", "/games", file).0, 400);
+        assert_eq!(route(&registry, "POST", "/games", "[1, 2]").0, 400);
+        assert_eq!(route(&registry, "POST", "/games", "{").0, 400);
+        assert_eq!(route(&registry, "GET", "/games", "").0, 405);
+        assert_eq!(route(&registry, "DELETE", "/games/2", "").0, 405);
+        assert_eq!(route(&registry, "GET", "/games/9", "").0, 404);
+        assert_eq!(route(&registry, "GET", "/games/x", "").0, 404);
+        assert_eq!(route(&registry, "POST", "/games/2/explode", "").0, 404);
+        assert_eq!(route(&registry, "GET", "/", "").0, 404);
+        assert_eq!(registry.len(), 2);
+    }
+
+    #[test]
+    fn seeds() {
+        let registry = Registry::new(None);
+        let body = r#"{"height": 6, "width": 6, "bombs": 8, "seed": 42}"#;
+        route(&registry, "POST", "/games", body);
+        route(&registry, "POST", "/games", body);
+        route(&registry, "POST", "/games", &body.replace("42", "43"));
+        let board = |id| {
+            registry
+                .play(&Request::State(id), |_, s| s.client.minefield.to_string())
+                .unwrap()
+        };
+        assert_eq!(board(1), board(2));
+        assert_ne!(board(1), board(3));
+        let (_, first) = route(&registry, "POST", "/games/1/dig", r#"{"coords": [2, 3]}"#);
+        let (_, second) = route(&registry, "POST", "/games/2/dig", r#"{"coords": [2, 3]}"#);
+        assert_ne!(first["cells"][2][3], "h");
+        assert_eq!(first["cells"], second["cells"]);
+    }
+
+    #[test]
+    fn http() {
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let address = server.server_addr().to_ip().unwrap();
+        let registry = Arc::new(Registry::new(Some(Duration::from_secs(60))));
+        let server_registry = registry.clone();
+        std::thread::spawn(move || serve(server, server_registry));
+        let body = r#"{"height": 1, "width": 2, "bombs": 0}"#;
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "POST /games HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 201"));
+        assert!(response.contains("application/json"));
+        assert!(response.ends_with(r#""shape":[1,2],"state":"Running"}"#));
+        assert_eq!(registry.len(), 1);
+
+        let body = format!(r#"{{"height": 1, "width": 2{}}}"#, " ".repeat(5000));
+        let mut stream = std::net::TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "POST /games HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 413"));
+        assert!(response.ends_with(r#"{"error":"The body is too large"}"#));
+        assert_eq!(registry.len(), 1);
+    }
+}
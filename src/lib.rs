@@ -1,3 +1,15 @@
 pub mod ascii_client;
+pub mod bot;
+pub mod daily;
+pub mod glyphs;
+pub mod http_api;
 pub mod minefield;
-pub mod tui_client;
\ No newline at end of file
+pub mod options;
+pub mod protocol;
+pub mod race;
+pub mod referee;
+pub mod registry;
+pub mod scores;
+pub mod server;
+pub mod spectate;
+pub mod tui_client;
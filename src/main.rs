@@ -67,4 +67,4 @@ fn main() {
     // Show the cursor again before we exit.
     write!(stdout, "{}", termion::cursor::Show).unwrap();
 }
-*/
\ No newline at end of file
+*/
@@ -1,14 +1,49 @@
 use super::field::{Cell, Minefield};
 use super::grid;
+use std::sync::mpsc::{self, Receiver};
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CellState {
     Revealed(Cell),
     Hidden,
     Flagged,
+    /// Several flags, on minefields whose cells hold several bombs
+    Flags(u8),
     Marked,
+    /// Mine dug while the player had lives left
+    Exploded(Cell),
 }
 
+impl CellState {
+    pub fn num_flags(self) -> u8 {
+        //! Number of mines marked on the cell; exploded mines count as flagged
+        match self {
+            CellState::Flagged => 1,
+            CellState::Flags(k) => k,
+            CellState::Exploded(cell) => cell.num_mines(),
+            _ => 0,
+        }
+    }
+
+    pub fn next_flag(self, max_flags: u8) -> CellState {
+        //! State after toggling the flag of the cell
+        //!
+        //! When cells can hold several bombs, flags are added one at a time,
+        //! up to `max_flags`, then removed. Revealed cells are left as is.
+        match self {
+            CellState::Hidden => CellState::Flagged,
+            CellState::Flagged if max_flags > 1 => CellState::Flags(2),
+            CellState::Flags(k) if k < max_flags => CellState::Flags(k + 1),
+            CellState::Flagged | CellState::Flags(_) => CellState::Hidden,
+            other => other,
+        }
+    }
+}
+
+/// Points lost for every mine dug
+pub const STRIKE_PENALTY: usize = 10;
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum GameState {
     Running,
@@ -16,10 +51,38 @@ pub enum GameState {
     Won,
 }
 
+/// Change of a game, for frontends that follow it incrementally
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// First move of the game
+    TimerStarted(Instant),
+    /// Cell dug, with what it shows: `Revealed`, or `Exploded` for a mine
+    /// dug with lives left
+    Revealed(Vec<usize>, CellState),
+    /// Flags of a cell toggled, with its new state
+    FlagChanged(Vec<usize>, CellState),
+    /// The game was won or lost
+    GameOver(GameState),
+}
+
+/// Function called with every event of a game
+pub type Listener = Box<dyn FnMut(&Event) + Send>;
+
 pub struct Client {
     pub minefield: Minefield,
     state: grid::Grid<CellState>,
     game_state: GameState,
+    /// Number of mines that can be dug before losing
+    lives: usize,
+    strikes: usize,
+    /// Clean cells dug by the player
+    cleared: usize,
+    /// Time of the first move
+    start_time: Option<Instant>,
+    last_move_time: Option<Instant>,
+    /// Time the game was won or lost
+    end_time: Option<Instant>,
+    listeners: Vec<Listener>,
 }
 
 // split off an arbitrary element from a (non-empty) set
@@ -34,13 +97,54 @@ where
 
 impl Client {
     pub fn from_minefield(field: Minefield) -> Client {
-        let (height, width) = field.shape();
-        let state_init_data = vec![CellState::Hidden; height * width];
-        let state = grid::Grid::new(height, width, state_init_data).unwrap();
+        let state = field.layout(CellState::Hidden);
         Client {
             minefield: field,
-            state: state,
+            state,
             game_state: GameState::Running,
+            lives: 1,
+            strikes: 0,
+            cleared: 0,
+            start_time: None,
+            last_move_time: None,
+            end_time: None,
+            listeners: vec![],
+        }
+    }
+
+    pub fn with_lives(mut self, lives: usize) -> Client {
+        //! Lets the player dig `lives - 1` mines and keep playing
+        self.lives = lives.max(1);
+        self
+    }
+
+    pub fn add_listener(&mut self, listener: impl FnMut(&Event) + Send + 'static) {
+        //! Calls `listener` with every change of the game, as it happens
+        self.listeners.push(Box::new(listener));
+    }
+
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        //! Sends every change of the game to the receiver, e.g. to follow the
+        //! game from another thread
+        let (events, receiver) = mpsc::channel();
+        self.add_listener(move |event| {
+            let _ = events.send(event.clone());
+        });
+        receiver
+    }
+
+    fn emit(&mut self, event: Event) {
+        for listener in &mut self.listeners {
+            listener(&event);
+        }
+    }
+
+    fn finish(&mut self, game_state: GameState) {
+        //! Ends a running game
+        if self.game_state == GameState::Running {
+            self.game_state = game_state;
+            self.end_time = Some(Instant::now());
+            self.emit(Event::GameOver(game_state));
         }
     }
 
@@ -49,93 +153,200 @@ impl Client {
         Self::from_minefield(field)
     }
 
-    pub fn query_update(&mut self, row: usize, col: usize) -> Cell {
-        let cell = self.minefield.dig(row, col).unwrap();
-        self.state.set(row, col, CellState::Revealed(cell)).unwrap();
-        if cell == Cell::Bomb {
-            self.game_state = GameState::Lost;
+    pub fn query_update(&mut self, coords: &[usize]) -> Cell {
+        //! Digs a cell
+        //!
+        //! Digging a mine costs a life: the mine explodes and the game goes
+        //! on while lives are left, and is lost otherwise
+        let cell = self.minefield.dig(coords).unwrap();
+        match self.state.get(coords).unwrap() {
+            CellState::Revealed(_) | CellState::Exploded(_) => return cell,
+            _ => (),
+        }
+        if !cell.is_mine() {
+            self.cleared += 1;
+        } else {
+            self.strikes += 1;
+            if self.strikes < self.lives {
+                self.state.set(coords, CellState::Exploded(cell)).unwrap();
+                self.emit(Event::Revealed(coords.to_vec(), CellState::Exploded(cell)));
+                return cell;
+            }
+        }
+        self.uncover(coords)
+    }
+
+    fn uncover(&mut self, coords: &[usize]) -> Cell {
+        //! Shows the content of a cell; the game is lost on a mine
+        let cell = self.minefield.dig(coords).unwrap();
+        self.state.set(coords, CellState::Revealed(cell)).unwrap();
+        self.emit(Event::Revealed(coords.to_vec(), CellState::Revealed(cell)));
+        if cell.is_mine() {
+            self.finish(GameState::Lost);
         }
         cell
     }
 
+    pub fn lives(&self) -> usize {
+        self.lives
+    }
+
+    pub fn lives_left(&self) -> usize {
+        self.lives.saturating_sub(self.strikes)
+    }
+
+    pub fn strikes(&self) -> usize {
+        self.strikes
+    }
+
+    pub fn score(&self) -> usize {
+        //! One point per cleared cell, plus one per mine on a won game, minus
+        //! `STRIKE_PENALTY` per mine dug
+        let bonus = match self.game_state {
+            GameState::Won => self.num_bombs(),
+            _ => 0,
+        };
+        (self.cleared + bonus).saturating_sub(STRIKE_PENALTY * self.strikes)
+    }
+
+    pub fn progress(&self) -> usize {
+        //! Percentage of the clean cells dug so far; 100 once won
+        if self.game_state == GameState::Won {
+            return 100;
+        }
+        let clean = self
+            .state
+            .iter()
+            .filter(|(coords, _)| {
+                self.state.exists(coords)
+                    && self.minefield.dig(coords).is_some_and(|c| !c.is_mine())
+            })
+            .count();
+        match clean {
+            0 => 100,
+            clean => 100 * self.cleared / clean,
+        }
+    }
+
     pub fn num_bombs(&self) -> usize {
-        return self.minefield.num_bombs();
+        self.minefield.num_bombs()
     }
 
-    pub fn query_smart(&mut self, row: usize, col: usize) -> GameState {
+    pub fn query_smart(&mut self, coords: &[usize]) -> GameState {
+        if !self.state.exists(coords) {
+            return self.game_state;
+        }
+        self.record_move();
         let mut set = std::collections::HashSet::new();
-        set.insert((row, col));
+        set.insert(coords.to_vec());
         while !set.is_empty() {
-            let (i, j) = pop_from_set(&mut set);
-            // print!("Query: {},{}", i, j);
-            let c = self.query_update(i, j);
+            let cell = pop_from_set(&mut set);
+            // print!("Query: {:?}", cell);
+            let c = self.query_update(&cell);
             // println!("-> {:?}", c);
             // println!("Set: {:?}", set);
-            if c == Cell::Clean(0) {
-                for (i_n, j_n) in self.state.neighbours8(i, j) {
-                    if self.state.get(i_n, j_n).unwrap() == CellState::Hidden {
-                        set.insert((i_n, j_n));
+            if c == Cell::Clean(0) && self.minefield.safe_zeros() {
+                for neighbour in self.state.neighbours(&cell) {
+                    if self.state.get(&neighbour).unwrap() == CellState::Hidden {
+                        set.insert(neighbour);
                     }
                 }
             }
         }
-        GameState::Running
+        self.game_state
     }
 
-    pub fn reveal(&mut self, all: bool) {
-        let original_game_state = self.game_state;
-        let (h, w) = self.state.shape();
-        for i in 0..h {
-            for j in 0..w {
-                let cell_state = self.state.get(i, j).unwrap();
-                if cell_state == CellState::Hidden
-                    || ((cell_state == CellState::Flagged || cell_state == CellState::Marked)
-                        && all)
-                {
-                    self.query_update(i, j);
+    pub fn chord(&mut self, coords: &[usize]) -> GameState {
+        //! Digs all the hidden neighbours of a revealed number
+        //!
+        //! Nothing happens unless the number of flags around the cell
+        //! matches the number it shows, or if numbers do not count the mines
+        //! around (anti-mines, liars)
+        if !self.minefield.safe_zeros() {
+            return self.game_state;
+        }
+        self.record_move();
+        if let Some(CellState::Revealed(Cell::Clean(num))) = self.state.get(coords) {
+            let neighbours = self.state.neighbours(coords);
+            let num_flags: i16 = neighbours
+                .iter()
+                .map(|n| self.state.get(n).unwrap().num_flags() as i16)
+                .sum();
+            if num_flags == num {
+                for neighbour in neighbours {
+                    if self.state.get(&neighbour) == Some(CellState::Hidden) {
+                        self.query_smart(&neighbour);
+                    }
                 }
             }
         }
-        if original_game_state == GameState::Won {
-            // preserve win even if cells were revealed
-            self.game_state = GameState::Won;
-        }
+        self.game_state
     }
 
-    pub fn get_flag_locations(&self) -> Vec<(usize, usize)> {
-        let mut flag_locations = vec![];
-        let (h, w) = self.state.shape();
-        for i in 0..h {
-            for j in 0..w {
-                let cell_state = self.state.get(i, j).unwrap();
-                if cell_state == CellState::Flagged {
-                    flag_locations.push((i, j));
-                }
+    pub fn reveal(&mut self, all: bool) {
+        //! Shows the hidden cells (and the flagged ones if `all`); a won game
+        //! stays won
+        for index in 0..self.state.len() {
+            let coords = self.state.index_rev(index).unwrap();
+            let cell_state = self.state.get(&coords).unwrap();
+            if !self.state.exists(&coords) {
+                continue;
+            }
+            let marked = matches!(
+                cell_state,
+                CellState::Flagged | CellState::Flags(_) | CellState::Marked
+            );
+            if cell_state == CellState::Hidden || (marked && all) {
+                self.uncover(&coords);
             }
         }
-        flag_locations
+    }
+
+    pub fn get_flag_locations(&self) -> Vec<Vec<usize>> {
+        //! Flagged cells, listed once per flag
+        self.state
+            .iter()
+            .flat_map(|(coords, cell_state)| {
+                std::iter::repeat_n(coords, cell_state.num_flags() as usize)
+            })
+            .collect()
     }
 
     pub fn submit(&mut self) -> Result<GameState, String> {
         if self.game_state != GameState::Running {
-            return Err(format!("Game state must be 'Running' to submit, current state is: {:?}", self.game_state));
+            return Err(format!(
+                "Game state must be 'Running' to submit, current state is: {:?}",
+                self.game_state
+            ));
         }
+        self.record_move();
         let flag_locations = self.get_flag_locations();
         if self.minefield.submit(&flag_locations) {
-            self.game_state = GameState::Won;
+            self.finish(GameState::Won);
         } else {
-            self.game_state = GameState::Lost;
+            self.finish(GameState::Lost);
         }
         Ok(self.game_state)
     }
 
-    pub fn flag(&mut self, row: usize, col: usize) -> CellState {
-        let new_state = match self.state.get(row, col).unwrap() {
-            CellState::Hidden => CellState::Flagged,
-            CellState::Flagged => CellState::Hidden,
-            other => other,
+    pub fn flag(&mut self, coords: &[usize]) -> CellState {
+        //! Toggles the flag of a hidden cell
+        //!
+        //! When cells can hold several bombs, flags are added one at a time,
+        //! up to the largest number of bombs per cell, then removed.
+        if self.state.exists(coords) {
+            self.record_move();
+        }
+        let max_flags = self.minefield.max_per_cell();
+        let old_state = self.state.get(coords).unwrap();
+        let new_state = match old_state {
+            other if !self.state.exists(coords) => other,
+            other => other.next_flag(max_flags),
         };
-        self.state.set(row, col, new_state).unwrap();
+        self.state.set(coords, new_state).unwrap();
+        if new_state != old_state {
+            self.emit(Event::FlagChanged(coords.to_vec(), new_state));
+        }
         new_state
     }
 
@@ -143,31 +354,87 @@ impl Client {
         &self.state
     }
 
+    pub fn shown_state(&self) -> grid::Grid<CellState> {
+        //! State shown to remote players: once the game is over, the mines
+        //! left hidden or flagged are shown too
+        let mut state = self.state.map(|&cell_state| cell_state);
+        if self.game_state == GameState::Running {
+            return state;
+        }
+        for (coords, cell_state) in self.state.iter() {
+            let hidden = !matches!(cell_state, CellState::Revealed(_) | CellState::Exploded(_));
+            match self.minefield.dig(&coords) {
+                Some(cell) if hidden && cell.is_mine() => {
+                    state.set(&coords, CellState::Revealed(cell)).unwrap()
+                }
+                _ => (),
+            }
+        }
+        state
+    }
+
     pub fn get_game_state(&self) -> GameState {
         self.game_state
     }
+
+    fn record_move(&mut self) {
+        //! Starts the clock on the first move
+        let now = Instant::now();
+        if self.start_time.is_none() {
+            self.start_time = Some(now);
+            self.emit(Event::TimerStarted(now));
+        }
+        self.last_move_time = Some(now);
+    }
+
+    pub fn start_time(&self) -> Option<Instant> {
+        self.start_time
+    }
+
+    pub fn last_move_time(&self) -> Option<Instant> {
+        self.last_move_time
+    }
+
+    pub fn end_time(&self) -> Option<Instant> {
+        self.end_time
+    }
+
+    pub fn elapsed(&self, now: Instant) -> Duration {
+        //! Time spent since the first move, zero before it, and stopped at
+        //! the end of the game
+        let end = self.end_time.map_or(now, |end| end.min(now));
+        match self.start_time {
+            Some(start_time) => end.saturating_duration_since(start_time),
+            None => Duration::from_secs(0),
+        }
+    }
+
+    pub fn time_out(&mut self) {
+        //! Loses a running game whose time is up
+        self.finish(GameState::Lost);
+    }
 }
 
 #[cfg(test)]
 mod test {
-    use super::*;
     use super::super::field;
+    use super::*;
 
     #[test]
     fn scenario_0() {
         let mut client = Client::new_random(10, 10, 0);
-        assert_eq!(client.flag(0, 0), CellState::Flagged);
-        assert_eq!(client.flag(0, 0), CellState::Hidden);
-        assert_eq!(client.query_update(0, 0), Cell::Clean(0));
+        assert_eq!(client.flag(&[0, 0]), CellState::Flagged);
+        assert_eq!(client.flag(&[0, 0]), CellState::Hidden);
+        assert_eq!(client.query_update(&[0, 0]), Cell::Clean(0));
         assert_eq!(
-            client.get_state().get(0, 0).unwrap(),
+            client.get_state().get(&[0, 0]).unwrap(),
             CellState::Revealed(Cell::Clean(0))
         );
-        assert_eq!(client.query_smart(0, 0), GameState::Running);
+        assert_eq!(client.query_smart(&[0, 0]), GameState::Running);
         for i in 0..10 {
             for j in 0..10 {
                 assert_eq!(
-                    client.get_state().get(i, j),
+                    client.get_state().get(&[i, j]),
                     Some(CellState::Revealed(Cell::Clean(0)))
                 );
             }
@@ -179,17 +446,17 @@ mod test {
         let mut c = Client::from_minefield(minefield);
         // ensure state is pristine when client is created
         let state = c.get_state();
-        let (height, width) = state.shape();
+        let (height, width) = state.layer_shape();
         for i in 0..height {
             for j in 0..width {
-                assert_eq!(state.get(i, j), Some(CellState::Hidden));
+                assert_eq!(state.get(&[i, j]), Some(CellState::Hidden));
             }
         }
         // ensure game state is 'Running'
         assert_eq!(c.get_game_state(), GameState::Running);
 
-        for (i, j) in bomb_locations {
-            c.flag(i, j);
+        for bomb in bomb_locations {
+            c.flag(&bomb);
         }
         assert_eq!(c.submit(), Ok(GameState::Won));
         assert_eq!(c.get_game_state(), GameState::Won);
@@ -198,6 +465,160 @@ mod test {
         assert!(c.submit().is_err());
     }
 
+    #[test]
+    fn chord() {
+        // X22X2
+        // 2X33X
+        // 12X21
+        let (minefield, _) = field::test::generate_test_minefield();
+        let mut c = Client::from_minefield(minefield);
+        assert_eq!(c.query_update(&[2, 0]), Cell::Clean(1));
+        assert_eq!(c.start_time(), None);
+        // no flag around: chording does nothing, but starts the clock
+        assert_eq!(c.chord(&[2, 0]), GameState::Running);
+        assert!(c.start_time().is_some());
+        assert_eq!(c.get_state().get(&[1, 0]), Some(CellState::Hidden));
+        // chording a hidden cell does nothing either
+        c.chord(&[2, 1]);
+        assert_eq!(c.get_state().get(&[2, 1]), Some(CellState::Hidden));
+
+        c.flag(&[1, 1]);
+        assert_eq!(c.chord(&[2, 0]), GameState::Running);
+        assert_eq!(
+            c.get_state().get(&[1, 0]),
+            Some(CellState::Revealed(Cell::Clean(2)))
+        );
+        assert_eq!(
+            c.get_state().get(&[2, 1]),
+            Some(CellState::Revealed(Cell::Clean(2)))
+        );
+        assert_eq!(c.get_state().get(&[1, 1]), Some(CellState::Flagged));
+
+        // a misplaced flag makes chording explode
+        c.flag(&[0, 1]);
+        assert_eq!(c.chord(&[1, 0]), GameState::Lost);
+        assert_eq!(
+            c.get_state().get(&[0, 0]),
+            Some(CellState::Revealed(Cell::Bomb))
+        );
+    }
+
+    #[test]
+    fn flood_fill_3d() {
+        // a single bomb in a corner of a 3x3x3 cube
+        let layout = grid::Grid::with_dims(&[3, 3, 3], vec![(); 27]).unwrap();
+        let mut c = Client::from_minefield(Minefield::from_layout(&layout, 0).unwrap());
+        c.query_smart(&[2, 2, 2]);
+        assert!(c
+            .get_state()
+            .iter()
+            .all(|(_, &s)| s == CellState::Revealed(Cell::Clean(0))));
+
+        let mut c = Client::from_minefield(Minefield::from_layout(&layout, 27).unwrap());
+        c.flag(&[1, 1, 1]);
+        assert_eq!(c.get_flag_locations(), vec![vec![1, 1, 1]]);
+        assert_eq!(c.query_smart(&[0, 0, 0]), GameState::Lost);
+        assert_eq!(c.get_game_state(), GameState::Lost);
+    }
+
+    #[test]
+    fn stacked_mines() {
+        // 2 bombs in the corner, 1 next to it
+        // 2X 1X 2
+        // 3  3  1
+        let layout = grid::Grid::new(2, 3, vec![(); 6]).unwrap();
+        let mut field = Minefield::with_stacks(&layout, 0, 3).unwrap();
+        field = field
+            .with_bombs(&[vec![0, 0], vec![0, 0], vec![0, 1]])
+            .unwrap();
+        let mut c = Client::from_minefield(field);
+        assert_eq!(c.query_update(&[1, 0]), Cell::Clean(3));
+        assert_eq!(c.query_update(&[0, 2]), Cell::Clean(1));
+
+        // flags cycle up to 3, then back to hidden
+        assert_eq!(c.flag(&[0, 0]), CellState::Flagged);
+        assert_eq!(c.flag(&[0, 0]), CellState::Flags(2));
+        assert_eq!(c.flag(&[0, 0]), CellState::Flags(3));
+        assert_eq!(c.flag(&[0, 0]), CellState::Hidden);
+        c.flag(&[0, 0]);
+        c.flag(&[0, 0]);
+        c.flag(&[0, 1]);
+        assert_eq!(
+            c.get_flag_locations(),
+            vec![vec![0, 0], vec![0, 0], vec![0, 1]]
+        );
+        // chording counts every flag
+        c.chord(&[1, 0]);
+        assert_eq!(
+            c.get_state().get(&[1, 1]),
+            Some(CellState::Revealed(Cell::Clean(3)))
+        );
+        assert_eq!(c.submit(), Ok(GameState::Won));
+
+        let mut c = Client::from_minefield(
+            Minefield::with_stacks(&layout, 0, 3)
+                .unwrap()
+                .with_bombs(&[vec![0, 0], vec![0, 0]])
+                .unwrap(),
+        );
+        c.flag(&[0, 0]);
+        assert_eq!(c.submit(), Ok(GameState::Lost));
+        assert_eq!(c.query_update(&[0, 0]), Cell::Mines(2));
+    }
+
+    #[test]
+    fn holes() {
+        // a ring of cells around a hole, without bombs
+        let layout = grid::Grid::new(3, 3, vec![(); 9])
+            .unwrap()
+            .with_mask(&[true, true, true, true, false, true, true, true, true])
+            .unwrap();
+        let mut c = Client::from_minefield(Minefield::from_layout(&layout, 0).unwrap());
+        assert_eq!(c.flag(&[1, 1]), CellState::Hidden);
+        assert!(c.get_flag_locations().is_empty());
+        assert_eq!(c.query_smart(&[1, 1]), GameState::Running);
+        assert_eq!(c.get_state().get(&[0, 0]), Some(CellState::Hidden));
+        c.query_smart(&[0, 0]);
+        assert_eq!(
+            c.get_state().get(&[2, 2]),
+            Some(CellState::Revealed(Cell::Clean(0)))
+        );
+        c.reveal(true);
+        assert_eq!(c.get_state().get(&[1, 1]), Some(CellState::Hidden));
+    }
+
+    #[test]
+    fn anti_mines() {
+        // X 0 O -1 0: the bomb and the anti-mine cancel out
+        let layout = grid::Grid::new(1, 5, vec![(); 5]).unwrap();
+        let minefield = Minefield::from_layout(&layout, 0)
+            .unwrap()
+            .with_bombs(&[vec![0, 0]])
+            .unwrap()
+            .with_anti_mines(&[vec![0, 2]])
+            .unwrap();
+        let mut c = Client::from_minefield(minefield);
+        assert_eq!(c.num_bombs(), 2);
+        // a 0 does not open its neighbours, nor does chording
+        c.query_smart(&[0, 1]);
+        assert_eq!(c.get_state().get(&[0, 0]), Some(CellState::Hidden));
+        assert_eq!(c.chord(&[0, 1]), GameState::Running);
+        assert_eq!(c.get_state().get(&[0, 2]), Some(CellState::Hidden));
+        assert_eq!(c.query_update(&[0, 3]), Cell::Clean(-1));
+        c.flag(&[0, 0]);
+        c.flag(&[0, 2]);
+        assert_eq!(c.submit(), Ok(GameState::Won));
+
+        // anti-mines explode too
+        let minefield = Minefield::from_layout(&layout, 0)
+            .unwrap()
+            .with_anti_mines(&[vec![0, 4]])
+            .unwrap();
+        let mut c = Client::from_minefield(minefield);
+        assert_eq!(c.query_update(&[0, 4]), Cell::AntiMine);
+        assert_eq!(c.get_game_state(), GameState::Lost);
+    }
+
     #[test]
     fn reveal() {
         let (minefield, _) = field::test::generate_test_minefield();
@@ -207,18 +628,127 @@ mod test {
         assert_eq!(c.get_game_state(), GameState::Lost);
 
         // mark some cells
-        let marked_cells:&[(usize,usize)] = &[(1,1), (2,4)];
-        for &(i, j) in marked_cells {
-            c.flag(i, j);
+        let marked_cells = [vec![1, 1], vec![2, 4]];
+        for cell in &marked_cells {
+            c.flag(cell);
         }
         // revealing the grid (all=false) reveals all Hidden cells (not flagged and marked)
         c.reveal(false);
-        for (i, j, &val) in c.get_state().iter() {
-            if marked_cells.contains(&(i, j)) {
+        for (coords, &val) in c.get_state().iter() {
+            if marked_cells.contains(&coords) {
                 assert_eq!(val, CellState::Flagged);
             } else {
                 assert!(matches!(val, CellState::Revealed(_)));
             }
         }
     }
+
+    #[test]
+    fn clock() {
+        let mut c = Client::new_random(3, 3, 0);
+        let before = Instant::now();
+        assert_eq!(c.start_time(), None);
+        assert_eq!(c.elapsed(before), Duration::from_secs(0));
+        c.flag(&[0, 0]);
+        let start_time = c.start_time().unwrap();
+        assert!(start_time >= before);
+        c.query_smart(&[2, 2]);
+        assert_eq!(c.start_time(), Some(start_time));
+        assert!(c.last_move_time().unwrap() >= start_time);
+        let later = start_time + Duration::from_secs(5);
+        assert_eq!(c.elapsed(later), Duration::from_secs(5));
+        assert_eq!(c.end_time(), None);
+        c.time_out();
+        assert_eq!(c.get_game_state(), GameState::Lost);
+        let end_time = c.end_time().unwrap();
+        let elapsed = end_time - start_time;
+        assert_eq!(c.elapsed(end_time + Duration::from_secs(60)), elapsed);
+    }
+
+    #[test]
+    fn lives() {
+        // X22X2
+        // 2X33X
+        // 12X21
+        let (minefield, bomb_locations) = field::test::generate_test_minefield();
+        let mut c = Client::from_minefield(minefield).with_lives(3);
+        assert_eq!(c.lives(), 3);
+        for cell in &[[2, 0], [2, 1], [1, 0], [0, 1], [0, 2], [1, 2], [1, 3]] {
+            c.query_update(cell);
+        }
+        assert_eq!(c.score(), 7);
+        // 7 of the 10 clean cells
+        assert_eq!(c.progress(), 70);
+        assert_eq!(c.query_update(&[0, 0]), Cell::Bomb);
+        assert_eq!(c.get_game_state(), GameState::Running);
+        assert_eq!(
+            c.get_state().get(&[0, 0]),
+            Some(CellState::Exploded(Cell::Bomb))
+        );
+        // digging it again costs nothing
+        c.query_smart(&[0, 0]);
+        assert_eq!((c.strikes(), c.lives_left()), (1, 2));
+        // exploded bombs count as flags, and cannot be unflagged
+        assert_eq!(c.flag(&[0, 0]), CellState::Exploded(Cell::Bomb));
+        assert_eq!(c.get_flag_locations(), vec![vec![0, 0]]);
+        for bomb in &bomb_locations[1..] {
+            c.flag(bomb);
+        }
+        assert_eq!(c.submit(), Ok(GameState::Won));
+        // 7 cleared cells and 5 bombs, minus one strike
+        assert_eq!(c.score(), 2);
+        assert_eq!(c.progress(), 100);
+
+        let (minefield, _) = field::test::generate_test_minefield();
+        let mut c = Client::from_minefield(minefield).with_lives(2);
+        c.query_update(&[0, 0]);
+        c.query_update(&[1, 1]);
+        assert_eq!(c.get_game_state(), GameState::Lost);
+        assert_eq!(
+            c.get_state().get(&[1, 1]),
+            Some(CellState::Revealed(Cell::Bomb))
+        );
+        assert_eq!(c.lives_left(), 0);
+        // the end of game display does not cost lives nor give points
+        c.reveal(true);
+        assert_eq!((c.strikes(), c.score()), (2, 0));
+    }
+
+    #[test]
+    fn events() {
+        let (minefield, _) = field::test::generate_test_minefield();
+        let mut c = Client::from_minefield(minefield).with_lives(2);
+        let events = c.subscribe();
+        let flags = std::sync::Arc::new(std::sync::Mutex::new(0));
+        let counter = flags.clone();
+        c.add_listener(move |event| {
+            if let Event::FlagChanged(..) = event {
+                *counter.lock().unwrap() += 1;
+            }
+        });
+        c.flag(&[0, 0]);
+        c.flag(&[0, 0]);
+        c.query_smart(&[0, 1]);
+        c.query_smart(&[1, 1]);
+        // digging a revealed cell changes nothing
+        c.query_smart(&[0, 1]);
+        c.query_smart(&[2, 2]);
+        c.reveal(false);
+        let events: Vec<Event> = events.try_iter().collect();
+        assert!(matches!(events[0], Event::TimerStarted(_)));
+        assert_eq!(
+            events[1..6],
+            [
+                Event::FlagChanged(vec![0, 0], CellState::Flagged),
+                Event::FlagChanged(vec![0, 0], CellState::Hidden),
+                Event::Revealed(vec![0, 1], CellState::Revealed(Cell::Clean(2))),
+                Event::Revealed(vec![1, 1], CellState::Exploded(Cell::Bomb)),
+                Event::Revealed(vec![2, 2], CellState::Revealed(Cell::Bomb)),
+            ]
+        );
+        assert_eq!(events[6], Event::GameOver(GameState::Lost));
+        // the end of game display shows the 12 other cells, and ends nothing
+        assert_eq!(events.len(), 7 + 12);
+        assert_eq!(*flags.lock().unwrap(), 2);
+    }
 }
@@ -5,96 +5,350 @@ use rand;
 pub enum Cell {
     /// Represents a bomb
     Bomb,
-    /// Clean cell, with X neighbours (0<=X<=8)
-    Clean(u8),
+    /// Several bombs buried in the same cell (at least 2)
+    Mines(u8),
+    /// Explodes like a bomb, but subtracts one from the neighbouring counts
+    AntiMine,
+    /// Clean cell, with X neighbouring bombs minus the neighbouring anti-mines
+    /// (0<=X<=8 with the default neighbourhood and no anti-mines)
+    Clean(i16),
+}
+
+impl Cell {
+    pub fn num_mines(self) -> u8 {
+        //! Number of mines buried in the cell, i.e. of flags it needs
+        match self {
+            Cell::Bomb | Cell::AntiMine => 1,
+            Cell::Mines(k) => k,
+            Cell::Clean(_) => 0,
+        }
+    }
+
+    pub fn is_mine(self) -> bool {
+        self.num_mines() > 0
+    }
 }
 
 impl std::fmt::Display for Cell {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
         match self {
             Cell::Bomb => write!(f, "X"),
+            Cell::Mines(k) => write!(f, "{}X", k),
+            Cell::AntiMine => write!(f, "O"),
             Cell::Clean(i) => write!(f, "{}", i),
         }
     }
 }
 
+pub fn describe_variant(num_anti_mines: usize, liar: bool) -> Option<String> {
+    //! Short description of the rules of a puzzle variant, if any
+    let mut rules = vec![];
+    if num_anti_mines > 0 {
+        rules.push(format!("{} anti-mines", num_anti_mines));
+    }
+    if liar {
+        rules.push(String::from("numbers off by one"));
+    }
+    if rules.is_empty() {
+        None
+    } else {
+        Some(rules.join(", "))
+    }
+}
+
 /// Stateless minefield
 #[derive(Debug)]
 pub struct Minefield {
     field: grid::Grid<Cell>,
+    /// Number of mines to flag, anti-mines included
     num_bombs: usize,
+    /// Largest number of bombs a cell can hold
+    max_per_cell: u8,
+    num_anti_mines: usize,
+    /// Every number is off by one
+    liar: bool,
 }
 
 impl Minefield {
     pub fn new(height: usize, width: usize, num_bombs: usize) -> Result<Minefield, String> {
         //! Creates a minefield with the required size and number of bombs
-        let empty_field = vec![Cell::Clean(0); height * width];
-        let mut field_grid = grid::Grid::new(height, width, empty_field)?;
-        let mut rng = rand::thread_rng();
-        let bomb_indices_1d = rand::seq::index::sample(&mut rng, field_grid.len(), num_bombs);
-        // let bomb_indices_1d = (0..num_bombs * 3).step_by(3);
-        // for bomb_ix in bomb_indices.iter() {
-        let bomb_indices_2d: Vec<(usize, usize)> = bomb_indices_1d
+        let layout = grid::Grid::new(height, width, vec![(); height * width])?;
+        Minefield::from_layout(&layout, num_bombs)
+    }
+
+    pub fn from_layout<T>(layout: &grid::Grid<T>, num_bombs: usize) -> Result<Minefield, String> {
+        //! Creates a minefield with the same shape and topology as `layout`
+        Minefield::with_stacks(layout, num_bombs, 1)
+    }
+
+    pub fn with_stacks<T>(
+        layout: &grid::Grid<T>,
+        num_bombs: usize,
+        max_per_cell: u8,
+    ) -> Result<Minefield, String> {
+        //! Creates a minefield where a cell can hold up to `max_per_cell` bombs
+        //!
+        //! Numbers then report the total number of bombs around a cell.
+        //! Holes of the layout never get bombs.
+        Minefield::with_stacks_rng(layout, num_bombs, max_per_cell, &mut rand::thread_rng())
+    }
+
+    pub fn with_stacks_rng<T, R: rand::Rng>(
+        layout: &grid::Grid<T>,
+        num_bombs: usize,
+        max_per_cell: u8,
+        rng: &mut R,
+    ) -> Result<Minefield, String> {
+        //! Same as `with_stacks`, drawing bombs from `rng`, e.g. to replay a
+        //! board from its seed
+        let mut field_grid = layout.map(|_| Cell::Clean(0));
+        let cells: Vec<usize> = (0..field_grid.len())
+            .filter(|&i| field_grid.exists(&field_grid.index_rev(i).unwrap()))
+            .collect();
+        let num_slots = cells.len() * max_per_cell as usize;
+        if num_bombs > num_slots {
+            return Err(format!(
+                "Cannot bury {} bombs in {} cells",
+                num_bombs, num_slots
+            ));
+        }
+        // each cell offers `max_per_cell` slots, drawn without replacement
+        let slots = rand::seq::index::sample(rng, num_slots, num_bombs);
+        let bomb_locations: Vec<Vec<usize>> = slots
             .iter()
-            .map(|i| field_grid.index_rev(i).unwrap())
+            .map(|slot| {
+                field_grid
+                    .index_rev(cells[slot / max_per_cell as usize])
+                    .unwrap()
+            })
             .collect();
-        Minefield::bury_bombs(&mut field_grid, &bomb_indices_2d)?;
+        Minefield::bury_bombs(&mut field_grid, &bomb_locations)?;
         Ok(Minefield {
             field: field_grid,
-            num_bombs: num_bombs,
+            num_bombs,
+            max_per_cell,
+            num_anti_mines: 0,
+            liar: false,
         })
     }
+
     fn bury_bombs(
         field_grid: &mut grid::Grid<Cell>,
-        bomb_locations: &[(usize, usize)],
+        bomb_locations: &[Vec<usize>],
     ) -> Result<(), String> {
-        for &(i_bomb, j_bomb) in bomb_locations {
-            field_grid.set(i_bomb, j_bomb, Cell::Bomb)?;
-            let neighbours = field_grid.neighbours8(i_bomb, j_bomb);
-            for (i_n, j_n) in neighbours {
-                if let Some(Cell::Clean(i)) = field_grid.get(i_n, j_n) {
-                    field_grid.set(i_n, j_n, Cell::Clean(i + 1))?;
+        //! Adds a bomb at every location; locations listed several times get
+        //! several bombs
+        for bomb in bomb_locations {
+            let cell = match field_grid.get(bomb) {
+                _ if !field_grid.exists(bomb) => {
+                    return Err(format!(
+                        "Cannot bury a bomb outside of the board: {:?}",
+                        bomb
+                    ))
+                }
+                Some(Cell::Clean(_)) => Cell::Bomb,
+                Some(Cell::AntiMine) => {
+                    return Err(format!("Cannot bury a bomb on an anti-mine: {:?}", bomb))
+                }
+                Some(cell) => Cell::Mines(cell.num_mines() + 1),
+                None => return Err(format!("Non-existent index requested: {:?}", bomb)),
+            };
+            field_grid.set(bomb, cell)?;
+            for neighbour in field_grid.neighbours(bomb) {
+                if let Some(Cell::Clean(i)) = field_grid.get(&neighbour) {
+                    field_grid.set(&neighbour, Cell::Clean(i.saturating_add(1)))?;
                 }
             }
         }
         Ok(())
     }
 
+    fn bury_anti_mines(
+        field_grid: &mut grid::Grid<Cell>,
+        locations: &[Vec<usize>],
+    ) -> Result<(), String> {
+        //! Adds an anti-mine at every location, which must be clean
+        for location in locations {
+            match field_grid.get(location) {
+                Some(Cell::Clean(_)) if field_grid.exists(location) => (),
+                _ => {
+                    return Err(format!(
+                        "Cannot bury an anti-mine at {:?}: the cell is not clean",
+                        location
+                    ))
+                }
+            }
+            field_grid.set(location, Cell::AntiMine)?;
+            for neighbour in field_grid.neighbours(location) {
+                if let Some(Cell::Clean(i)) = field_grid.get(&neighbour) {
+                    field_grid.set(&neighbour, Cell::Clean(i.saturating_sub(1)))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn tell_lies<R: rand::Rng>(
+        field_grid: &mut grid::Grid<Cell>,
+        allow_negative: bool,
+        rng: &mut R,
+    ) {
+        //! Moves every number one up or down at random
+        //!
+        //! Numbers never go below 0 unless `allow_negative` is set.
+        for i in 0..field_grid.len() {
+            let coords = field_grid.index_rev(i).unwrap();
+            if let Some(Cell::Clean(count)) = field_grid.get(&coords) {
+                let up = rng.gen() || (count <= 0 && !allow_negative);
+                let lie = if up { count + 1 } else { count - 1 };
+                field_grid.set(&coords, Cell::Clean(lie)).unwrap();
+            }
+        }
+    }
+
+    pub fn with_bombs(mut self, bomb_locations: &[Vec<usize>]) -> Result<Minefield, String> {
+        //! Adds bombs at chosen locations, e.g. to set up a puzzle
+        Minefield::bury_bombs(&mut self.field, bomb_locations)?;
+        self.num_bombs += bomb_locations.len();
+        let max_per_cell = self.max_per_cell;
+        if self
+            .field
+            .data()
+            .iter()
+            .any(|c| c.num_mines() > max_per_cell)
+        {
+            return Err(format!(
+                "Cells cannot hold more than {} bombs",
+                max_per_cell
+            ));
+        }
+        Ok(self)
+    }
+
+    pub fn with_anti_mines(mut self, locations: &[Vec<usize>]) -> Result<Minefield, String> {
+        //! Adds anti-mines at chosen locations, which must be clean
+        Minefield::bury_anti_mines(&mut self.field, locations)?;
+        self.num_bombs += locations.len();
+        self.num_anti_mines += locations.len();
+        Ok(self)
+    }
+
+    pub fn with_random_anti_mines(self, count: usize) -> Result<Minefield, String> {
+        //! Adds `count` anti-mines in random clean cells
+        self.with_random_anti_mines_rng(count, &mut rand::thread_rng())
+    }
+
+    pub fn with_random_anti_mines_rng<R: rand::Rng>(
+        self,
+        count: usize,
+        rng: &mut R,
+    ) -> Result<Minefield, String> {
+        //! Same as `with_random_anti_mines`, drawing cells from `rng`
+        let clean: Vec<Vec<usize>> = self
+            .field
+            .iter()
+            .filter(|(coords, cell)| self.field.exists(coords) && !cell.is_mine())
+            .map(|(coords, _)| coords)
+            .collect();
+        if count > clean.len() {
+            return Err(format!(
+                "Cannot bury {} anti-mines in {} clean cells",
+                count,
+                clean.len()
+            ));
+        }
+        let locations: Vec<Vec<usize>> = rand::seq::index::sample(rng, clean.len(), count)
+            .iter()
+            .map(|i| clean[i].clone())
+            .collect();
+        self.with_anti_mines(&locations)
+    }
+
+    pub fn with_liars(self) -> Minefield {
+        //! Makes every number off by exactly one, up or down
+        self.with_liars_rng(&mut rand::thread_rng())
+    }
+
+    pub fn with_liars_rng<R: rand::Rng>(mut self, rng: &mut R) -> Minefield {
+        //! Same as `with_liars`, drawing the lies from `rng`
+        if !self.liar {
+            Minefield::tell_lies(&mut self.field, self.num_anti_mines > 0, rng);
+            self.liar = true;
+        }
+        self
+    }
+
     pub fn num_bombs(&self) -> usize {
+        //! Number of mines to flag, anti-mines included
         self.num_bombs
     }
 
-    pub fn shape(&self) -> (usize, usize) {
+    pub fn num_anti_mines(&self) -> usize {
+        self.num_anti_mines
+    }
+
+    pub fn is_liar(&self) -> bool {
+        self.liar
+    }
+
+    pub fn safe_zeros(&self) -> bool {
+        //! Whether a cell showing 0 has no mine around it
+        //!
+        //! Anti-mines can cancel bombs out, and liars show 0 next to a bomb,
+        //! so empty areas cannot be opened automatically in these variants.
+        self.num_anti_mines == 0 && !self.liar
+    }
+
+    pub fn variant(&self) -> Option<String> {
+        //! Short description of the puzzle variant, if any
+        describe_variant(self.num_anti_mines, self.liar)
+    }
+
+    pub fn max_per_cell(&self) -> u8 {
+        self.max_per_cell
+    }
+
+    pub fn shape(&self) -> &[usize] {
         self.field.shape()
     }
 
-    pub fn dig(&self, row: usize, col: usize) -> Option<Cell> {
+    pub fn layout<U: Copy>(&self, value: U) -> grid::Grid<U> {
+        //! Grid with the same shape and topology as the minefield, filled with `value`
+        self.field.map(|_| value)
+    }
+
+    pub fn dig(&self, coords: &[usize]) -> Option<Cell> {
         //! Query the status of a cell
         //!
         //! Game is lost if returned cell is a bomb
-        //! None is returned if cell is outside of minefield
-        self.field.get(row, col)
+        //! None is returned if cell is outside of minefield, or in a hole
+        if !self.field.exists(coords) {
+            return None;
+        }
+        self.field.get(coords)
     }
 
-    pub fn submit(&self, bomb_locations: &[(usize, usize)]) -> bool {
+    pub fn submit(&self, bomb_locations: &[Vec<usize>]) -> bool {
         //! Submit a list of bombs
         //!
         //! Game is won is that list matches **all** the bomb locations
+        //! A location holding several bombs must be listed once per bomb.
         //! Strategy:
-        //! - create a set of unique bomb locations
-        //! - check that this set has the required number of locations
-        //! - check that every location is effectively a bomb
-        let mut bomb_submission = std::collections::HashSet::with_capacity(self.num_bombs);
+        //! - count how many times each location is listed
+        //! - check that the counts add up to the required number of bombs
+        //! - check that every location holds as many bombs as listed
+        let mut bomb_submission = std::collections::HashMap::with_capacity(self.num_bombs);
         for loc in bomb_locations {
-            bomb_submission.insert(*loc);
+            *bomb_submission.entry(&loc[..]).or_insert(0) += 1;
         }
-        if bomb_submission.len() != self.num_bombs {
+        if bomb_submission.values().sum::<usize>() != self.num_bombs {
             return false;
         }
 
-        for (row, col) in bomb_submission.iter() {
-            if self.dig(*row, *col) != Some(Cell::Bomb) {
-                return false;
+        for (loc, &count) in bomb_submission.iter() {
+            match self.dig(loc) {
+                Some(cell) if cell.num_mines() as usize == count => (),
+                _ => return false,
             }
         }
         true
@@ -107,7 +361,6 @@ impl std::fmt::Display for Minefield {
     }
 }
 
-
 #[cfg(test)]
 pub mod test {
     use super::*;
@@ -116,7 +369,7 @@ pub mod test {
         for c in pattern.chars() {
             let car = match c {
                 'X' => Some(Cell::Bomb),
-                '0'..='8' => Some(Cell::Clean(c.to_digit(10).unwrap() as u8)),
+                '0'..='8' => Some(Cell::Clean(c.to_digit(10).unwrap() as i16)),
                 _ => None,
             };
             if let Some(cell) = car {
@@ -131,13 +384,15 @@ pub mod test {
         let width = 4;
         let expected_num_bombs = 5;
         let field = Minefield::new(height, width, expected_num_bombs).unwrap();
-        assert_eq!(field.shape(), (height, width));
+        assert_eq!(field.shape(), &[height, width]);
         assert_eq!(field.num_bombs(), expected_num_bombs);
         let mut actual_num_bombs = 0;
         for i in 0..height {
             for j in 0..width {
-                match field.dig(i, j) {
+                match field.dig(&[i, j]) {
                     Some(Cell::Bomb) => actual_num_bombs += 1,
+                    Some(Cell::Mines(_)) => panic!("Single bombs were expected"),
+                    Some(Cell::AntiMine) => panic!("No anti-mine was expected"),
                     Some(Cell::Clean(_)) => (),
                     None => panic!("Digging legal cell was denied: {}, {}", i, j),
                 }
@@ -158,6 +413,9 @@ pub mod test {
         let field = Minefield {
             field: grid,
             num_bombs: 2,
+            max_per_cell: 1,
+            num_anti_mines: 0,
+            liar: false,
         };
 
         let display_string = format!("{}", field);
@@ -177,37 +435,235 @@ pub mod test {
         let field = Minefield {
             field: grid,
             num_bombs: 2,
+            max_per_cell: 1,
+            num_anti_mines: 0,
+            liar: false,
         };
 
-        assert_eq!(field.dig(0, 0), Some(Cell::Bomb));
-        assert_eq!(field.dig(1, 1), Some(Cell::Clean(2)));
+        assert_eq!(field.dig(&[0, 0]), Some(Cell::Bomb));
+        assert_eq!(field.dig(&[1, 1]), Some(Cell::Clean(2)));
 
-        assert_eq!(field.submit(&vec![(0, 0), (0, 1)]), true);
-        assert_eq!(field.submit(&vec![(0, 1), (0, 0)]), true);
-        assert_eq!(field.submit(&vec![(0, 0)]), false);
-        assert_eq!(field.submit(&vec![(0, 0), (0, 0)]), false);
-        assert_eq!(field.submit(&vec![(0, 0), (0, 1), (1, 0)]), false);
+        assert!(field.submit(&[vec![0, 0], vec![0, 1]]));
+        assert!(field.submit(&[vec![0, 1], vec![0, 0]]));
+        assert!(!field.submit(&[vec![0, 0]]));
+        assert!(!field.submit(&[vec![0, 0], vec![0, 0]]));
+        assert!(!field.submit(&[vec![0, 0], vec![0, 1], vec![1, 0]]));
     }
 
     #[test]
     fn bury_bombs_1() {
         let mut grid = grid::Grid::new(3, 3, vec![Cell::Clean(0); 9]).unwrap();
-        let bomb_locations: Vec<(usize, usize)> = vec![(1, 1)];
+        let bomb_locations = vec![vec![1, 1]];
         Minefield::bury_bombs(&mut grid, &bomb_locations).unwrap();
         let expected_grid = cell_pattern("1111X1111");
         assert_eq!(grid.data(), &expected_grid[..]);
     }
 
+    #[test]
+    fn bury_bombs_hex() {
+        // X 2 1
+        //  2 X 1
+        // 0 1 1
+        let mut grid = grid::Grid::new(3, 3, vec![Cell::Clean(0); 9])
+            .unwrap()
+            .with_topology(grid::Topology::Hex);
+        Minefield::bury_bombs(&mut grid, &[vec![0, 0], vec![1, 1]]).unwrap();
+        let expected_grid = cell_pattern("X212X1011");
+        assert_eq!(grid.data(), &expected_grid[..]);
+    }
+
+    #[test]
+    fn bury_bombs_wrap() {
+        // the corner bomb is counted from the other edges
+        let mut grid = grid::Grid::new(3, 4, vec![Cell::Clean(0); 12])
+            .unwrap()
+            .with_wrap(true)
+            .unwrap();
+        Minefield::bury_bombs(&mut grid, &[vec![0, 0]]).unwrap();
+        let expected_grid = cell_pattern("X10111011101");
+        assert_eq!(grid.data(), &expected_grid[..]);
+    }
+
+    #[test]
+    fn bury_bombs_3d() {
+        // bombs in the middle of the cube count for every other cell
+        let mut grid = grid::Grid::with_dims(&[3, 3, 3], vec![Cell::Clean(0); 27]).unwrap();
+        Minefield::bury_bombs(&mut grid, &[vec![1, 1, 1], vec![0, 0, 0]]).unwrap();
+        assert_eq!(grid.get(&[1, 1, 1]), Some(Cell::Bomb));
+        assert_eq!(grid.get(&[0, 1, 1]), Some(Cell::Clean(2)));
+        assert_eq!(grid.get(&[2, 2, 2]), Some(Cell::Clean(1)));
+        let num_ones = grid.data().iter().filter(|&&c| c == Cell::Clean(1)).count();
+        assert_eq!(num_ones, 26 - 7);
+    }
+
+    #[test]
+    fn bury_bombs_knight() {
+        // knight's move neighbourhood: the bomb is counted 2 cells away
+        let mut grid = grid::Grid::new(3, 3, vec![Cell::Clean(0); 9])
+            .unwrap()
+            .with_neighbourhood(grid::Neighbourhood::Knight);
+        Minefield::bury_bombs(&mut grid, &[vec![0, 0]]).unwrap();
+        let expected_grid = cell_pattern("X00001010");
+        assert_eq!(grid.data(), &expected_grid[..]);
+    }
+
+    #[test]
+    fn stacks() {
+        // X 2X 3
+        let mut grid = grid::Grid::new(1, 3, vec![Cell::Clean(0); 3]).unwrap();
+        Minefield::bury_bombs(&mut grid, &[vec![0, 1], vec![0, 0], vec![0, 1]]).unwrap();
+        assert_eq!(grid.data(), &[Cell::Bomb, Cell::Mines(2), Cell::Clean(2)]);
+        assert_eq!(format!("{}", grid), "X 2X 2");
+
+        let layout = grid::Grid::new(2, 2, vec![(); 4]).unwrap();
+        let field = Minefield::with_stacks(&layout, 12, 3).unwrap();
+        assert_eq!(field.max_per_cell(), 3);
+        let num_mines: usize = field
+            .field
+            .data()
+            .iter()
+            .map(|c| c.num_mines() as usize)
+            .sum();
+        assert_eq!(num_mines, 12);
+        // every cell is full
+        let all_bombs: Vec<Vec<usize>> =
+            (0..12).map(|i| layout.index_rev(i / 3).unwrap()).collect();
+        assert!(field.submit(&all_bombs));
+        assert!(Minefield::with_stacks(&layout, 13, 3).is_err());
+
+        let field = Minefield::with_stacks(&layout, 0, 2).unwrap();
+        assert!(field.with_bombs(&vec![vec![0, 0]; 3]).is_err());
+    }
+
+    #[test]
+    fn anti_mines() {
+        // X 0 O 1 0
+        let mut grid = grid::Grid::new(1, 5, vec![Cell::Clean(0); 5]).unwrap();
+        Minefield::bury_bombs(&mut grid, &[vec![0, 0]]).unwrap();
+        Minefield::bury_anti_mines(&mut grid, &[vec![0, 2]]).unwrap();
+        assert_eq!(
+            grid.data(),
+            &[
+                Cell::Bomb,
+                Cell::Clean(0),
+                Cell::AntiMine,
+                Cell::Clean(-1),
+                Cell::Clean(0)
+            ]
+        );
+        assert_eq!(format!("{}", grid), "X 0 O -1 0");
+        assert!(Minefield::bury_anti_mines(&mut grid, &[vec![0, 0]]).is_err());
+        assert!(Minefield::bury_bombs(&mut grid, &[vec![0, 2]]).is_err());
+
+        let field = Minefield::from_layout(&grid, 0)
+            .unwrap()
+            .with_bombs(&[vec![0, 0]])
+            .unwrap()
+            .with_anti_mines(&[vec![0, 2]])
+            .unwrap();
+        assert_eq!(field.num_bombs(), 2);
+        assert_eq!(field.num_anti_mines(), 1);
+        assert!(!field.safe_zeros());
+        assert_eq!(field.variant(), Some(String::from("1 anti-mines")));
+        assert!(field.submit(&[vec![0, 0], vec![0, 2]]));
+        assert!(!field.submit(&[vec![0, 0]]));
+
+        let layout = grid::Grid::new(3, 3, vec![(); 9]).unwrap();
+        let field = Minefield::from_layout(&layout, 3)
+            .unwrap()
+            .with_random_anti_mines(2)
+            .unwrap();
+        assert_eq!(field.num_bombs(), 5);
+        let num_anti_mines = (0..9)
+            .filter(|i| field.dig(&[i / 3, i % 3]) == Some(Cell::AntiMine))
+            .count();
+        assert_eq!(num_anti_mines, 2);
+        assert!(field.with_random_anti_mines(5).is_err());
+    }
+
+    #[test]
+    fn liars() {
+        let layout = grid::Grid::new(4, 4, vec![(); 16]).unwrap();
+        let field = Minefield::from_layout(&layout, 4).unwrap();
+        let truth = field.field.map(|&cell| cell);
+        let field = field.with_liars();
+        assert!(field.is_liar());
+        assert!(!field.safe_zeros());
+        assert_eq!(field.variant(), Some(String::from("numbers off by one")));
+        for (coords, &cell) in truth.iter() {
+            match (cell, field.dig(&coords).unwrap()) {
+                (Cell::Clean(count), Cell::Clean(lie)) => {
+                    assert_eq!((count - lie).abs(), 1);
+                    assert!(lie >= 0);
+                }
+                (cell, other) => assert_eq!(cell, other),
+            }
+        }
+    }
+
+    #[test]
+    fn seeded() {
+        use rand::SeedableRng;
+        let layout = grid::Grid::new(8, 8, vec![(); 64]).unwrap();
+        let generate = |seed| {
+            let mut rng = rand::rngs::StdRng::seed_from_u64(seed);
+            Minefield::with_stacks_rng(&layout, 10, 1, &mut rng)
+                .unwrap()
+                .with_random_anti_mines_rng(3, &mut rng)
+                .unwrap()
+                .with_liars_rng(&mut rng)
+                .to_string()
+        };
+        assert_eq!(generate(42), generate(42));
+        assert_ne!(generate(42), generate(43));
+    }
+
+    #[test]
+    fn holes() {
+        // only the 4 corners exist
+        let layout = grid::Grid::new(3, 3, vec![(); 9])
+            .unwrap()
+            .with_mask(&[true, false, true, false, false, false, true, false, true])
+            .unwrap();
+        let field = Minefield::from_layout(&layout, 4).unwrap();
+        assert_eq!(field.dig(&[0, 0]), Some(Cell::Bomb));
+        assert_eq!(field.dig(&[2, 2]), Some(Cell::Bomb));
+        assert_eq!(field.dig(&[1, 1]), None);
+        assert!(Minefield::from_layout(&layout, 5).is_err());
+        assert!(!field.submit(&[vec![0, 0], vec![0, 2], vec![2, 0], vec![1, 1]]));
+
+        let field = Minefield::from_layout(&layout, 0).unwrap();
+        assert!(field.with_bombs(&[vec![0, 1]]).is_err());
+    }
+
+    #[test]
+    fn from_layout() {
+        let layout = grid::Grid::new(5, 6, vec![0; 30])
+            .unwrap()
+            .with_topology(grid::Topology::Hex);
+        let field = Minefield::from_layout(&layout, 7).unwrap();
+        assert_eq!(field.shape(), &[5, 6]);
+        assert_eq!(field.layout(false).topology(), grid::Topology::Hex);
+        let num_bombs = field
+            .field
+            .data()
+            .iter()
+            .filter(|&&c| c == Cell::Bomb)
+            .count();
+        assert_eq!(num_bombs, 7);
+        assert!(Minefield::from_layout(&layout, 31).is_err());
+    }
+
     #[test]
     fn bury_bombs_2() {
         let mut grid = grid::Grid::new(3, 3, vec![Cell::Clean(0); 9]).unwrap();
-        let bomb_locations: Vec<(usize, usize)> = vec![(0, 0), (1, 1), (2, 2)];
+        let bomb_locations = vec![vec![0, 0], vec![1, 1], vec![2, 2]];
         Minefield::bury_bombs(&mut grid, &bomb_locations).unwrap();
         let expected_grid = cell_pattern("X212X212X");
         assert_eq!(grid.data(), &expected_grid[..]);
     }
 
-    pub fn generate_test_minefield() -> (Minefield, Vec<(usize, usize)>) {
+    pub fn generate_test_minefield() -> (Minefield, Vec<Vec<usize>>) {
         //! Test minefield of dimension 3x5, with 5 bombs
         //!
         //! Pattern
@@ -215,7 +671,7 @@ pub mod test {
         //! _X__X -> 2X33X
         //! __X__    12X21
         let mut grid = grid::Grid::new(3, 5, vec![Cell::Clean(0); 15]).unwrap();
-        let bomb_locations: Vec<(usize, usize)> = vec![(0, 0), (0, 3), (1, 1), (1, 4), (2, 2)];
+        let bomb_locations = vec![vec![0, 0], vec![0, 3], vec![1, 1], vec![1, 4], vec![2, 2]];
 
         // pattern for first 3 bombs
         // X__X_    X22X1
@@ -233,7 +689,16 @@ pub mod test {
         let expected_grid = cell_pattern("X22X22X33X12X21");
         assert_eq!(grid.data(), &expected_grid[..]);
 
-        (Minefield{ field:grid, num_bombs:5}, bomb_locations)
+        (
+            Minefield {
+                field: grid,
+                num_bombs: 5,
+                max_per_cell: 1,
+                num_anti_mines: 0,
+                liar: false,
+            },
+            bomb_locations,
+        )
     }
 
     #[test]
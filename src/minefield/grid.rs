@@ -1,10 +1,146 @@
 use std::fmt;
-use std::usize::{self};
 
+/// How the cells of a grid are connected to each other
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Topology {
+    /// Square cells, with 8 neighbours
+    Square,
+    /// Hexagonal cells with 6 neighbours, in rows shifted by half a cell:
+    /// odd rows are shifted to the right
+    Hex,
+}
+
+/// Which cells count as neighbours of a square cell
+///
+/// Hexagonal grids always use their 6 neighbours.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Neighbourhood {
+    /// Cells within a distance, diagonals included (8 neighbours at distance 1)
+    Moore(usize),
+    /// The 4 cells sharing a side (6 faces in three dimensions)
+    VonNeumann,
+    /// Cells a chess knight's move away
+    Knight,
+    /// Offsets (row, col) in the layer of the cell, see `from_mask`
+    Custom(Vec<(isize, isize)>),
+}
+
+impl Default for Neighbourhood {
+    fn default() -> Neighbourhood {
+        Neighbourhood::Moore(1)
+    }
+}
+
+impl Neighbourhood {
+    pub fn from_name(name: &str) -> Option<Neighbourhood> {
+        match name {
+            "moore" => Some(Neighbourhood::Moore(1)),
+            "radius-2" => Some(Neighbourhood::Moore(2)),
+            "von-neumann" | "cross" => Some(Neighbourhood::VonNeumann),
+            "knight" => Some(Neighbourhood::Knight),
+            _ => None,
+        }
+    }
+
+    pub fn from_mask(mask: &str) -> Result<Neighbourhood, String> {
+        //! Reads a picture of the neighbourhood, e.g. for von Neumann
+        //!
+        //! ```text
+        //! . x .
+        //! x o x
+        //! . x .
+        //! ```
+        //!
+        //! `o` marks the cell itself, `x` its neighbours and `.` other cells;
+        //! spaces are ignored. Masks must be symmetric around the cell, so that
+        //! a number counts the bombs of the cells it is a neighbour of.
+        let lines: Vec<Vec<char>> = mask
+            .lines()
+            .map(|l| l.chars().filter(|c| !c.is_whitespace()).collect())
+            .filter(|l: &Vec<char>| !l.is_empty())
+            .collect();
+        let mut center = None;
+        let mut cells = vec![];
+        for (row, line) in lines.iter().enumerate() {
+            for (col, &c) in line.iter().enumerate() {
+                match c {
+                    'o' | 'O' if center.is_none() => center = Some((row, col)),
+                    'x' | 'X' => cells.push((row, col)),
+                    '.' => (),
+                    _ => return Err(format!("Unexpected '{}' in neighbourhood mask", c)),
+                }
+            }
+        }
+        let (c_row, c_col) = center.ok_or("Neighbourhood mask has no 'o' cell")?;
+        let offsets: Vec<(isize, isize)> = cells
+            .iter()
+            .map(|&(row, col)| (row as isize - c_row as isize, col as isize - c_col as isize))
+            .collect();
+        if offsets
+            .iter()
+            .any(|&(d_row, d_col)| !offsets.contains(&(-d_row, -d_col)))
+        {
+            return Err(String::from("Neighbourhood mask must be symmetric"));
+        }
+        Ok(Neighbourhood::Custom(offsets))
+    }
+
+    fn offsets(&self, num_dims: usize) -> Vec<Vec<isize>> {
+        //! Offsets to the neighbours, in increasing order
+        let radius = match self {
+            Neighbourhood::Moore(radius) => *radius as isize,
+            Neighbourhood::VonNeumann => 1,
+            Neighbourhood::Knight => 2,
+            Neighbourhood::Custom(offsets) => offsets
+                .iter()
+                .map(|&(d_row, d_col)| d_row.abs().max(d_col.abs()))
+                .max()
+                .unwrap_or(0),
+        };
+        let side = 2 * radius as usize + 1;
+        let mut all_offsets = vec![];
+        for k in 0..side.pow(num_dims as u32) {
+            let mut offset = vec![0; num_dims];
+            let mut rest = k;
+            for d in offset.iter_mut().rev() {
+                *d = (rest % side) as isize - radius;
+                rest /= side;
+            }
+            let non_zero: Vec<isize> = offset
+                .iter()
+                .filter(|&&d| d != 0)
+                .map(|d| d.abs())
+                .collect();
+            let is_neighbour = match self {
+                Neighbourhood::Moore(_) => !non_zero.is_empty(),
+                Neighbourhood::VonNeumann => non_zero == [1],
+                Neighbourhood::Knight => non_zero == [1, 2] || non_zero == [2, 1],
+                Neighbourhood::Custom(offsets) => {
+                    let (layer, plane) = offset.split_at(num_dims - 2);
+                    layer.iter().all(|&d| d == 0) && offsets.contains(&(plane[0], plane[1]))
+                }
+            };
+            if is_neighbour {
+                all_offsets.push(offset);
+            }
+        }
+        all_offsets
+    }
+}
+
+/// Grid of cells with any number of dimensions (at least 2)
+///
+/// Coordinates list one index per dimension; the last two are the row and
+/// the column, leading ones select a layer.
 pub struct Grid<T> {
-    height: usize,
-    width: usize,
+    dims: Vec<usize>,
     data: Vec<T>,
+    topology: Topology,
+    /// Whether the edges wrap around to the opposite side (torus)
+    wrap: bool,
+    neighbourhood: Neighbourhood,
+    /// Cells that exist, for boards that are not full rectangles
+    mask: Option<Vec<bool>>,
 }
 
 pub struct IterGrid<'a, T: 'a> {
@@ -13,101 +149,290 @@ pub struct IterGrid<'a, T: 'a> {
 }
 
 impl<'a, T> Iterator for IterGrid<'a, T> {
-    type Item = (usize, usize, &'a T);
+    type Item = (Vec<usize>, &'a T);
     fn next(&mut self) -> Option<Self::Item> {
         match self.grid.index_rev(self.curr_ix) {
-            Some((i, j)) => {
+            Some(coords) => {
                 let value = &self.grid.data[self.curr_ix];
                 self.curr_ix += 1;
-                Some((i, j, value))
+                Some((coords, value))
             }
             None => None,
         }
     }
 }
 
+// offsets in the (row, col) plane, including the cell itself
+const HEX_ODD_OFFSETS: [(isize, isize); 7] =
+    [(-1, 0), (-1, 1), (0, -1), (0, 0), (0, 1), (1, 0), (1, 1)];
+const HEX_EVEN_OFFSETS: [(isize, isize); 7] =
+    [(-1, -1), (-1, 0), (0, -1), (0, 0), (0, 1), (1, -1), (1, 0)];
+
 impl<'a, T> Grid<T> {
     pub fn len(&self) -> usize {
-        self.height * self.width
+        self.data.len()
     }
 
-    pub fn shape(&self) -> (usize, usize) {
-        (self.height, self.width)
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
     }
 
-    pub fn index(&self, row: usize, col: usize) -> Option<usize> {
-        if (row <= self.height - 1) && (col <= self.width - 1) {
-            Some(row * self.width + col)
-        } else {
-            None
+    pub fn shape(&self) -> &[usize] {
+        &self.dims
+    }
+
+    pub fn layer_shape(&self) -> (usize, usize) {
+        //! Number of rows and columns of a layer
+        let n = self.dims.len();
+        (self.dims[n - 2], self.dims[n - 1])
+    }
+
+    pub fn num_layers(&self) -> usize {
+        //! Number of (row, col) planes, 1 for two-dimensional grids
+        self.dims[..self.dims.len() - 2].iter().product()
+    }
+
+    pub fn index(&self, coords: &[usize]) -> Option<usize> {
+        if coords.len() != self.dims.len() {
+            return None;
         }
+        let mut index = 0;
+        for (&coord, &dim) in coords.iter().zip(&self.dims) {
+            if coord >= dim {
+                return None;
+            }
+            index = index * dim + coord;
+        }
+        Some(index)
     }
 
-    pub fn index_rev(&self, index: usize) -> Option<(usize, usize)> {
-        if index < self.len() {
-            Some((index / self.width, index % self.width))
-        } else {
-            None
+    pub fn index_rev(&self, index: usize) -> Option<Vec<usize>> {
+        if index >= self.len() {
+            return None;
+        }
+        let mut coords = vec![0; self.dims.len()];
+        let mut rest = index;
+        for (coord, &dim) in coords.iter_mut().zip(&self.dims).rev() {
+            *coord = rest % dim;
+            rest /= dim;
         }
+        Some(coords)
+    }
+
+    pub fn exists(&self, coords: &[usize]) -> bool {
+        //! Whether a cell is part of the board: false outside of it and in holes
+        match (self.index(coords), &self.mask) {
+            (Some(index), Some(mask)) => mask[index],
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+
+    pub fn num_cells(&self) -> usize {
+        //! Number of cells that exist, holes excluded
+        match &self.mask {
+            Some(mask) => mask.iter().filter(|&&m| m).count(),
+            None => self.len(),
+        }
+    }
+
+    pub fn with_mask(mut self, mask: &[bool]) -> Result<Grid<T>, String> {
+        //! Removes the cells where `mask` is false, in the order of `index`
+        //!
+        //! Removed cells have no neighbours and are never neighbours.
+        if mask.len() != self.len() {
+            return Err(String::from("Mask length is incompatible with the grid"));
+        }
+        self.mask = Some(mask.to_vec());
+        Ok(self)
+    }
+
+    pub fn layer_coords(&self, layer: usize, row: usize, col: usize) -> Option<Vec<usize>> {
+        //! Coordinates of a cell given by its layer, row and column
+        let (height, width) = self.layer_shape();
+        if layer >= self.num_layers() || row >= height || col >= width {
+            return None;
+        }
+        self.index_rev((layer * height + row) * width + col)
+    }
+
+    pub fn layer_of(&self, coords: &[usize]) -> usize {
+        //! Layer containing a cell, as counted by `layer_coords`
+        let n = self.dims.len();
+        coords[..n - 2]
+            .iter()
+            .zip(&self.dims)
+            .fold(0, |layer, (&coord, &dim)| layer * dim + coord)
     }
 
     pub fn iter(&'a self) -> IterGrid<'a, T> {
         IterGrid {
-            grid: &self,
+            grid: self,
             curr_ix: 0,
         }
     }
 
-    pub fn neighbours8(&self, row: usize, col: usize) -> Vec<(usize, usize)> {
-        let mut neighbours: Vec<(usize, usize)> = vec![];
-        let row_left: usize = if row > 0 { row - 1 } else { row };
-        let row_right: usize = if row < self.height - 1 { row + 1 } else { row };
-        let col_left: usize = if col > 0 { col - 1 } else { col };
-        let col_right: usize = if col < self.width - 1 { col + 1 } else { col };
-        for i in row_left..=row_right {
-            for j in col_left..=col_right {
-                if i != row || j != col {
-                    neighbours.push((i, j))
+    fn offset(&self, coords: &[usize], offsets: &[isize]) -> Option<Vec<usize>> {
+        //! Cell at a given offset, wrapping around the edges if the grid does
+        let mut cell = Vec::with_capacity(coords.len());
+        for ((&coord, &offset), &dim) in coords.iter().zip(offsets).zip(&self.dims) {
+            let i = coord as isize + offset;
+            if self.wrap {
+                cell.push(i.rem_euclid(dim as isize) as usize);
+            } else if i >= 0 && (i as usize) < dim {
+                cell.push(i as usize);
+            } else {
+                return None;
+            }
+        }
+        Some(cell)
+    }
+
+    pub fn neighbours(&self, coords: &[usize]) -> Vec<Vec<usize>> {
+        //! Neighbours of a cell, according to the topology and neighbourhood
+        //! of the grid, holes excluded
+        //!
+        //! Square cells use the neighbourhood of the grid, 8 cells by default,
+        //! 26 for the cubes of a 3D grid.
+        //! Hexagonal cells (odd rows shifted right) have 6 neighbours in their
+        //! layer; cells of adjacent layers touching the cell or one of these
+        //! neighbours are neighbours too.
+        let n = self.dims.len();
+        let offsets = match self.topology {
+            Topology::Square => self.neighbourhood.offsets(n),
+            Topology::Hex => {
+                let plane_offsets = if coords[n - 2] % 2 == 1 {
+                    &HEX_ODD_OFFSETS
+                } else {
+                    &HEX_EVEN_OFFSETS
+                };
+                // every combination of -1, 0, 1 on the leading dimensions
+                let layer_offsets = Neighbourhood::Moore(1).offsets(n - 2);
+                let mut offsets = vec![];
+                for layer_offset in std::iter::once(vec![0; n - 2]).chain(layer_offsets) {
+                    for &(d_row, d_col) in plane_offsets {
+                        let mut offset = layer_offset.clone();
+                        offset.extend(&[d_row, d_col]);
+                        offsets.push(offset);
+                    }
+                }
+                offsets.sort();
+                offsets
+            }
+        };
+        let mut neighbours: Vec<Vec<usize>> = vec![];
+        for offset in offsets {
+            if let Some(cell) = self.offset(coords, &offset) {
+                // small wrapping grids reach the same cell through several edges
+                if cell != coords && self.exists(&cell) && !neighbours.contains(&cell) {
+                    neighbours.push(cell);
                 }
             }
         }
         neighbours
     }
+
+    pub fn topology(&self) -> Topology {
+        self.topology
+    }
+
+    pub fn with_topology(mut self, topology: Topology) -> Grid<T> {
+        self.topology = topology;
+        self
+    }
+
+    pub fn neighbourhood(&self) -> &Neighbourhood {
+        &self.neighbourhood
+    }
+
+    pub fn with_neighbourhood(mut self, neighbourhood: Neighbourhood) -> Grid<T> {
+        self.neighbourhood = neighbourhood;
+        self
+    }
+
+    pub fn wraps(&self) -> bool {
+        self.wrap
+    }
+
+    pub fn with_wrap(mut self, wrap: bool) -> Result<Grid<T>, String> {
+        //! Makes the edges wrap around to the opposite side
+        //!
+        //! Hexagonal grids need an even number of rows to wrap vertically, so
+        //! their topology is set first.
+        let (height, _) = self.layer_shape();
+        if wrap && self.topology == Topology::Hex && height % 2 == 1 {
+            // shifted rows would not line up across the top and bottom edges
+            return Err(String::from(
+                "Wrapping hexagonal boards need an even number of rows",
+            ));
+        }
+        self.wrap = wrap;
+        Ok(self)
+    }
+
+    pub fn map<U, F>(&self, f: F) -> Grid<U>
+    where
+        F: Fn(&T) -> U,
+    {
+        //! Grid of the same layout, with transformed values
+        Grid {
+            dims: self.dims.clone(),
+            data: self.data.iter().map(f).collect(),
+            topology: self.topology,
+            wrap: self.wrap,
+            neighbourhood: self.neighbourhood.clone(),
+            mask: self.mask.clone(),
+        }
+    }
 }
 
 impl<T: Copy> Grid<T> {
     pub fn new(height: usize, width: usize, data: Vec<T>) -> Result<Grid<T>, String> {
-        let size = height * width;
-        if size == data.len() {
+        Grid::with_dims(&[height, width], data)
+    }
+
+    pub fn with_dims(dims: &[usize], data: Vec<T>) -> Result<Grid<T>, String> {
+        //! Creates a grid of any number of dimensions, e.g. `[layers, height, width]`
+        if dims.len() < 2 {
+            return Err(String::from("A grid needs at least 2 dimensions"));
+        }
+        let len = dims
+            .iter()
+            .try_fold(1usize, |len, &dim| len.checked_mul(dim))
+            .ok_or_else(|| String::from("The grid is too large"))?;
+        if len == data.len() {
             Ok(Grid {
-                height: height,
-                width: width,
-                data: data,
+                dims: dims.to_vec(),
+                data,
+                topology: Topology::Square,
+                wrap: false,
+                neighbourhood: Neighbourhood::default(),
+                mask: None,
             })
         } else {
             Err(String::from(
-                "Data length is incompatible with given height and width",
+                "Data length is incompatible with given dimensions",
             ))
         }
     }
 
     pub fn data(&self) -> &[T] {
-        return &self.data;
+        &self.data
     }
 
-    pub fn get(&self, row: usize, col: usize) -> Option<T> {
-        let index = self.index(row, col)?;
+    pub fn get(&self, coords: &[usize]) -> Option<T> {
+        let index = self.index(coords)?;
         Some(self.data[index])
     }
 
-    pub fn set(&mut self, row: usize, col: usize, value: T) -> Result<(), String> {
-        let index = self.index(row, col);
+    pub fn set(&mut self, coords: &[usize], value: T) -> Result<(), String> {
+        let index = self.index(coords);
         match index {
             Some(i) => {
                 *self.data.get_mut(i).unwrap() = value;
                 Ok(())
             }
-            None => Err(format!("Non-existent index requested: ({}, {})", row, col)),
+            None => Err(format!("Non-existent index requested: {:?}", coords)),
         }
     }
 }
@@ -117,11 +442,7 @@ where
     T: fmt::Debug,
 {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "grid of size h={}, w={}\n{:?}",
-            self.height, self.width, self.data
-        )
+        write!(f, "grid of shape {:?}\n{:?}", self.dims, self.data)
     }
 }
 
@@ -130,13 +451,25 @@ where
     T: fmt::Display + Copy,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-        for row in 0..self.height {
-            for col in 0..self.width {
-                let sep = if col < self.width - 1 { " " } else { "" };
-                write!(f, "{}{}", self.get(row, col).unwrap(), sep)?
+        //! Layers are separated by an empty line, holes are left blank
+        let (height, width) = self.layer_shape();
+        for layer in 0..self.num_layers() {
+            if layer > 0 {
+                write!(f, "\n\n")?
             }
-            if row < self.height - 1 {
-                write!(f, "\n")?
+            for row in 0..height {
+                for col in 0..width {
+                    let sep = if col < width - 1 { " " } else { "" };
+                    let coords = self.layer_coords(layer, row, col).unwrap();
+                    if self.exists(&coords) {
+                        write!(f, "{}{}", self.get(&coords).unwrap(), sep)?
+                    } else {
+                        write!(f, " {}", sep)?
+                    }
+                }
+                if row < height - 1 {
+                    writeln!(f)?
+                }
             }
         }
         Ok(())
@@ -152,16 +485,43 @@ mod test {
         let data = vec![0; height * width];
 
         let grid = Grid::new(height, width, data).unwrap();
-        assert_eq!(grid.shape(), (height, width));
+        assert_eq!(grid.shape(), &[height, width]);
+        assert_eq!(grid.layer_shape(), (height, width));
+        assert_eq!(grid.num_layers(), 1);
         assert_eq!(grid.len(), height * width);
 
         for i in 0..height {
             for j in 0..width {
-                let tt_index = grid.index(i, j).unwrap();
-                let (i_rev, j_rev) = grid.index_rev(tt_index).unwrap();
-                assert_eq!((i, j), (i_rev, j_rev), "Wrong index_rev for t={}", tt_index);
+                let tt_index = grid.index(&[i, j]).unwrap();
+                let coords = grid.index_rev(tt_index).unwrap();
+                assert_eq!(vec![i, j], coords, "Wrong index_rev for t={}", tt_index);
             }
         }
+        assert_eq!(grid.index(&[0, 0, 0]), None);
+    }
+
+    #[test]
+    fn index_3d() {
+        let grid = Grid::with_dims(&[2, 3, 4], vec![0; 24]).unwrap();
+        assert_eq!(grid.shape(), &[2, 3, 4]);
+        assert_eq!(grid.layer_shape(), (3, 4));
+        assert_eq!(grid.num_layers(), 2);
+        assert_eq!(grid.index(&[1, 0, 0]), Some(12));
+        assert_eq!(grid.index(&[1, 2, 3]), Some(23));
+        assert_eq!(grid.index(&[2, 0, 0]), None);
+        assert_eq!(grid.index_rev(17), Some(vec![1, 1, 1]));
+        assert_eq!(grid.layer_coords(1, 1, 1), Some(vec![1, 1, 1]));
+        assert_eq!(grid.layer_coords(2, 1, 1), None);
+        assert_eq!(grid.layer_of(&[1, 2, 0]), 1);
+
+        let grid = Grid::with_dims(&[2, 3, 1, 2], vec![0; 12]).unwrap();
+        assert_eq!(grid.num_layers(), 6);
+        assert_eq!(grid.layer_coords(4, 0, 1), Some(vec![1, 1, 0, 1]));
+        assert_eq!(grid.layer_of(&[1, 1, 0, 1]), 4);
+
+        assert!(Grid::with_dims(&[4], vec![0; 4]).is_err());
+        assert!(Grid::with_dims(&[2, 2, 2], vec![0; 4]).is_err());
+        assert!(Grid::with_dims(&[usize::MAX, 2], vec![0; 0]).is_err());
     }
 
     #[test]
@@ -169,15 +529,16 @@ mod test {
         let data = vec![1, 2, 3, 4];
 
         let mut grid = Grid::new(2, 2, data).unwrap();
-        assert_eq!(grid.shape(), (2, 2));
+        assert_eq!(grid.shape(), &[2, 2]);
 
-        assert_eq!(grid.get(0, 0), Some(1));
-        assert_eq!(grid.get(0, 1), Some(2));
-        assert_eq!(grid.get(1, 0), Some(3));
-        assert_eq!(grid.get(3, 0), None);
+        assert_eq!(grid.get(&[0, 0]), Some(1));
+        assert_eq!(grid.get(&[0, 1]), Some(2));
+        assert_eq!(grid.get(&[1, 0]), Some(3));
+        assert_eq!(grid.get(&[3, 0]), None);
 
-        assert_eq!(grid.set(0, 0, 10), Ok(()));
-        assert_eq!(grid.get(0, 0), Some(10));
+        assert_eq!(grid.set(&[0, 0], 10), Ok(()));
+        assert_eq!(grid.get(&[0, 0]), Some(10));
+        assert!(grid.set(&[0, 2], 10).is_err());
     }
 
     #[test]
@@ -185,20 +546,226 @@ mod test {
         let data = vec![0; 12];
         let grid = Grid::new(4, 3, data).unwrap();
         assert_eq!(
-            grid.neighbours8(1, 1),
+            grid.neighbours(&[1, 1]),
+            vec![
+                vec![0, 0],
+                vec![0, 1],
+                vec![0, 2],
+                vec![1, 0],
+                vec![1, 2],
+                vec![2, 0],
+                vec![2, 1],
+                vec![2, 2]
+            ]
+        );
+        assert_eq!(
+            grid.neighbours(&[0, 0]),
+            vec![vec![0, 1], vec![1, 0], vec![1, 1]]
+        );
+        assert_eq!(
+            grid.neighbours(&[3, 2]),
+            vec![vec![2, 1], vec![2, 2], vec![3, 1]]
+        );
+    }
+
+    #[test]
+    fn neighbours_3d() {
+        let grid = Grid::with_dims(&[3, 3, 3], vec![0; 27]).unwrap();
+        let neighbours = grid.neighbours(&[1, 1, 1]);
+        assert_eq!(neighbours.len(), 26);
+        assert_eq!(neighbours[0], vec![0, 0, 0]);
+        assert_eq!(neighbours[13], vec![1, 1, 2]);
+        assert_eq!(grid.neighbours(&[0, 0, 0]).len(), 7);
+        assert_eq!(grid.neighbours(&[0, 1, 1]).len(), 17);
+
+        // hexagonal prisms: 6 neighbours in the layer, 7 above and 7 below
+        let grid = grid.with_topology(Topology::Hex);
+        assert_eq!(grid.neighbours(&[1, 1, 1]).len(), 20);
+        assert!(grid.neighbours(&[1, 1, 1]).contains(&vec![0, 1, 1]));
+
+        let grid = Grid::with_dims(&[3, 3, 3], vec![0; 27])
+            .unwrap()
+            .with_wrap(true)
+            .unwrap();
+        assert_eq!(grid.neighbours(&[0, 0, 0]).len(), 26);
+    }
+
+    #[test]
+    fn neighbourhoods() {
+        let grid = Grid::new(5, 5, vec![0; 25]).unwrap();
+        assert_eq!(grid.neighbourhood(), &Neighbourhood::Moore(1));
+        let grid = grid.with_neighbourhood(Neighbourhood::VonNeumann);
+        assert_eq!(
+            grid.neighbours(&[2, 2]),
+            vec![vec![1, 2], vec![2, 1], vec![2, 3], vec![3, 2]]
+        );
+        let grid = grid.with_neighbourhood(Neighbourhood::Moore(2));
+        assert_eq!(grid.neighbours(&[2, 2]).len(), 24);
+        assert_eq!(grid.neighbours(&[0, 0]).len(), 8);
+        let grid = grid.with_neighbourhood(Neighbourhood::Knight);
+        assert_eq!(
+            grid.neighbours(&[2, 2]),
+            vec![
+                vec![0, 1],
+                vec![0, 3],
+                vec![1, 0],
+                vec![1, 4],
+                vec![3, 0],
+                vec![3, 4],
+                vec![4, 1],
+                vec![4, 3]
+            ]
+        );
+        assert_eq!(grid.neighbours(&[0, 0]), vec![vec![1, 2], vec![2, 1]]);
+
+        // in three dimensions
+        let cube = Grid::with_dims(&[5, 5, 5], vec![0; 125]).unwrap();
+        let cube = cube.with_neighbourhood(Neighbourhood::VonNeumann);
+        assert_eq!(cube.neighbours(&[2, 2, 2]).len(), 6);
+        let cube = cube.with_neighbourhood(Neighbourhood::Knight);
+        assert_eq!(cube.neighbours(&[2, 2, 2]).len(), 24);
+
+        // masks stay in the layer of the cell
+        let mask = Neighbourhood::from_mask("x . . . x\n. . o . .\nx . . . x").unwrap();
+        assert_eq!(
+            mask,
+            Neighbourhood::Custom(vec![(-1, -2), (-1, 2), (1, -2), (1, 2)])
+        );
+        let grid = Grid::new(5, 5, vec![0; 25])
+            .unwrap()
+            .with_neighbourhood(mask.clone());
+        assert_eq!(
+            grid.neighbours(&[1, 2]),
+            vec![vec![0, 0], vec![0, 4], vec![2, 0], vec![2, 4]]
+        );
+        let cube = cube.with_neighbourhood(mask);
+        assert_eq!(cube.neighbours(&[2, 2, 2]).len(), 4);
+
+        assert!(Neighbourhood::from_mask("x o .").is_err());
+        assert!(Neighbourhood::from_mask("x x x").is_err());
+        assert!(Neighbourhood::from_mask("x o ?").is_err());
+        assert_eq!(
+            Neighbourhood::from_name("radius-2"),
+            Some(Neighbourhood::Moore(2))
+        );
+        assert_eq!(Neighbourhood::from_name("bishop"), None);
+    }
+
+    #[test]
+    fn neighbours_hex() {
+        // . x x .
+        //  x o x .
+        // . x x .
+        let data = vec![0; 12];
+        let grid = Grid::new(3, 4, data).unwrap().with_topology(Topology::Hex);
+        assert_eq!(grid.topology(), Topology::Hex);
+        assert_eq!(
+            grid.neighbours(&[1, 1]),
             vec![
-                (0, 0),
-                (0, 1),
-                (0, 2),
-                (1, 0),
-                (1, 2),
-                (2, 0),
-                (2, 1),
-                (2, 2)
+                vec![0, 1],
+                vec![0, 2],
+                vec![1, 0],
+                vec![1, 2],
+                vec![2, 1],
+                vec![2, 2]
             ]
         );
-        assert_eq!(grid.neighbours8(0, 0), vec![(0, 1), (1, 0), (1, 1)]);
-        assert_eq!(grid.neighbours8(3, 2), vec![(2, 1), (2, 2), (3, 1)]);
+        // borders
+        assert_eq!(grid.neighbours(&[0, 0]), vec![vec![0, 1], vec![1, 0]]);
+        assert_eq!(grid.neighbours(&[2, 0]), vec![vec![1, 0], vec![2, 1]]);
+        assert_eq!(
+            grid.neighbours(&[1, 3]),
+            vec![vec![0, 3], vec![1, 2], vec![2, 3]]
+        );
+        // square grids keep 8 neighbours
+        let grid = grid.map(|&v| v + 1).with_topology(Topology::Square);
+        assert_eq!(grid.neighbours(&[1, 1]).len(), 8);
+        assert_eq!(grid.get(&[2, 3]), Some(1));
+    }
+
+    #[test]
+    fn neighbours_wrap() {
+        let data = vec![0; 12];
+        let grid = Grid::new(4, 3, data).unwrap().with_wrap(true).unwrap();
+        assert!(grid.wraps());
+        assert_eq!(
+            grid.neighbours(&[0, 0]),
+            vec![
+                vec![3, 2],
+                vec![3, 0],
+                vec![3, 1],
+                vec![0, 2],
+                vec![0, 1],
+                vec![1, 2],
+                vec![1, 0],
+                vec![1, 1]
+            ]
+        );
+        // inner cells are not affected
+        assert_eq!(grid.neighbours(&[1, 1]).len(), 8);
+        // on small grids, cells are reached once
+        let grid = Grid::new(2, 3, vec![0; 6])
+            .unwrap()
+            .with_wrap(true)
+            .unwrap();
+        assert_eq!(
+            grid.neighbours(&[0, 0]),
+            vec![vec![1, 2], vec![1, 0], vec![1, 1], vec![0, 2], vec![0, 1]]
+        );
+        // shifted hexagonal rows only line up across an even number of rows
+        let odd = Grid::new(3, 4, vec![0; 12]).unwrap();
+        assert!(odd.with_topology(Topology::Hex).with_wrap(true).is_err());
+
+        let grid = Grid::new(4, 3, vec![0; 12])
+            .unwrap()
+            .with_topology(Topology::Hex)
+            .with_wrap(true)
+            .unwrap();
+        assert_eq!(
+            grid.neighbours(&[0, 0]),
+            vec![
+                vec![3, 2],
+                vec![3, 0],
+                vec![0, 2],
+                vec![0, 1],
+                vec![1, 2],
+                vec![1, 0]
+            ]
+        );
+        assert_eq!(
+            grid.neighbours(&[3, 2]),
+            vec![
+                vec![2, 2],
+                vec![2, 0],
+                vec![3, 1],
+                vec![3, 0],
+                vec![0, 2],
+                vec![0, 0]
+            ]
+        );
+    }
+
+    #[test]
+    fn mask() {
+        // x . x
+        // x x x
+        let grid = Grid::new(2, 3, vec![0; 6])
+            .unwrap()
+            .with_mask(&[true, false, true, true, true, true])
+            .unwrap();
+        assert!(grid.exists(&[0, 0]));
+        assert!(!grid.exists(&[0, 1]));
+        assert!(!grid.exists(&[2, 0]));
+        assert_eq!(grid.num_cells(), 5);
+        assert_eq!(grid.len(), 6);
+        assert_eq!(grid.neighbours(&[0, 0]), vec![vec![1, 0], vec![1, 1]]);
+        assert_eq!(grid.neighbours(&[1, 1]).len(), 4);
+        // the mask is kept by map, and holes are blank
+        assert_eq!(format!("{}", grid.map(|_| 1)), "1   1\n1 1 1");
+        assert!(Grid::new(2, 3, vec![0; 6])
+            .unwrap()
+            .with_mask(&[true])
+            .is_err());
     }
 
     #[test]
@@ -206,6 +773,8 @@ mod test {
         let data = vec![1, 2, 3, 4];
         let grid = Grid::new(2, 2, data).unwrap();
         assert_eq!(format!("{}", grid), "1 2\n3 4");
+        let grid = Grid::with_dims(&[2, 1, 2], vec![1, 2, 3, 4]).unwrap();
+        assert_eq!(format!("{}", grid), "1 2\n\n3 4");
     }
 
     #[test]
@@ -216,9 +785,9 @@ mod test {
         let mut grid_iter = grid.iter();
         for e_i in 0..h {
             for e_j in 0..w {
-                let expected_value = grid.index(e_i, e_j).unwrap();
-                let (i, j, &val) = grid_iter.next().unwrap();
-                assert_eq!((e_i, e_j), (i, j), "Grid indices don't match");
+                let expected_value = grid.index(&[e_i, e_j]).unwrap();
+                let (coords, &val) = grid_iter.next().unwrap();
+                assert_eq!(vec![e_i, e_j], coords, "Grid indices don't match");
                 assert_eq!(expected_value, val, "Grid values don't match");
             }
         }
@@ -1,3 +1,5 @@
-pub mod grid;
+pub mod client;
 pub mod field;
-pub mod client;
\ No newline at end of file
+pub mod grid;
+pub mod mode;
+pub mod shape;
@@ -0,0 +1,244 @@
+use super::client::{Client, Event, GameState};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+/// Rules of a game, on top of the rules of the board
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mode {
+    Classic,
+    /// The game is lost when the time runs out
+    Countdown(Duration),
+    /// Clear as many boards as possible before the time runs out
+    TimeAttack(Duration),
+    /// The game is lost when a move takes longer than the limit
+    MoveLimit(Duration),
+}
+
+impl Mode {
+    pub fn from_name(name: &str, limit: Duration) -> Option<Mode> {
+        match name {
+            "classic" => Some(Mode::Classic),
+            "countdown" => Some(Mode::Countdown(limit)),
+            "time-attack" => Some(Mode::TimeAttack(limit)),
+            "move-limit" => Some(Mode::MoveLimit(limit)),
+            _ => None,
+        }
+    }
+}
+
+/// Generates the boards of a game
+pub type BoardMaker = Box<dyn Fn() -> Result<Client, String>>;
+
+/// Game played according to a mode, over one or several boards
+pub struct Game {
+    pub client: Client,
+    mode: Mode,
+    new_board: Option<BoardMaker>,
+    /// Start of the first board
+    start_time: Option<Instant>,
+    boards_won: usize,
+    /// Followers of the game, over all its boards
+    subscribers: Vec<Sender<Event>>,
+}
+
+fn forward(client: &mut Client, events: Sender<Event>) {
+    client.add_listener(move |event| {
+        let _ = events.send(event.clone());
+    });
+}
+
+impl Game {
+    pub fn new(mode: Mode, new_board: BoardMaker) -> Result<Game, String> {
+        //! Starts a game on a board from `new_board`, which also provides the
+        //! following boards of a time attack
+        let client = new_board()?;
+        Ok(Game {
+            client,
+            mode,
+            new_board: Some(new_board),
+            start_time: None,
+            boards_won: 0,
+            subscribers: vec![],
+        })
+    }
+
+    pub fn from_client(client: Client) -> Game {
+        //! Classic game on a single board
+        Game {
+            client,
+            mode: Mode::Classic,
+            new_board: None,
+            start_time: None,
+            boards_won: 0,
+            subscribers: vec![],
+        }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    pub fn subscribe(&mut self) -> Receiver<Event> {
+        //! Sends every change of the game to the receiver, on the following
+        //! boards of a time attack too
+        let (events, receiver) = mpsc::channel();
+        forward(&mut self.client, events.clone());
+        self.subscribers.push(events);
+        receiver
+    }
+
+    pub fn boards_won(&self) -> usize {
+        self.boards_won
+    }
+
+    pub fn start_time(&self) -> Option<Instant> {
+        self.start_time.or_else(|| self.client.start_time())
+    }
+
+    pub fn remaining(&self, now: Instant) -> Option<Duration> {
+        //! Time left before the current limit, if the mode has one
+        //!
+        //! Clocks only start with the first move.
+        let (start, limit) = match self.mode {
+            Mode::Classic => return None,
+            Mode::Countdown(limit) | Mode::TimeAttack(limit) => (self.start_time(), limit),
+            Mode::MoveLimit(limit) => (self.client.last_move_time(), limit),
+        };
+        let elapsed = start.map_or(Duration::from_secs(0), |start| {
+            now.saturating_duration_since(start)
+        });
+        Some(limit.checked_sub(elapsed).unwrap_or_default())
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.client.get_game_state() != GameState::Running
+    }
+
+    pub fn tick(&mut self, now: Instant) -> GameState {
+        //! Applies the time limits and moves on to the next board of a time
+        //! attack; returns the state of the current board
+        if self.start_time.is_none() {
+            self.start_time = self.client.start_time();
+        }
+        if self.client.get_game_state() == GameState::Won {
+            if let (Mode::TimeAttack(_), Some(new_board)) = (self.mode, &self.new_board) {
+                if self.remaining(now) != Some(Duration::from_secs(0)) {
+                    if let Ok(mut client) = new_board() {
+                        for events in &self.subscribers {
+                            forward(&mut client, events.clone());
+                        }
+                        self.boards_won += 1;
+                        self.client = client;
+                    }
+                }
+            }
+        }
+        let started = match self.mode {
+            Mode::MoveLimit(_) => self.client.last_move_time().is_some(),
+            _ => self.start_time.is_some(),
+        };
+        if started && self.remaining(now) == Some(Duration::from_secs(0)) {
+            self.client.time_out();
+        }
+        self.client.get_game_state()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::super::client::CellState;
+    use super::*;
+
+    fn empty_board() -> Result<Client, String> {
+        Ok(Client::new_random(2, 2, 0))
+    }
+
+    #[test]
+    fn countdown() {
+        let mode = Mode::from_name("countdown", Duration::from_secs(60)).unwrap();
+        let mut game = Game::new(mode, Box::new(empty_board)).unwrap();
+        let now = Instant::now();
+        // the clock waits for the first move
+        assert_eq!(game.remaining(now), Some(Duration::from_secs(60)));
+        assert_eq!(
+            game.tick(now + Duration::from_secs(100)),
+            GameState::Running
+        );
+        game.client.flag(&[0, 0]);
+        let start = game.client.start_time().unwrap();
+        assert_eq!(game.tick(start), GameState::Running);
+        assert_eq!(
+            game.remaining(start + Duration::from_secs(15)),
+            Some(Duration::from_secs(45))
+        );
+        assert_eq!(game.tick(start + Duration::from_secs(60)), GameState::Lost);
+        assert!(game.is_over());
+    }
+
+    #[test]
+    fn time_attack() {
+        let mode = Mode::TimeAttack(Duration::from_secs(60));
+        let mut game = Game::new(mode, Box::new(empty_board)).unwrap();
+        let events = game.subscribe();
+        game.client.query_smart(&[0, 0]);
+        let start = game.start_time().unwrap();
+        assert_eq!(game.client.submit(), Ok(GameState::Won));
+        // a new board replaces the cleared one, on the same clock
+        assert_eq!(
+            game.tick(start + Duration::from_secs(10)),
+            GameState::Running
+        );
+        assert_eq!(game.boards_won(), 1);
+        assert_eq!(game.client.start_time(), None);
+        assert_eq!(
+            game.remaining(start + Duration::from_secs(10)),
+            Some(Duration::from_secs(50))
+        );
+        // the events of the new board follow those of the first one
+        game.client.flag(&[0, 0]);
+        let events: Vec<Event> = events.try_iter().collect();
+        assert_eq!(events.len(), 8);
+        assert_eq!(events[5], Event::GameOver(GameState::Won));
+        assert!(matches!(events[6], Event::TimerStarted(_)));
+        assert_eq!(
+            events[7],
+            Event::FlagChanged(vec![0, 0], CellState::Flagged)
+        );
+        assert_eq!(game.tick(start + Duration::from_secs(61)), GameState::Lost);
+        assert_eq!(game.boards_won(), 1);
+    }
+
+    #[test]
+    fn move_limit() {
+        let mut game = Game::new(
+            Mode::MoveLimit(Duration::from_secs(5)),
+            Box::new(empty_board),
+        )
+        .unwrap();
+        game.client.flag(&[0, 0]);
+        let first_move = game.client.last_move_time().unwrap();
+        assert_eq!(
+            game.tick(first_move + Duration::from_secs(4)),
+            GameState::Running
+        );
+        game.client.flag(&[0, 0]);
+        let second_move = game.client.last_move_time().unwrap();
+        assert_eq!(
+            game.remaining(second_move + Duration::from_secs(1)),
+            Some(Duration::from_secs(4))
+        );
+        assert_eq!(
+            game.tick(second_move + Duration::from_secs(5)),
+            GameState::Lost
+        );
+
+        let mut classic = Game::from_client(Client::new_random(2, 2, 0));
+        assert_eq!(classic.remaining(Instant::now()), None);
+        classic.client.flag(&[0, 0]);
+        assert_eq!(
+            classic.tick(Instant::now() + Duration::from_secs(3600)),
+            GameState::Running
+        );
+        assert_eq!(Mode::from_name("blitz", Duration::from_secs(1)), None);
+    }
+}
@@ -0,0 +1,115 @@
+/// Outline of a board, cut out of its bounding rectangle
+#[derive(Debug, Clone, PartialEq)]
+pub enum Shape {
+    Rectangle,
+    /// Ellipse touching the sides of the rectangle
+    Circle,
+    Heart,
+    /// Cells listed row by row, false for holes
+    Custom(Vec<bool>),
+}
+
+impl Shape {
+    pub fn from_name(name: &str) -> Option<Shape> {
+        match name {
+            "rectangle" => Some(Shape::Rectangle),
+            "circle" => Some(Shape::Circle),
+            "heart" => Some(Shape::Heart),
+            _ => None,
+        }
+    }
+
+    pub fn from_text(text: &str) -> Result<(usize, usize, Shape), String> {
+        //! Reads a board drawn with '#' for cells and '.' or ' ' for holes
+        //!
+        //! Returns the height and width of the board with its shape. Short
+        //! lines are completed with holes.
+        let lines: Vec<&str> = text.lines().collect();
+        let height = lines.len();
+        let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        let mut mask = vec![false; height * width];
+        for (row, line) in lines.iter().enumerate() {
+            for (col, c) in line.chars().enumerate() {
+                match c {
+                    '#' => mask[row * width + col] = true,
+                    '.' | ' ' => (),
+                    _ => return Err(format!("Unexpected '{}' in board shape", c)),
+                }
+            }
+        }
+        if !mask.contains(&true) {
+            return Err(String::from("The board shape has no cell"));
+        }
+        Ok((height, width, Shape::Custom(mask)))
+    }
+
+    pub fn mask(&self, height: usize, width: usize) -> Result<Vec<bool>, String> {
+        //! Cells of a `height` x `width` layer that are part of the board
+        let center = |i: usize, size: usize| (i as f64 + 0.5) / size as f64 * 2.0 - 1.0;
+        let cells = (0..height).flat_map(|row| (0..width).map(move |col| (row, col)));
+        match self {
+            Shape::Rectangle => Ok(vec![true; height * width]),
+            Shape::Circle => Ok(cells
+                .map(|(row, col)| {
+                    let (y, x) = (center(row, height), center(col, width));
+                    x * x + y * y <= 1.0
+                })
+                .collect()),
+            Shape::Heart => Ok(cells
+                .map(|(row, col)| {
+                    // (x² + y² - 1)³ - x²y³ <= 0, for x in [-1.2, 1.2], y in [-1.1, 1.3]
+                    let x = center(col, width) * 1.2;
+                    let y = 0.1 - center(row, height) * 1.2;
+                    (x * x + y * y - 1.0).powi(3) - x * x * y.powi(3) <= 0.0
+                })
+                .collect()),
+            Shape::Custom(mask) if mask.len() == height * width => Ok(mask.clone()),
+            Shape::Custom(_) => Err(String::from(
+                "The board shape does not match the height and width",
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn draw(mask: &[bool], width: usize) -> Vec<String> {
+        mask.chunks(width)
+            .map(|row| row.iter().map(|&m| if m { '#' } else { '.' }).collect())
+            .collect()
+    }
+
+    #[test]
+    fn shapes() {
+        assert_eq!(Shape::Rectangle.mask(2, 2), Ok(vec![true; 4]));
+        let circle = Shape::from_name("circle").unwrap().mask(5, 5).unwrap();
+        assert_eq!(
+            draw(&circle, 5),
+            vec![".###.", "#####", "#####", "#####", ".###."]
+        );
+        let heart = Shape::Heart.mask(7, 9).unwrap();
+        assert_eq!(
+            draw(&heart, 9),
+            vec![
+                ".###.###.",
+                "#########",
+                "#########",
+                ".#######.",
+                ".#######.",
+                "...###...",
+                "....#....",
+            ]
+        );
+
+        let (height, width, shape) = Shape::from_text("#.#\n###\n #").unwrap();
+        assert_eq!((height, width), (3, 3));
+        let mask = shape.mask(3, 3).unwrap();
+        assert_eq!(draw(&mask, 3), vec!["#.#", "###", ".#."]);
+        assert!(shape.mask(3, 4).is_err());
+        assert!(Shape::from_text("#x#").is_err());
+        assert!(Shape::from_text("...").is_err());
+        assert_eq!(Shape::from_name("square"), None);
+    }
+}
@@ -0,0 +1,492 @@
+use crate::daily::{self, Date};
+use crate::minefield::client::Client;
+use crate::minefield::field::Minefield;
+use crate::minefield::grid::{Grid, Neighbourhood, Topology};
+use crate::minefield::mode::{Game, Mode};
+use crate::minefield::shape::Shape;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use std::time::Duration;
+
+pub const USAGE: &str = "Options:
+    --height N      number of rows (default: 12)
+    --width N       number of columns (default: 30)
+    --bombs N       number of bombs (default: 20)
+    --layers N      number of layers, for 3D boards (default: 1)
+    --stack K       a cell can hold up to K bombs (default: 1)
+    --anti-mines N  number of anti-mines, which subtract one from the
+                    numbers around them (default: 0)
+    --liar          every number is off by exactly one
+    --mode M        classic (default); countdown: lose when the time is up;
+                    time-attack: clear as many boards as possible in time;
+                    move-limit: lose when a move takes too long (tui_client)
+    --time SECONDS  time limit of the mode (default: 300, 10 per move)
+    --daily         board of the day: the same for everyone with the same
+                    options
+    --date YYYY-MM-DD
+                    replay the board of a past day
+    --seed N        board generated from a number: the same for every game
+                    with the same seed and options
+    --lives N       number of bombs that can be dug before losing; each one
+                    costs points (default: 1)
+    --shape S       outline of the board: rectangle (default), circle or heart
+    --shape-file FILE
+                    board drawn with '#' for cells and '.' for holes; sets
+                    the height and width
+    --hex           hexagonal cells, with 6 neighbours
+    --wrap          edges wrap around to the opposite side (torus)
+    --neighbours N  cells counted by the numbers: moore (default, 8 cells),
+                    von-neumann (4), radius-2 (24) or knight (8)
+    --neighbours-mask FILE
+                    custom neighbourhood, drawn with 'o' for the cell and 'x'
+                    for its neighbours
+    --help          print this message";
+
+/// Board settings shared by the command line clients
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameOptions {
+    pub height: usize,
+    pub width: usize,
+    /// More than one layer makes a three-dimensional board
+    pub layers: usize,
+    pub num_bombs: usize,
+    /// Largest number of bombs in a cell
+    pub max_per_cell: u8,
+    pub num_anti_mines: usize,
+    /// Numbers are off by one
+    pub liar: bool,
+    pub lives: usize,
+    /// Time limits, over one or several boards
+    pub mode: Mode,
+    /// Day of the daily challenge, whose board derives from the date
+    pub daily: Option<Date>,
+    /// Seed of the board, to replay it
+    pub seed: Option<u64>,
+    pub topology: Topology,
+    pub wrap: bool,
+    pub neighbourhood: Neighbourhood,
+    pub shape: Shape,
+}
+
+impl Default for GameOptions {
+    fn default() -> GameOptions {
+        GameOptions {
+            height: 12,
+            width: 30,
+            layers: 1,
+            num_bombs: 20,
+            max_per_cell: 1,
+            num_anti_mines: 0,
+            liar: false,
+            lives: 1,
+            mode: Mode::Classic,
+            daily: None,
+            seed: None,
+            topology: Topology::Square,
+            wrap: false,
+            neighbourhood: Neighbourhood::default(),
+            shape: Shape::Rectangle,
+        }
+    }
+}
+
+fn parse_number(option: &str, value: Option<String>) -> Result<usize, String> {
+    let value = value.ok_or(format!("Missing value for {}", option))?;
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value for {}: {}", option, value))
+}
+
+impl GameOptions {
+    pub fn parse<I>(args: I) -> Result<GameOptions, String>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        //! Reads options from command line arguments (without the program name)
+        //!
+        //! Errors carry a message to display, followed by the usage
+        let mut options = GameOptions::default();
+        let mut mode = String::from("classic");
+        let mut time_limit = None;
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            match &arg[..] {
+                "--height" => options.height = parse_number(&arg, args.next())?,
+                "--width" => options.width = parse_number(&arg, args.next())?,
+                "--bombs" => options.num_bombs = parse_number(&arg, args.next())?,
+                "--layers" => options.layers = parse_number(&arg, args.next())?,
+                "--stack" => {
+                    let max = parse_number(&arg, args.next())?;
+                    if max == 0 || max > 20 {
+                        return Err(String::from("Cells can hold 1 to 20 bombs"));
+                    }
+                    options.max_per_cell = max as u8;
+                }
+                "--anti-mines" => options.num_anti_mines = parse_number(&arg, args.next())?,
+                "--liar" => options.liar = true,
+                "--lives" => options.lives = parse_number(&arg, args.next())?,
+                "--mode" => mode = args.next().ok_or("Missing value for --mode")?,
+                "--daily" => options.daily = Some(Date::today()),
+                "--date" => {
+                    let date = args.next().ok_or("Missing value for --date")?;
+                    options.daily = Some(Date::parse(&date)?);
+                }
+                "--seed" => options.seed = Some(parse_number(&arg, args.next())? as u64),
+                "--time" => time_limit = Some(parse_number(&arg, args.next())? as u64),
+                "--hex" => options.topology = Topology::Hex,
+                "--wrap" => options.wrap = true,
+                "--neighbours" => {
+                    let name = args.next().ok_or("Missing value for --neighbours")?;
+                    options.neighbourhood = Neighbourhood::from_name(&name)
+                        .ok_or(format!("Unknown neighbourhood: {}\n{}", name, USAGE))?;
+                }
+                "--shape" => {
+                    let name = args.next().ok_or("Missing value for --shape")?;
+                    options.shape = Shape::from_name(&name)
+                        .ok_or(format!("Unknown shape: {}\n{}", name, USAGE))?;
+                }
+                "--shape-file" => {
+                    let path = args.next().ok_or("Missing value for --shape-file")?;
+                    let text = std::fs::read_to_string(&path)
+                        .map_err(|e| format!("Cannot read {}: {}", path, e))?;
+                    let (height, width, shape) = Shape::from_text(&text)?;
+                    options.height = height;
+                    options.width = width;
+                    options.shape = shape;
+                }
+                "--neighbours-mask" => {
+                    let path = args.next().ok_or("Missing value for --neighbours-mask")?;
+                    let mask = std::fs::read_to_string(&path)
+                        .map_err(|e| format!("Cannot read {}: {}", path, e))?;
+                    options.neighbourhood = Neighbourhood::from_mask(&mask)?;
+                }
+                "--help" | "-h" => return Err(String::from(USAGE)),
+                _ => return Err(format!("Unknown option: {}\n{}", arg, USAGE)),
+            }
+        }
+        if options.height == 0 || options.width == 0 || options.layers == 0 {
+            return Err(String::from("The board cannot be empty"));
+        }
+        let default_limit = if mode == "move-limit" { 10 } else { 300 };
+        let time_limit = Duration::from_secs(time_limit.unwrap_or(default_limit));
+        options.mode = Mode::from_name(&mode, time_limit)
+            .ok_or(format!("Unknown mode: {}\n{}", mode, USAGE))?;
+        if time_limit.as_secs() == 0 {
+            return Err(String::from("The time limit cannot be 0"));
+        }
+        if options.daily.is_some() && options.seed.is_some() {
+            return Err(String::from("Daily challenges have their own seed"));
+        }
+        if options.daily.is_some() || options.seed.is_some() {
+            if let Mode::TimeAttack(_) = options.mode {
+                return Err(String::from(
+                    "Seeded boards cannot be repeated in time attacks",
+                ));
+            }
+        }
+        if options.lives == 0 {
+            return Err(String::from("At least one life is needed"));
+        }
+        if options.topology == Topology::Hex && options.neighbourhood != Neighbourhood::default() {
+            return Err(String::from("Hexagonal cells always have 6 neighbours"));
+        }
+        Ok(options)
+    }
+
+    pub fn from_args() -> Result<GameOptions, String> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn layout(&self) -> Result<Grid<()>, String> {
+        //! Empty grid with the shape and topology of the board
+        let dims: &[usize] = if self.layers > 1 {
+            &[self.layers, self.height, self.width]
+        } else {
+            &[self.height, self.width]
+        };
+        let len = dims
+            .iter()
+            .try_fold(1usize, |len, &dim| len.checked_mul(dim))
+            .ok_or_else(|| String::from("The board is too large"))?;
+        let layout = Grid::with_dims(dims, vec![(); len])?;
+        // every layer has the same outline
+        let mask = self
+            .shape
+            .mask(self.height, self.width)?
+            .repeat(self.layers);
+        Ok(layout
+            .with_mask(&mask)?
+            .with_topology(self.topology)
+            .with_wrap(self.wrap)?
+            .with_neighbourhood(self.neighbourhood.clone()))
+    }
+
+    pub fn preset(&self) -> String {
+        //! Description of the board options, which changes with every option
+        //! that changes the board
+        //!
+        //! Daily boards derive from it: it is written out by hand, so that it
+        //! stays the same across versions
+        let topology = match self.topology {
+            Topology::Square => String::from("Square"),
+            Topology::Hex => String::from("Hex"),
+        };
+        let neighbourhood = match &self.neighbourhood {
+            Neighbourhood::Moore(distance) => format!("Moore({})", distance),
+            Neighbourhood::VonNeumann => String::from("VonNeumann"),
+            Neighbourhood::Knight => String::from("Knight"),
+            Neighbourhood::Custom(offsets) => {
+                let offsets: Vec<String> = offsets
+                    .iter()
+                    .map(|(row, col)| format!("({}, {})", row, col))
+                    .collect();
+                format!("Custom([{}])", offsets.join(", "))
+            }
+        };
+        let shape = match &self.shape {
+            Shape::Rectangle => String::from("Rectangle"),
+            Shape::Circle => String::from("Circle"),
+            Shape::Heart => String::from("Heart"),
+            Shape::Custom(cells) => {
+                let cells: Vec<&str> = cells
+                    .iter()
+                    .map(|&cell| if cell { "true" } else { "false" })
+                    .collect();
+                format!("Custom([{}])", cells.join(", "))
+            }
+        };
+        format!(
+            "{}x{}x{} bombs={} stack={} anti-mines={} liar={} {} wrap={} {} {}",
+            self.layers,
+            self.height,
+            self.width,
+            self.num_bombs,
+            self.max_per_cell,
+            self.num_anti_mines,
+            self.liar,
+            topology,
+            self.wrap,
+            neighbourhood,
+            shape
+        )
+    }
+
+    pub fn new_client(&self) -> Result<Client, String> {
+        //! Random board, or the board of the seed or of the day for daily
+        //! challenges
+        //!
+        //! ChaCha8 generates the same boards from a seed in every version of
+        //! rand_chacha, unlike `StdRng`
+        let seed = self
+            .seed
+            .or_else(|| self.daily.map(|date| daily::seed(date, &self.preset())));
+        let mut rng = match seed {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+            None => ChaCha8Rng::from_entropy(),
+        };
+        let layout = self.layout()?;
+        let mut field =
+            Minefield::with_stacks_rng(&layout, self.num_bombs, self.max_per_cell, &mut rng)?
+                .with_random_anti_mines_rng(self.num_anti_mines, &mut rng)?;
+        if self.liar {
+            field = field.with_liars_rng(&mut rng);
+        }
+        Ok(Client::from_minefield(field).with_lives(self.lives))
+    }
+
+    pub fn new_game(&self) -> Result<Game, String> {
+        //! Game in the chosen mode, where every board follows these options
+        let options = self.clone();
+        Game::new(self.mode, Box::new(move || options.new_client()))
+    }
+}
+
+pub fn take_option(args: &mut Vec<String>, option: &str) -> Result<Option<String>, String> {
+    //! Removes an option of a program, with its value, from the board options
+    let i = match args.iter().position(|arg| arg == option) {
+        Some(i) => i,
+        None => return Ok(None),
+    };
+    if i + 1 == args.len() {
+        return Err(format!("Missing value for {}", option));
+    }
+    Ok(args.drain(i..i + 2).nth(1))
+}
+
+pub fn exit_on_error<T>(result: Result<T, String>) -> T {
+    //! Value of a result, or exits after printing the error
+    result.unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        std::process::exit(1);
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn parse() {
+        assert_eq!(GameOptions::parse(args("")), Ok(GameOptions::default()));
+        let options = GameOptions::parse(args("--hex --height 5 --bombs 3")).unwrap();
+        assert_eq!(
+            options,
+            GameOptions {
+                height: 5,
+                width: 30,
+                layers: 1,
+                num_bombs: 3,
+                max_per_cell: 1,
+                num_anti_mines: 0,
+                liar: false,
+                lives: 1,
+                mode: Mode::Classic,
+                daily: None,
+                seed: None,
+                topology: Topology::Hex,
+                wrap: false,
+                neighbourhood: Neighbourhood::default(),
+                shape: Shape::Rectangle,
+            }
+        );
+        let client = options.new_client().unwrap();
+        assert_eq!(client.get_state().shape(), &[5, 30]);
+        assert_eq!(client.get_state().topology(), Topology::Hex);
+        assert_eq!(client.num_bombs(), 3);
+
+        assert!(GameOptions::parse(args("--height")).is_err());
+        assert!(GameOptions::parse(args("--width x")).is_err());
+        assert!(GameOptions::parse(args("--width 0")).is_err());
+        assert!(GameOptions::parse(args("--square")).is_err());
+        assert_eq!(GameOptions::parse(args("--help")), Err(String::from(USAGE)));
+        let too_many_bombs = GameOptions::parse(args("--height 2 --width 2 --bombs 5")).unwrap();
+        assert!(too_many_bombs.new_client().is_err());
+    }
+
+    #[test]
+    fn wrap() {
+        let torus = GameOptions::parse(args("--wrap --hex --height 4")).unwrap();
+        assert!(torus.new_client().unwrap().get_state().wraps());
+        let odd = GameOptions::parse(args("--wrap --hex --height 5")).unwrap();
+        assert_eq!(
+            odd.new_client().err(),
+            Some(String::from(
+                "Wrapping hexagonal boards need an even number of rows"
+            ))
+        );
+    }
+
+    #[test]
+    fn layers() {
+        let cube = GameOptions::parse(args("--layers 4 --height 3")).unwrap();
+        assert_eq!(cube.new_client().unwrap().get_state().shape(), &[4, 3, 30]);
+        assert!(GameOptions::parse(args("--layers 0")).is_err());
+    }
+
+    #[test]
+    fn neighbours() {
+        let knight = GameOptions::parse(args("--neighbours knight")).unwrap();
+        assert_eq!(
+            knight.new_client().unwrap().get_state().neighbourhood(),
+            &Neighbourhood::Knight
+        );
+        assert!(GameOptions::parse(args("--neighbours bishop")).is_err());
+        assert!(GameOptions::parse(args("--neighbours knight --hex")).is_err());
+        assert!(GameOptions::parse(args("--neighbours-mask /nonexistent")).is_err());
+    }
+
+    #[test]
+    fn stack() {
+        let stacked =
+            GameOptions::parse(args("--stack 3 --height 2 --width 2 --bombs 12")).unwrap();
+        assert_eq!(stacked.new_client().unwrap().minefield.max_per_cell(), 3);
+        assert!(GameOptions::parse(args("--stack 0")).is_err());
+    }
+
+    #[test]
+    fn shape() {
+        let heart = GameOptions::parse(args("--shape heart --height 7 --width 9")).unwrap();
+        assert_eq!(heart.new_client().unwrap().get_state().num_cells(), 42);
+        assert!(GameOptions::parse(args("--shape blob")).is_err());
+        assert!(GameOptions::parse(args("--shape-file /nonexistent")).is_err());
+    }
+
+    #[test]
+    fn variants() {
+        let variant = GameOptions::parse(args("--anti-mines 4 --liar")).unwrap();
+        let minefield = variant.new_client().unwrap().minefield;
+        assert_eq!(minefield.num_anti_mines(), 4);
+        assert_eq!(minefield.num_bombs(), 24);
+        assert!(minefield.is_liar());
+    }
+
+    #[test]
+    fn lives() {
+        let casual = GameOptions::parse(args("--lives 3")).unwrap();
+        assert_eq!(casual.new_client().unwrap().lives_left(), 3);
+        assert!(GameOptions::parse(args("--lives 0")).is_err());
+    }
+
+    #[test]
+    fn modes() {
+        let countdown = GameOptions::parse(args("--mode countdown --time 90")).unwrap();
+        assert_eq!(countdown.mode, Mode::Countdown(Duration::from_secs(90)));
+        let game = countdown.new_game().unwrap();
+        assert_eq!(game.client.get_state().shape(), &[12, 30]);
+        let move_limit = GameOptions::parse(args("--mode move-limit")).unwrap();
+        assert_eq!(move_limit.mode, Mode::MoveLimit(Duration::from_secs(10)));
+        assert!(GameOptions::parse(args("--mode blitz")).is_err());
+        assert!(GameOptions::parse(args("--mode countdown --time 0")).is_err());
+    }
+
+    #[test]
+    fn daily() {
+        let daily = GameOptions::parse(args("--date 2024-02-29 --liar")).unwrap();
+        let board = daily.new_client().unwrap().minefield.to_string();
+        assert_eq!(board, daily.new_client().unwrap().minefield.to_string());
+        let other_day = GameOptions::parse(args("--date 2024-03-01 --liar")).unwrap();
+        assert_ne!(board, other_day.new_client().unwrap().minefield.to_string());
+        assert_ne!(daily.preset(), GameOptions::default().preset());
+        // boards of past days must not change with new versions
+        let small = GameOptions::parse(args("--date 2024-02-29 --height 4 --width 6 --bombs 5"));
+        let small = small.unwrap();
+        assert_eq!(
+            small.preset(),
+            "1x4x6 bombs=5 stack=1 anti-mines=0 liar=false Square wrap=false Moore(1) Rectangle"
+        );
+        assert_eq!(
+            small.new_client().unwrap().minefield.to_string(),
+            "0 1 2 X 3 X\n0 1 X 2 3 X\n1 2 1 1 1 1\nX 1 0 0 0 0"
+        );
+        assert!(GameOptions::parse(args("--date 2024-02-30")).is_err());
+        assert!(GameOptions::parse(args("--daily --mode time-attack")).is_err());
+    }
+
+    #[test]
+    fn seed() {
+        let seeded = GameOptions::parse(args("--seed 42 --height 5")).unwrap();
+        let board = seeded.new_client().unwrap().minefield.to_string();
+        assert_eq!(board, seeded.new_client().unwrap().minefield.to_string());
+        assert!(GameOptions::parse(args("--seed 42 --daily")).is_err());
+        assert!(GameOptions::parse(args("--seed 42 --mode time-attack")).is_err());
+    }
+
+    #[test]
+    fn take_options() {
+        let mut line = args("--height 5 --games 3 --bombs 2");
+        assert_eq!(
+            take_option(&mut line, "--games"),
+            Ok(Some(String::from("3")))
+        );
+        assert_eq!(take_option(&mut line, "--games"), Ok(None));
+        assert_eq!(line, args("--height 5 --bombs 2"));
+        assert_eq!(
+            take_option(&mut args("--bombs 2 --games"), "--games"),
+            Err(String::from("Missing value for --games"))
+        );
+    }
+}
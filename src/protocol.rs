@@ -0,0 +1,618 @@
+use crate::minefield::client::{CellState, Client, GameState};
+use crate::minefield::field::Cell;
+use crate::minefield::grid::Grid;
+use crate::race::{Progress, RaceState, Standings};
+use crate::registry::Player;
+use std::time::Duration;
+
+/// Request of the line protocol spoken by the game server
+///
+/// Moves on a cell may carry the state the player saw the cell in: when the
+/// cell has changed since, the move is refused instead of undoing the move of
+/// another player.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    /// Starts a game, with the options of the command line clients
+    New(Vec<String>),
+    Dig(u64, Vec<usize>, Option<CellState>),
+    Flag(u64, Vec<usize>, Option<CellState>),
+    Chord(u64, Vec<usize>, Option<CellState>),
+    Submit(u64),
+    State(u64),
+    /// Plays a game as a named player, along with the other players; the
+    /// token of the player is needed to play as them again
+    Join(u64, String, Option<String>),
+    /// Shows the other players where the player is looking
+    Cursor(u64, Vec<usize>),
+    /// Players of a game, with their cursors
+    Players(u64),
+    /// Player who last changed each cell
+    Owners(u64),
+    /// Options the game was started with
+    Options(u64),
+    /// Starts a race, with the options of the command line clients
+    Race(Vec<String>),
+    /// Enters a race as a named player; the token of the racer is needed to
+    /// enter again
+    Enter(u64, String, Option<String>),
+    /// Progress of the racers of a race
+    Standings(u64),
+    Quit,
+}
+
+fn parse_id(word: Option<&str>) -> Result<u64, String> {
+    let word = word.ok_or("Missing game id")?;
+    word.parse()
+        .map_err(|_| format!("Invalid game id: {}", word))
+}
+
+pub fn parse_coords(word: Option<&str>) -> Result<Vec<usize>, String> {
+    //! Reads coordinates written as `row,col` (`layer,row,col` in 3D)
+    let word = word.ok_or("Missing coordinates")?;
+    word.split(',')
+        .map(|c| c.parse())
+        .collect::<Result<Vec<usize>, _>>()
+        .map_err(|_| format!("Invalid coordinates: {}", word))
+}
+
+pub fn format_coords(coords: &[usize]) -> String {
+    let coords: Vec<String> = coords.iter().map(|c| c.to_string()).collect();
+    coords.join(",")
+}
+
+pub fn valid_name(name: &str) -> bool {
+    //! Player names are single words of letters, digits, `-` and `_`
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+}
+
+impl Request {
+    pub fn parse(line: &str) -> Result<Request, String> {
+        let mut words = line.split_whitespace();
+        let command = words.next().ok_or("Empty request")?;
+        let request = match command {
+            "new" => return Ok(Request::New(words.map(String::from).collect())),
+            "race" => return Ok(Request::Race(words.map(String::from).collect())),
+            "dig" | "flag" | "chord" => {
+                let id = parse_id(words.next())?;
+                let coords = parse_coords(words.next())?;
+                let expected = match words.next() {
+                    Some(word) => Some(parse_cellstate_token(word)?),
+                    None => None,
+                };
+                match command {
+                    "dig" => Request::Dig(id, coords, expected),
+                    "flag" => Request::Flag(id, coords, expected),
+                    _ => Request::Chord(id, coords, expected),
+                }
+            }
+            "submit" => Request::Submit(parse_id(words.next())?),
+            "state" => Request::State(parse_id(words.next())?),
+            "join" | "enter" => {
+                let id = parse_id(words.next())?;
+                let name = words.next().ok_or("Missing player name")?;
+                if !valid_name(name) {
+                    return Err(format!("Invalid player name: {}", name));
+                }
+                let token = words.next().map(String::from);
+                match command {
+                    "join" => Request::Join(id, name.to_string(), token),
+                    _ => Request::Enter(id, name.to_string(), token),
+                }
+            }
+            "cursor" => Request::Cursor(parse_id(words.next())?, parse_coords(words.next())?),
+            "players" => Request::Players(parse_id(words.next())?),
+            "owners" => Request::Owners(parse_id(words.next())?),
+            "options" => Request::Options(parse_id(words.next())?),
+            "standings" => Request::Standings(parse_id(words.next())?),
+            "quit" => Request::Quit,
+            _ => return Err(format!("Unknown request: {}", command)),
+        };
+        match words.next() {
+            Some(word) => Err(format!("Unexpected argument: {}", word)),
+            None => Ok(request),
+        }
+    }
+
+    pub fn game_id(&self) -> Option<u64> {
+        //! Game the request applies to; races are not games
+        match self {
+            Request::Dig(id, _, _)
+            | Request::Flag(id, _, _)
+            | Request::Chord(id, _, _)
+            | Request::Submit(id)
+            | Request::State(id)
+            | Request::Join(id, ..)
+            | Request::Cursor(id, _)
+            | Request::Players(id)
+            | Request::Owners(id)
+            | Request::Options(id) => Some(*id),
+            Request::New(_)
+            | Request::Race(_)
+            | Request::Enter(..)
+            | Request::Standings(_)
+            | Request::Quit => None,
+        }
+    }
+
+    pub fn is_move(&self) -> bool {
+        //! Whether the request changes the board
+        matches!(
+            self,
+            Request::Dig(..) | Request::Flag(..) | Request::Chord(..) | Request::Submit(_)
+        )
+    }
+}
+
+impl std::fmt::Display for Request {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let on_cell = |f: &mut std::fmt::Formatter, command, id, coords: &[usize], expected| {
+            write!(f, "{} {} {}", command, id, format_coords(coords))?;
+            match expected {
+                Some(state) => write!(f, " {}", cellstate_token(state)),
+                None => Ok(()),
+            }
+        };
+        match self {
+            Request::New(args) if args.is_empty() => write!(f, "new"),
+            Request::New(args) => write!(f, "new {}", args.join(" ")),
+            Request::Dig(id, coords, expected) => on_cell(f, "dig", id, coords, *expected),
+            Request::Flag(id, coords, expected) => on_cell(f, "flag", id, coords, *expected),
+            Request::Chord(id, coords, expected) => on_cell(f, "chord", id, coords, *expected),
+            Request::Submit(id) => write!(f, "submit {}", id),
+            Request::State(id) => write!(f, "state {}", id),
+            Request::Join(id, name, None) => write!(f, "join {} {}", id, name),
+            Request::Join(id, name, Some(token)) => write!(f, "join {} {} {}", id, name, token),
+            Request::Cursor(id, coords) => write!(f, "cursor {} {}", id, format_coords(coords)),
+            Request::Players(id) => write!(f, "players {}", id),
+            Request::Owners(id) => write!(f, "owners {}", id),
+            Request::Options(id) => write!(f, "options {}", id),
+            Request::Race(args) if args.is_empty() => write!(f, "race"),
+            Request::Race(args) => write!(f, "race {}", args.join(" ")),
+            Request::Enter(id, name, None) => write!(f, "enter {} {}", id, name),
+            Request::Enter(id, name, Some(token)) => {
+                write!(f, "enter {} {} {}", id, name, token)
+            }
+            Request::Standings(id) => write!(f, "standings {}", id),
+            Request::Quit => write!(f, "quit"),
+        }
+    }
+}
+
+fn cell_token(cell: Cell) -> String {
+    match cell {
+        Cell::Bomb => String::from("x"),
+        Cell::Mines(k) => format!("x{}", k),
+        Cell::AntiMine => String::from("a"),
+        Cell::Clean(count) => count.to_string(),
+    }
+}
+
+pub fn cellstate_token(state: CellState) -> String {
+    //! Word describing what the player sees of a cell
+    //!
+    //! `h` hidden, `f` flagged (`f3` for 3 flags), `m` marked, numbers for
+    //! clean cells, `x` bomb (`x2` for 2 bombs), `a` anti-mine, and `!`
+    //! before the cell for exploded mines
+    match state {
+        CellState::Hidden => String::from("h"),
+        CellState::Flagged => String::from("f"),
+        CellState::Flags(k) => format!("f{}", k),
+        CellState::Marked => String::from("m"),
+        CellState::Revealed(cell) => cell_token(cell),
+        CellState::Exploded(cell) => format!("!{}", cell_token(cell)),
+    }
+}
+
+pub fn parse_cellstate_token(token: &str) -> Result<CellState, String> {
+    let invalid = || format!("Invalid cell: {}", token);
+    let cell = |token: &str| match token {
+        "x" => Ok(Cell::Bomb),
+        "a" => Ok(Cell::AntiMine),
+        _ if token.starts_with('x') => token[1..].parse().map(Cell::Mines),
+        _ => token.parse().map(Cell::Clean),
+    };
+    match token {
+        "h" => Ok(CellState::Hidden),
+        "f" => Ok(CellState::Flagged),
+        "m" => Ok(CellState::Marked),
+        _ if token.starts_with('f') => token[1..]
+            .parse()
+            .map(CellState::Flags)
+            .map_err(|_| invalid()),
+        _ if token.starts_with('!') => cell(&token[1..])
+            .map(CellState::Exploded)
+            .map_err(|_| invalid()),
+        _ => cell(token).map(CellState::Revealed).map_err(|_| invalid()),
+    }
+}
+
+pub fn state_line(id: u64, client: &Client) -> String {
+    //! Visible state of a game: `ok state ID STATE BOMBS LIVES DIMS CELLS...`
+    //!
+    //! Dimensions are written as `9x9` (`4x9x9` in 3D), and cells follow in
+    //! row order, with `_` for holes. The mines of finished games are shown.
+    let state = state_words(
+        client.get_game_state(),
+        client.num_bombs(),
+        client.lives_left(),
+        &client.shown_state(),
+    );
+    format!("ok state {} {}", id, state)
+}
+
+pub fn state_words(
+    game_state: GameState,
+    num_bombs: usize,
+    lives_left: usize,
+    state: &Grid<CellState>,
+) -> String {
+    //! `STATE BOMBS LIVES DIMS CELLS...`, as in `state_line`
+    let dims: Vec<String> = state.shape().iter().map(|d| d.to_string()).collect();
+    let cells: Vec<String> = state
+        .iter()
+        .map(|(coords, &cell_state)| {
+            if state.exists(&coords) {
+                cellstate_token(cell_state)
+            } else {
+                String::from("_")
+            }
+        })
+        .collect();
+    format!(
+        "{:?} {} {} {} {}",
+        game_state,
+        num_bombs,
+        lives_left,
+        dims.join("x"),
+        cells.join(" ")
+    )
+}
+
+pub fn players_line(id: u64, players: &[Player]) -> String {
+    //! Players of a game: `ok players ID NAME@ROW,COL...`, with `-` for
+    //! players who did not show their cursor
+    let players: Vec<String> = players
+        .iter()
+        .map(|player| match &player.cursor {
+            Some(coords) => format!("{}@{}", player.name, format_coords(coords)),
+            None => format!("{}@-", player.name),
+        })
+        .collect();
+    format!("ok players {} {}", id, players.join(" "))
+        .trim_end()
+        .to_string()
+}
+
+pub fn parse_players_line(line: &str) -> Result<Vec<Player>, String> {
+    let invalid = || format!("Invalid players: {}", line);
+    let mut words = line.split_whitespace();
+    if words.next() != Some("ok") || words.next() != Some("players") {
+        return Err(invalid());
+    }
+    parse_id(words.next())?;
+    words
+        .map(|word| {
+            let mut parts = word.splitn(2, '@');
+            let name = parts.next().ok_or_else(invalid)?.to_string();
+            let cursor = match parts.next().ok_or_else(invalid)? {
+                "-" => None,
+                coords => Some(parse_coords(Some(coords))?),
+            };
+            Ok(Player { name, cursor })
+        })
+        .collect()
+}
+
+pub fn owners_line(id: u64, owners: &[Option<usize>]) -> String {
+    //! Who last changed each cell: `ok owners ID OWNERS...`, with player
+    //! numbers in row order and `-` for nobody
+    let owners: Vec<String> = owners
+        .iter()
+        .map(|owner| match owner {
+            Some(player) => player.to_string(),
+            None => String::from("-"),
+        })
+        .collect();
+    format!("ok owners {} {}", id, owners.join(" "))
+}
+
+pub fn parse_owners_line(line: &str) -> Result<Vec<Option<usize>>, String> {
+    let invalid = || format!("Invalid owners: {}", line);
+    let mut words = line.split_whitespace();
+    if words.next() != Some("ok") || words.next() != Some("owners") {
+        return Err(invalid());
+    }
+    parse_id(words.next())?;
+    words
+        .map(|word| match word {
+            "-" => Ok(None),
+            _ => word.parse().map(Some).map_err(|_| invalid()),
+        })
+        .collect()
+}
+
+pub fn standings_line(standings: &Standings) -> String {
+    //! Progress of a race: `ok standings ID STAGE NAME:PERCENT:STRIKES:STATE...`
+    //!
+    //! The stage is `waiting`, `starting:MS` with the milliseconds left
+    //! before the start, `racing`, `won:RACER` with the number of the
+    //! winner, or `draw`. Racers who did not enter yet are named `-`.
+    let stage = match standings.state {
+        RaceState::Waiting => String::from("waiting"),
+        RaceState::Starting(left) => format!("starting:{}", left.as_millis()),
+        RaceState::Racing => String::from("racing"),
+        RaceState::Won(racer) => format!("won:{}", racer),
+        RaceState::Draw => String::from("draw"),
+    };
+    let racers: Vec<String> = standings
+        .racers
+        .iter()
+        .map(|r| format!("{}:{}:{}:{:?}", r.name, r.progress, r.strikes, r.game_state))
+        .collect();
+    format!(
+        "ok standings {} {} {}",
+        standings.id,
+        stage,
+        racers.join(" ")
+    )
+    .trim_end()
+    .to_string()
+}
+
+fn parse_game_state(word: Option<&str>) -> Option<GameState> {
+    match word? {
+        "Running" => Some(GameState::Running),
+        "Won" => Some(GameState::Won),
+        "Lost" => Some(GameState::Lost),
+        _ => None,
+    }
+}
+
+impl Standings {
+    pub fn parse(line: &str) -> Result<Standings, String> {
+        //! Reads a line written by `standings_line`
+        let invalid = || format!("Invalid standings: {}", line);
+        let mut words = line.split_whitespace();
+        if words.next() != Some("ok") || words.next() != Some("standings") {
+            return Err(invalid());
+        }
+        let id = parse_id(words.next())?;
+        let mut stage = words.next().ok_or_else(invalid)?.split(':');
+        let number = |word: Option<&str>| -> Result<u64, String> {
+            word.and_then(|w| w.parse().ok()).ok_or_else(invalid)
+        };
+        let state = match stage.next() {
+            Some("waiting") => RaceState::Waiting,
+            Some("starting") => RaceState::Starting(Duration::from_millis(number(stage.next())?)),
+            Some("racing") => RaceState::Racing,
+            Some("won") => RaceState::Won(number(stage.next())? as usize),
+            Some("draw") => RaceState::Draw,
+            _ => return Err(invalid()),
+        };
+        let racers = words
+            .map(|word| {
+                let mut parts = word.split(':');
+                Ok(Progress {
+                    name: parts.next().ok_or_else(invalid)?.to_string(),
+                    progress: number(parts.next())? as usize,
+                    strikes: number(parts.next())? as usize,
+                    game_state: parse_game_state(parts.next()).ok_or_else(invalid)?,
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Ok(Standings { id, state, racers })
+    }
+}
+
+/// Game described by a `state` line, as seen by a remote player
+#[derive(Debug, Clone, PartialEq)]
+pub struct StateLine {
+    pub id: u64,
+    pub game_state: GameState,
+    pub num_bombs: usize,
+    pub lives_left: usize,
+    pub dims: Vec<usize>,
+    /// Cells in row order, `None` for holes
+    pub cells: Vec<Option<CellState>>,
+}
+
+impl StateLine {
+    pub fn parse(line: &str) -> Result<StateLine, String> {
+        //! Reads a line written by `state_line`
+        let invalid = || format!("Invalid state: {}", line);
+        let mut words = line.split_whitespace();
+        if words.next() != Some("ok") || words.next() != Some("state") {
+            return Err(invalid());
+        }
+        let id = parse_id(words.next())?;
+        let game_state = parse_game_state(words.next()).ok_or_else(invalid)?;
+        let mut number = || -> Result<usize, String> {
+            words
+                .next()
+                .and_then(|w| w.parse().ok())
+                .ok_or_else(invalid)
+        };
+        let num_bombs = number()?;
+        let lives_left = number()?;
+        let dims = words
+            .next()
+            .ok_or_else(invalid)?
+            .split('x')
+            .map(|d| d.parse().map_err(|_| invalid()))
+            .collect::<Result<Vec<usize>, String>>()?;
+        let cells = words
+            .map(|word| match word {
+                "_" => Ok(None),
+                _ => parse_cellstate_token(word).map(Some),
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        if cells.len() != dims.iter().product::<usize>() {
+            return Err(invalid());
+        }
+        Ok(StateLine {
+            id,
+            game_state,
+            num_bombs,
+            lives_left,
+            dims,
+            cells,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::minefield::field::test::generate_test_minefield;
+
+    #[test]
+    fn requests() {
+        assert_eq!(
+            Request::parse("new --height 9"),
+            Ok(Request::New(vec![
+                String::from("--height"),
+                String::from("9")
+            ]))
+        );
+        assert_eq!(
+            Request::parse("dig 3 1,2"),
+            Ok(Request::Dig(3, vec![1, 2], None))
+        );
+        assert_eq!(
+            Request::parse("chord 3 0,1,2"),
+            Ok(Request::Chord(3, vec![0, 1, 2], None))
+        );
+        assert_eq!(Request::parse("state 12"), Ok(Request::State(12)));
+        assert_eq!(
+            Request::parse("flag 3 1,2 f2"),
+            Ok(Request::Flag(3, vec![1, 2], Some(CellState::Flags(2))))
+        );
+        assert_eq!(
+            Request::parse("join 3 alice"),
+            Ok(Request::Join(3, String::from("alice"), None))
+        );
+        assert_eq!(
+            Request::parse("join 3 alice 0123abcd"),
+            Ok(Request::Join(
+                3,
+                String::from("alice"),
+                Some(String::from("0123abcd"))
+            ))
+        );
+        assert!(Request::parse("join 3 al:ice").is_err());
+        assert!(Request::parse("dig 3 1,2 y").is_err());
+        assert!(Request::parse("dig 3").is_err());
+        assert!(Request::parse("dig x 1,2").is_err());
+        assert!(Request::parse("flag 1 1;2").is_err());
+        assert!(Request::parse("submit 1 2").is_err());
+        assert!(Request::parse("explode").is_err());
+        for line in &[
+            "new",
+            "new --hex",
+            "flag 1 2,3",
+            "dig 1 2,3 h",
+            "submit 4",
+            "join 4 bob",
+            "join 4 bob 0123abcd",
+            "cursor 4 0,1",
+            "owners 4",
+            "race --height 9",
+            "enter 2 carol",
+            "enter 2 carol 0123abcd",
+            "standings 2",
+            "quit",
+        ] {
+            assert_eq!(Request::parse(line).unwrap().to_string(), *line);
+        }
+    }
+
+    #[test]
+    fn states() {
+        let (field, _) = generate_test_minefield();
+        let mut client = Client::from_minefield(field).with_lives(2);
+        client.flag(&[0, 0]);
+        client.query_update(&[0, 1]);
+        client.query_update(&[0, 3]);
+        assert_eq!(
+            state_line(7, &client),
+            "ok state 7 Running 5 1 3x5 f 2 h !x h h h h h h h h h h h"
+        );
+        for state in &[
+            CellState::Flags(3),
+            CellState::Revealed(Cell::Clean(-2)),
+            CellState::Revealed(Cell::Mines(2)),
+            CellState::Exploded(Cell::AntiMine),
+            CellState::Marked,
+        ] {
+            assert_eq!(parse_cellstate_token(&cellstate_token(*state)), Ok(*state));
+        }
+        let parsed = StateLine::parse(&state_line(7, &client)).unwrap();
+        assert_eq!(parsed.game_state, GameState::Running);
+        assert_eq!((parsed.id, parsed.num_bombs, parsed.lives_left), (7, 5, 1));
+        assert_eq!(parsed.dims, vec![3, 5]);
+        assert_eq!(parsed.cells[3], Some(CellState::Exploded(Cell::Bomb)));
+        assert_eq!(
+            StateLine::parse("ok state 2 Won 0 1 1x2 _ 0")
+                .unwrap()
+                .cells,
+            vec![None, Some(CellState::Revealed(Cell::Clean(0)))]
+        );
+        assert!(StateLine::parse("ok state 2 Won 0 1 1x2 0").is_err());
+        assert!(StateLine::parse("ok game 2").is_err());
+        assert!(parse_cellstate_token("y").is_err());
+        let players = vec![
+            Player {
+                name: String::from("alice"),
+                cursor: Some(vec![1, 2]),
+            },
+            Player {
+                name: String::from("bob"),
+                cursor: None,
+            },
+        ];
+        let line = players_line(4, &players);
+        assert_eq!(line, "ok players 4 alice@1,2 bob@-");
+        assert_eq!(parse_players_line(&line), Ok(players));
+        assert_eq!(parse_players_line("ok players 4"), Ok(vec![]));
+        let owners = vec![Some(0), None, Some(12)];
+        assert_eq!(owners_line(4, &owners), "ok owners 4 0 - 12");
+        assert_eq!(parse_owners_line(&owners_line(4, &owners)), Ok(owners));
+        assert!(parse_owners_line("ok owners 4 x").is_err());
+        assert!(parse_cellstate_token("!h").is_err());
+        let standings = Standings {
+            id: 3,
+            state: RaceState::Starting(Duration::from_millis(2500)),
+            racers: vec![
+                Progress {
+                    name: String::from("alice"),
+                    progress: 42,
+                    strikes: 1,
+                    game_state: GameState::Running,
+                },
+                Progress {
+                    name: String::from("-"),
+                    progress: 0,
+                    strikes: 0,
+                    game_state: GameState::Running,
+                },
+            ],
+        };
+        let line = standings_line(&standings);
+        assert_eq!(
+            line,
+            "ok standings 3 starting:2500 alice:42:1:Running -:0:0:Running"
+        );
+        assert_eq!(Standings::parse(&line), Ok(standings));
+        assert_eq!(
+            Standings::parse("ok standings 3 won:1 a:100:0:Won b:50:2:Lost")
+                .unwrap()
+                .winner(),
+            Some("b")
+        );
+        assert!(Standings::parse("ok standings 3 won a:1:0:Won").is_err());
+        assert!(Standings::parse("ok standings 3 racing a:1:0").is_err());
+    }
+}
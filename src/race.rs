@@ -0,0 +1,348 @@
+use crate::minefield::client::GameState;
+use crate::registry::{self, Registry, Start};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Players of a race
+pub const RACERS: usize = 2;
+/// Wait between the arrival of the last racer and the start of the race
+pub const COUNTDOWN: Duration = Duration::from_secs(3);
+
+/// Stage of a race
+#[derive(Debug, Clone, PartialEq)]
+pub enum RaceState {
+    /// Waiting for racers to enter
+    Waiting,
+    /// Everyone is in; the race starts after the countdown
+    Starting(Duration),
+    Racing,
+    /// Won by a racer, by number
+    Won(usize),
+    /// Every racer lost
+    Draw,
+}
+
+/// Progress of a racer, as shown to the other racers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Progress {
+    pub name: String,
+    /// Percentage of the clean cells dug
+    pub progress: usize,
+    pub strikes: usize,
+    pub game_state: GameState,
+}
+
+/// Race as seen by the racers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Standings {
+    pub id: u64,
+    pub state: RaceState,
+    pub racers: Vec<Progress>,
+}
+
+impl Standings {
+    pub fn started(&self) -> bool {
+        !matches!(self.state, RaceState::Waiting | RaceState::Starting(_))
+    }
+
+    pub fn winner(&self) -> Option<&str> {
+        match self.state {
+            RaceState::Won(racer) => self.racers.get(racer).map(|r| r.name.as_str()),
+            _ => None,
+        }
+    }
+}
+
+/// Race between players on boards drawn from the same seed
+///
+/// The boards are started along with the race, one per racer, and handed
+/// out as racers enter.
+struct Race {
+    /// Game of every racer, and who plays it
+    games: Vec<(u64, Option<String>)>,
+    start: Option<Instant>,
+    /// Winner, or draw, once decided
+    result: Option<RaceState>,
+    created: Instant,
+}
+
+/// Races hosted by a server
+#[derive(Default)]
+pub struct Races {
+    races: HashMap<u64, Race>,
+    next_id: u64,
+}
+
+impl Races {
+    pub(crate) fn expire(&mut self, alive: impl Fn(u64) -> bool, oldest: Instant) {
+        //! Drops the races whose games are gone, and the races nobody
+        //! entered for too long
+        self.races.retain(|_, race| {
+            let entered = race.games.iter().any(|(_, name)| name.is_some());
+            race.games.iter().any(|&(game, _)| alive(game)) && (entered || race.created >= oldest)
+        });
+    }
+}
+
+fn public_args(args: &[String]) -> Vec<String> {
+    //! Options of a race shown to the racers, without those that tell the
+    //! board away: the seed and the day
+    let mut public = vec![];
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--seed" | "--date" => {
+                args.next();
+            }
+            "--daily" => (),
+            _ => public.push(arg.clone()),
+        }
+    }
+    public
+}
+
+impl Registry {
+    pub fn create_race(&self, args: &[String]) -> Result<u64, String> {
+        //! Starts a race with command line options, e.g. `--height 9`
+        //!
+        //! Boards without a seed get a random one, so that every racer gets
+        //! the same board. The options of the games leave the seed out, so
+        //! that racers cannot draw the board.
+        let options = registry::server_options(args)?;
+        let mut seeded = args.to_vec();
+        if options.seed.is_none() && options.daily.is_none() {
+            seeded.push(String::from("--seed"));
+            seeded.push(rand::random::<u64>().to_string());
+        }
+        let public = public_args(args);
+        let mut races = self.races.lock().unwrap();
+        races.next_id += 1;
+        let id = races.next_id;
+        let mut games = vec![];
+        for _ in 0..RACERS {
+            let game = self.create(&seeded)?;
+            self.update(game, |session| {
+                session.start = Start::Later;
+                session.race = Some(id);
+                session.args = public.clone();
+            })?;
+            games.push((game, None));
+        }
+        let race = Race {
+            games,
+            start: None,
+            result: None,
+            created: Instant::now(),
+        };
+        races.races.insert(id, race);
+        Ok(id)
+    }
+
+    pub fn enter_race(
+        &self,
+        id: u64,
+        name: &str,
+        token: Option<&str>,
+        now: Instant,
+    ) -> Result<(u64, String), String> {
+        //! Enters a racer, and returns the game they play with their token
+        //!
+        //! Entering again with the name and the token of a racer gives back
+        //! the same game. The countdown starts once every racer is in: moves
+        //! are refused until then.
+        let mut races = self.races.lock().unwrap();
+        let race = races
+            .races
+            .get_mut(&id)
+            .ok_or_else(|| format!("Unknown race: {}", id))?;
+        if let Some(&(game, _)) = race
+            .games
+            .iter()
+            .find(|(_, racer)| racer.as_deref() == Some(name))
+        {
+            // racers are the only player of their game
+            let secret = self.update(game, |session| session.token(0).to_string())?;
+            if token != Some(&secret[..]) {
+                return Err(format!("{} already races in race {}", name, id));
+            }
+            return Ok((game, secret));
+        }
+        let seat = race
+            .games
+            .iter()
+            .position(|(_, racer)| racer.is_none())
+            .ok_or_else(|| format!("Race {} is full", id))?;
+        let game = race.games[seat].0;
+        let (_, token) = self.update(game, |session| session.add_player(name))?;
+        race.games[seat].1 = Some(name.to_string());
+        if race.games.iter().all(|(_, racer)| racer.is_some()) {
+            let start = now + COUNTDOWN;
+            race.start = Some(start);
+            for &(game, _) in &race.games {
+                self.update(game, |session| session.start = Start::At(start))?;
+            }
+        }
+        Ok((game, token))
+    }
+
+    pub fn standings(&self, id: u64, now: Instant) -> Result<Standings, String> {
+        //! Progress of every racer, deciding the winner on the way
+        //!
+        //! The first racer to clear their board wins, as does the last racer
+        //! standing. The games of the other racers are then lost.
+        let mut races = self.races.lock().unwrap();
+        let race = races
+            .races
+            .get_mut(&id)
+            .ok_or_else(|| format!("Unknown race: {}", id))?;
+        let mut racers = vec![];
+        let mut finish_times = vec![];
+        for (game, name) in &race.games {
+            let (progress, finish_time) = self.update(*game, |session| {
+                let client = &session.client;
+                let progress = Progress {
+                    name: name.clone().unwrap_or_else(|| String::from("-")),
+                    progress: client.progress(),
+                    strikes: client.strikes(),
+                    game_state: client.get_game_state(),
+                };
+                (progress, client.last_move_time())
+            })?;
+            racers.push(progress);
+            finish_times.push(finish_time);
+        }
+        if race.result.is_none() {
+            let winner = (0..racers.len())
+                .filter(|&racer| racers[racer].game_state == GameState::Won)
+                .min_by_key(|&racer| finish_times[racer]);
+            let standing: Vec<usize> = (0..racers.len())
+                .filter(|&racer| racers[racer].game_state != GameState::Lost)
+                .collect();
+            race.result = match (winner, standing.as_slice()) {
+                (Some(racer), _) | (None, &[racer]) => Some(RaceState::Won(racer)),
+                (None, []) => Some(RaceState::Draw),
+                _ => None,
+            };
+            if let Some(RaceState::Won(winner)) = race.result {
+                for (racer, &(game, _)) in race.games.iter().enumerate() {
+                    if racer != winner {
+                        self.update(game, |session| session.client.time_out())?;
+                        racers[racer].game_state = GameState::Lost;
+                    }
+                }
+            }
+        }
+        let state = match (&race.result, race.start) {
+            (Some(result), _) => result.clone(),
+            (None, None) => RaceState::Waiting,
+            (None, Some(start)) if start > now => RaceState::Starting(start - now),
+            (None, Some(_)) => RaceState::Racing,
+        };
+        Ok(Standings { id, state, racers })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::protocol::Request;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn races() {
+        let registry = Registry::new(None);
+        let id = registry
+            .create_race(&args("--height 4 --width 5 --bombs 6"))
+            .unwrap();
+        assert!(registry.create_race(&args("--mode countdown")).is_err());
+        // the countdown is over by now
+        let now = Instant::now() - COUNTDOWN;
+        let (alice, token) = registry.enter_race(id, "alice", None, now).unwrap();
+        // only the racer can enter again, with their token
+        assert_eq!(
+            registry.enter_race(id, "alice", Some(&token), now),
+            Ok((alice, token.clone()))
+        );
+        assert_eq!(
+            registry.enter_race(id, "alice", None, now),
+            Err(String::from("alice already races in race 1"))
+        );
+        assert!(registry.join(alice, "mallory", None).is_err());
+        let standings = registry.standings(id, now).unwrap();
+        assert_eq!(standings.state, RaceState::Waiting);
+        assert_eq!(standings.racers[1].name, "-");
+        // moves wait for the start
+        let submit = |game| {
+            registry.play_as(&Request::Submit(game), Some(0), |_, s| {
+                s.client.get_state().data().to_vec()
+            })
+        };
+        assert_eq!(
+            submit(alice),
+            Err(format!("Game {} has not started", alice))
+        );
+
+        let (bob, _) = registry.enter_race(id, "bob", None, now).unwrap();
+        assert_ne!(alice, bob);
+        assert_eq!(
+            registry.enter_race(id, "carol", None, now),
+            Err(String::from("Race 1 is full"))
+        );
+        assert_eq!(
+            registry.standings(id, now).unwrap().state,
+            RaceState::Starting(COUNTDOWN)
+        );
+        // both boards were drawn from the same seed
+        let board = |game| {
+            registry
+                .play(&Request::State(game), |_, s| s.client.minefield.to_string())
+                .unwrap()
+        };
+        assert_eq!(board(alice), board(bob));
+        submit(alice).unwrap();
+        submit(bob).unwrap();
+        let standings = registry.standings(id, Instant::now()).unwrap();
+        assert_eq!(standings.state, RaceState::Draw);
+        assert!(standings.started());
+        assert_eq!(standings.winner(), None);
+    }
+
+    #[test]
+    fn winner() {
+        let registry = Registry::new(None);
+        let id = registry
+            .create_race(&args("--height 2 --width 3 --bombs 0 --seed 4"))
+            .unwrap();
+        let long_ago = Instant::now() - COUNTDOWN;
+        let (alice, _) = registry.enter_race(id, "alice", None, long_ago).unwrap();
+        let (bob, _) = registry.enter_race(id, "bob", None, long_ago).unwrap();
+        // only the racer plays their game
+        assert_eq!(
+            registry.play(&Request::Dig(bob, vec![0, 0], None), |_, _| ()),
+            Err(format!("Enter race 1 to play game {}", bob))
+        );
+        registry
+            .play_as(&Request::Dig(bob, vec![0, 0], None), Some(0), |_, _| ())
+            .unwrap();
+        let standings = registry.standings(id, Instant::now()).unwrap();
+        assert_eq!(standings.state, RaceState::Racing);
+        assert_eq!(standings.racers[1].progress, 100);
+        registry
+            .play_as(&Request::Submit(bob), Some(0), |_, _| ())
+            .unwrap();
+        let standings = registry.standings(id, Instant::now()).unwrap();
+        assert_eq!(standings.winner(), Some("bob"));
+        // the race is over for alice
+        assert_eq!(standings.racers[0].game_state, GameState::Lost);
+        assert_eq!(
+            registry.play_as(&Request::Dig(alice, vec![0, 0], None), Some(0), |_, _| ()),
+            Err(format!("Game {} is over", alice))
+        );
+        // the seed is left out of the options of the games
+        let options = registry.play(&Request::Options(alice), |_, s| s.args.clone());
+        assert_eq!(options, Ok(args("--height 2 --width 3 --bombs 0")));
+    }
+}
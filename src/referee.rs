@@ -0,0 +1,300 @@
+use crate::ascii_client;
+use crate::bot::{self, Action};
+use crate::minefield::client::{CellState, Client, GameState};
+use crate::minefield::grid::Grid;
+use crate::options::GameOptions;
+use crate::protocol;
+use crate::spectate;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+/// Time left to bots to exit by themselves once their stdin is closed
+const GRACE: Duration = Duration::from_millis(100);
+
+/// End of a refereed game
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub game_state: GameState,
+    pub moves: usize,
+    /// Time the bot spent choosing its moves
+    pub time: Duration,
+    /// Why the bot forfeited: time limit, invalid command, crash...
+    pub fault: Option<String>,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> Result<(), std::fmt::Error> {
+        let plural = if self.moves == 1 { "" } else { "s" };
+        write!(f, "{:?} in {} move{}", self.game_state, self.moves, plural)?;
+        if let Some(fault) = &self.fault {
+            write!(f, " ({})", fault)?;
+        }
+        Ok(())
+    }
+}
+
+pub fn check_board(state: &Grid<CellState>) -> Result<(), String> {
+    //! Boards whose cells can all be named with ascii_client commands
+    let shape = state.shape();
+    let (rows, cols) = state.layer_shape();
+    if shape.len() > 3 || rows > 10 || cols > 26 || state.num_layers() > 10 {
+        return Err(String::from(
+            "Refereed boards have at most 10 rows, 26 columns and 10 layers",
+        ));
+    }
+    Ok(())
+}
+
+/// Bot running as a subprocess, playing over its stdin and stdout
+///
+/// Its stdin and stdout have a thread each, so that a bot that stops reading
+/// or writing only runs out its time.
+struct Player {
+    child: Child,
+    /// Lines for the stdin of the bot, which closes at the end of the game
+    stdin: Option<Sender<String>>,
+    lines: Receiver<String>,
+}
+
+fn write_lines(mut stdin: ChildStdin, lines: Receiver<String>) {
+    for line in lines {
+        if stdin.write_all(format!("{}\n", line).as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+impl Player {
+    fn spawn(command: &[String]) -> Result<Player, String> {
+        let (program, args) = command.split_first().ok_or("Missing bot command")?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("Cannot start {}: {}", program, e))?;
+        let (stdin, stdin_rx) = mpsc::channel();
+        let child_stdin = child.stdin.take().unwrap();
+        std::thread::spawn(move || write_lines(child_stdin, stdin_rx));
+        let stdout = child.stdout.take().unwrap();
+        let (lines_tx, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                if lines_tx.send(line).is_err() {
+                    return;
+                }
+            }
+        });
+        Ok(Player {
+            child,
+            stdin: Some(stdin),
+            lines,
+        })
+    }
+
+    fn send(&mut self, line: &str) -> Result<(), String> {
+        //! Queues a line for the bot, without waiting for the bot to read it
+        let stopped = || String::from("The bot stopped");
+        self.stdin
+            .as_ref()
+            .ok_or_else(stopped)?
+            .send(line.to_string())
+            .map_err(|_| stopped())
+    }
+
+    fn receive(&mut self, timeout: Duration) -> Result<String, String> {
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(line),
+            Err(RecvTimeoutError::Timeout) => {
+                Err(format!("No move within {}ms", timeout.as_millis()))
+            }
+            Err(RecvTimeoutError::Disconnected) => Err(String::from("The bot stopped")),
+        }
+    }
+}
+
+impl Drop for Player {
+    fn drop(&mut self) {
+        //! Closes the stdin of the bot, and kills it if it does not exit
+        self.stdin = None;
+        let deadline = Instant::now() + GRACE;
+        while let Ok(None) = self.child.try_wait() {
+            if Instant::now() >= deadline {
+                let _ = self.child.kill();
+                let _ = self.child.wait();
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+}
+
+/// Referee of games between the board and a bot written in any language
+///
+/// The bot is a program that reads the board on its stdin and writes its
+/// moves on its stdout, one line at a time:
+/// - the referee starts with `board DIMS TOPOLOGY LIVES [wrap]`, e.g.
+///   `board 9x9 square 1`, as in the stream of spectators;
+/// - before every move, it sends the board as seen by the player:
+///   `state STATE BOMBS LIVES DIMS CELLS...`, with the cells in the words of
+///   the game server (`h` hidden, `f` flagged, `0`-`8`...);
+/// - the bot answers with a command of ascii_client: `da3` (or `a3`) digs
+///   column a of row 3, `fa3` flags it, `2a3` names a cell of layer 2 on 3D
+///   boards, `s` submits the flags and `q` resigns;
+/// - at the end, the referee sends the last `state` and `result Won` or
+///   `result Lost`, then closes the stdin of the bot.
+///
+/// Bots lose by forfeit when they take longer than the time limit to move,
+/// write an invalid command, or stop.
+pub struct Referee {
+    command: Vec<String>,
+    move_time: Duration,
+}
+
+impl Referee {
+    pub fn new(command: Vec<String>) -> Referee {
+        Referee {
+            command,
+            move_time: Duration::from_secs(1),
+        }
+    }
+
+    pub fn with_move_time(mut self, move_time: Duration) -> Referee {
+        self.move_time = move_time;
+        self
+    }
+
+    pub fn name(&self) -> String {
+        self.command.join(" ")
+    }
+
+    pub fn referee(&self, options: &GameOptions) -> Result<Outcome, String> {
+        //! Plays a game with the options, in a new process of the bot
+        let mut client = options.new_client()?;
+        check_board(client.get_state())?;
+        let mut player = Player::spawn(&self.command)?;
+        let mut outcome = Outcome {
+            game_state: GameState::Running,
+            moves: 0,
+            time: Duration::from_secs(0),
+            fault: None,
+        };
+        let max_moves = 2 * client.get_state().len() * options.max_per_cell as usize + 1;
+        let played = self.play(&mut player, &mut client, &mut outcome, max_moves);
+        if let Err(fault) = played {
+            client.time_out();
+            outcome.fault = Some(fault);
+        }
+        outcome.game_state = client.get_game_state();
+        // the bot may be gone already
+        let _ = player
+            .send(&Self::state_line(&client))
+            .and_then(|_| player.send(&format!("result {:?}", outcome.game_state)));
+        Ok(outcome)
+    }
+
+    fn state_line(client: &Client) -> String {
+        let words = protocol::state_words(
+            client.get_game_state(),
+            client.num_bombs(),
+            client.lives_left(),
+            client.get_state(),
+        );
+        format!("state {}", words)
+    }
+
+    fn play(
+        &self,
+        player: &mut Player,
+        client: &mut Client,
+        outcome: &mut Outcome,
+        max_moves: usize,
+    ) -> Result<(), String> {
+        //! Plays the moves of the bot until the game ends, or the bot
+        //! forfeits
+        player.send(&spectate::board_line(client.get_state(), client.lives()))?;
+        while client.get_game_state() == GameState::Running {
+            if outcome.moves == max_moves {
+                return Err(String::from("Too many moves"));
+            }
+            player.send(&Self::state_line(client))?;
+            let start = Instant::now();
+            let line = player.receive(self.move_time)?;
+            outcome.time += start.elapsed();
+            let action = ascii_client::parse_action(&line)?
+                .ok_or_else(|| String::from("The bot resigned"))?;
+            match &action {
+                Action::Dig(coords) | Action::Flag(coords) | Action::Chord(coords)
+                    if !client.get_state().exists(coords) =>
+                {
+                    return Err(format!("No cell at {}", line.trim()));
+                }
+                _ => (),
+            }
+            bot::play(client, &action);
+            outcome.moves += 1;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn shell(script: &str) -> Referee {
+        Referee::new(vec![String::from("sh"), String::from("-c"), script.into()])
+            .with_move_time(Duration::from_millis(500))
+    }
+
+    #[test]
+    fn referee() {
+        let options = GameOptions::parse(
+            "--height 2 --width 3 --bombs 0 --seed 1"
+                .split_whitespace()
+                .map(String::from),
+        )
+        .unwrap();
+        // digs everything at once, then submits, if the board is as expected
+        let winner = shell(
+            "read board; [ \"$board\" = 'board 2x3 square 1' ] || exit; \
+             read state; echo da0; read state; echo s; cat > /dev/null",
+        );
+        let outcome = winner.referee(&options).unwrap();
+        assert_eq!(outcome.game_state, GameState::Won);
+        assert_eq!(outcome.moves, 2);
+        assert_eq!(outcome.to_string(), "Won in 2 moves");
+
+        let outcome = shell("read board; read state; echo dig").referee(&options);
+        assert_eq!(
+            outcome.unwrap().fault,
+            Some(String::from("Invalid command: dig"))
+        );
+        let outcome = shell("read board; read state; echo fc4").referee(&options);
+        assert_eq!(
+            outcome.unwrap().to_string(),
+            "Lost in 0 moves (No cell at fc4)"
+        );
+        let outcome = shell("sleep 2").referee(&options).unwrap();
+        assert_eq!(outcome.game_state, GameState::Lost);
+        assert_eq!(outcome.fault, Some(String::from("No move within 500ms")));
+        let outcome = shell("read board; echo q").referee(&options).unwrap();
+        assert_eq!(outcome.fault, Some(String::from("The bot resigned")));
+        let outcome = shell("exit 1").referee(&options).unwrap();
+        assert_eq!(outcome.fault, Some(String::from("The bot stopped")));
+        // a bot that never reads does not hold up the referee once its stdin
+        // is full
+        let full = GameOptions::parse(
+            "--height 10 --width 26 --bombs 10 --seed 1"
+                .split_whitespace()
+                .map(String::from),
+        );
+        let outcome = shell("yes fa0").referee(&full.unwrap()).unwrap();
+        assert_eq!(outcome.fault, Some(String::from("Too many moves")));
+
+        let big = GameOptions::parse("--height 11".split_whitespace().map(String::from));
+        assert!(winner.referee(&big.unwrap()).is_err());
+    }
+}
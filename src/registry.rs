@@ -0,0 +1,466 @@
+use crate::minefield::client::{CellState, Client, GameState};
+use crate::minefield::mode::Mode;
+use crate::options::GameOptions;
+use crate::protocol::{self, Request};
+use crate::race::Races;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Options of server games: the options of the clients, but those reading
+/// files on the server
+const SERVER_OPTIONS: &[&str] = &[
+    "--height",
+    "--width",
+    "--bombs",
+    "--layers",
+    "--stack",
+    "--anti-mines",
+    "--liar",
+    "--lives",
+    "--seed",
+    "--daily",
+    "--date",
+    "--shape",
+    "--hex",
+    "--wrap",
+    "--neighbours",
+];
+/// Largest boards of server games
+const MAX_SIDE: usize = 100;
+const MAX_LAYERS: usize = 10;
+const MAX_BOMBS: usize = 10_000;
+
+pub(crate) fn server_options(args: &[String]) -> Result<GameOptions, String> {
+    //! Options of a server game, refusing the options and boards it does not
+    //! host
+    if let Some(arg) = args
+        .iter()
+        .find(|arg| arg.starts_with("--") && !SERVER_OPTIONS.contains(&&arg[..]))
+    {
+        return Err(format!("Option not available on the server: {}", arg));
+    }
+    let options = GameOptions::parse(args.iter().cloned())
+        .map_err(|e| e.lines().next().unwrap_or_default().to_string())?;
+    if options.mode != Mode::Classic {
+        return Err(String::from("Game modes are not available on the server"));
+    }
+    if options.height > MAX_SIDE
+        || options.width > MAX_SIDE
+        || options.layers > MAX_LAYERS
+        || options.num_bombs > MAX_BOMBS
+    {
+        return Err(format!(
+            "Boards have at most {} rows, {} columns, {} layers and {} bombs",
+            MAX_SIDE, MAX_SIDE, MAX_LAYERS, MAX_BOMBS
+        ));
+    }
+    Ok(options)
+}
+
+/// Player of a game shared by several people
+#[derive(Debug, Clone, PartialEq)]
+pub struct Player {
+    pub name: String,
+    /// Cell the player is looking at
+    pub cursor: Option<Vec<usize>>,
+}
+
+/// When a game accepts moves
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Start {
+    Now,
+    At(Instant),
+    /// Once scheduled, e.g. when every racer is in
+    Later,
+}
+
+/// Game hosted by a server, with its players
+pub struct Session {
+    pub client: Client,
+    /// Options the game was started with
+    pub args: Vec<String>,
+    pub players: Vec<Player>,
+    /// Secret of every player, to play as them again
+    tokens: Vec<String>,
+    /// Player who last changed each cell, by index in the grid; `None` for
+    /// cells untouched or changed by anonymous connections
+    pub owners: Vec<Option<usize>>,
+    pub start: Start,
+    /// Race the game is part of: only its racer plays it
+    pub race: Option<u64>,
+    last_access: Instant,
+}
+
+impl Session {
+    fn check_expected(&self, coords: &[usize], expected: Option<CellState>) -> Result<(), String> {
+        //! Refuses moves made on an outdated view of the cell
+        let state = self.client.get_state();
+        match expected {
+            Some(expected) if state.get(coords) != Some(expected) => {
+                let owner = state.index(coords).and_then(|index| self.owners[index]);
+                Err(match owner {
+                    Some(player) => format!(
+                        "Conflict: {} was changed by {}",
+                        protocol::format_coords(coords),
+                        self.players[player].name
+                    ),
+                    None => format!("Conflict: {} has changed", protocol::format_coords(coords)),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn add_player(&mut self, name: &str) -> (usize, String) {
+        //! Adds a player, and returns their number with their secret token
+        let token = format!("{:016x}", rand::random::<u64>());
+        self.players.push(Player {
+            name: name.to_string(),
+            cursor: None,
+        });
+        self.tokens.push(token.clone());
+        (self.players.len() - 1, token)
+    }
+
+    pub(crate) fn token(&self, player: usize) -> &str {
+        &self.tokens[player]
+    }
+
+    fn check_started(&self, id: u64, now: Instant) -> Result<(), String> {
+        match self.start {
+            Start::At(start) if start > now => Err(format!(
+                "Game {} starts in {}s",
+                id,
+                (start - now).as_secs() + 1
+            )),
+            Start::Later => Err(format!("Game {} has not started", id)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Games hosted by a server, shared between its connections
+///
+/// Every game has its own lock, so that moves on different games do not wait
+/// for each other.
+pub struct Registry {
+    games: Mutex<HashMap<u64, Arc<Mutex<Session>>>>,
+    next_id: Mutex<u64>,
+    pub(crate) races: Mutex<Races>,
+    /// Games left alone for longer are dropped
+    ttl: Option<Duration>,
+}
+
+impl Registry {
+    pub fn new(ttl: Option<Duration>) -> Registry {
+        Registry {
+            games: Mutex::new(HashMap::new()),
+            next_id: Mutex::new(0),
+            races: Mutex::new(Races::default()),
+            ttl,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.games.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, id: u64) -> bool {
+        self.games.lock().unwrap().contains_key(&id)
+    }
+
+    pub fn create(&self, args: &[String]) -> Result<u64, String> {
+        //! Starts a game with command line options, e.g. `--height 9`
+        let client = server_options(args)?.new_client()?;
+        let id = {
+            let mut next_id = self.next_id.lock().unwrap();
+            *next_id += 1;
+            *next_id
+        };
+        let session = Session {
+            owners: vec![None; client.get_state().len()],
+            client,
+            args: args.to_vec(),
+            players: vec![],
+            tokens: vec![],
+            start: Start::Now,
+            race: None,
+            last_access: Instant::now(),
+        };
+        self.games
+            .lock()
+            .unwrap()
+            .insert(id, Arc::new(Mutex::new(session)));
+        Ok(id)
+    }
+
+    fn session(&self, id: u64) -> Result<Arc<Mutex<Session>>, String> {
+        let session = self
+            .games
+            .lock()
+            .unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| format!("Unknown game: {}", id))?;
+        session.lock().unwrap().last_access = Instant::now();
+        Ok(session)
+    }
+
+    pub fn join(
+        &self,
+        id: u64,
+        name: &str,
+        token: Option<&str>,
+    ) -> Result<(usize, String), String> {
+        //! Adds a player to a game, and returns their number with their
+        //! token
+        //!
+        //! Joining again with the name and the token of a player gives back
+        //! the same player, e.g. after a dropped connection.
+        let session = self.session(id)?;
+        let mut session = session.lock().unwrap();
+        if let Some(race) = session.race {
+            return Err(format!(
+                "Game {} is part of race {}: enter the race",
+                id, race
+            ));
+        }
+        if let Some(player) = session.players.iter().position(|p| p.name == name) {
+            let secret = &session.tokens[player];
+            if token != Some(&secret[..]) {
+                return Err(format!("{} already plays game {}", name, id));
+            }
+            return Ok((player, secret.clone()));
+        }
+        Ok(session.add_player(name))
+    }
+
+    pub fn set_cursor(&self, id: u64, player: usize, coords: &[usize]) -> Result<(), String> {
+        let session = self.session(id)?;
+        let mut session = session.lock().unwrap();
+        if session.client.get_state().index(coords).is_none() {
+            return Err(format!("Invalid coordinates for game {}", id));
+        }
+        match session.players.get_mut(player) {
+            Some(p) => p.cursor = Some(coords.to_vec()),
+            None => return Err(format!("Unknown player: {}", player)),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn update<T>(
+        &self,
+        id: u64,
+        f: impl FnOnce(&mut Session) -> T,
+    ) -> Result<T, String> {
+        //! Changes a game outside of the moves of its players
+        let session = self.session(id)?;
+        let mut session = session.lock().unwrap();
+        Ok(f(&mut session))
+    }
+
+    pub fn play<T, F>(&self, request: &Request, view: F) -> Result<T, String>
+    where
+        F: FnOnce(u64, &Session) -> T,
+    {
+        //! Applies a move, or looks at a game, for an anonymous player
+        self.play_as(request, None, view)
+    }
+
+    pub fn play_as<T, F>(
+        &self,
+        request: &Request,
+        player: Option<usize>,
+        view: F,
+    ) -> Result<T, String>
+    where
+        F: FnOnce(u64, &Session) -> T,
+    {
+        //! Applies a move to its game, then looks at the game; other requests
+        //! on a game only look at it
+        //!
+        //! Moves are refused on finished games, on games not started yet,
+        //! on race games from anyone but their racer, outside of the board
+        //! and on cells that changed since the player saw them. The cells a
+        //! move changes are credited to the player.
+        let id = request
+            .game_id()
+            .ok_or_else(|| format!("Not a move: {}", request))?;
+        let session = self.session(id)?;
+        let mut session = session.lock().unwrap();
+        if !request.is_move() {
+            return Ok(view(id, &session));
+        }
+        if session.client.get_game_state() != GameState::Running {
+            return Err(format!("Game {} is over", id));
+        }
+        if let (Some(race), None) = (session.race, player) {
+            return Err(format!("Enter race {} to play game {}", race, id));
+        }
+        session.check_started(id, Instant::now())?;
+        let before = session.client.get_state().data().to_vec();
+        match request {
+            Request::Dig(_, coords, _)
+            | Request::Flag(_, coords, _)
+            | Request::Chord(_, coords, _)
+                if session.client.get_state().index(coords).is_none() =>
+            {
+                return Err(format!("Invalid coordinates for game {}", id));
+            }
+            Request::Dig(_, coords, expected) => {
+                session.check_expected(coords, *expected)?;
+                session.client.query_smart(coords);
+            }
+            Request::Flag(_, coords, expected) => {
+                session.check_expected(coords, *expected)?;
+                session.client.flag(coords);
+            }
+            Request::Chord(_, coords, expected) => {
+                session.check_expected(coords, *expected)?;
+                session.client.chord(coords);
+            }
+            Request::Submit(_) => {
+                session.client.submit()?;
+            }
+            _ => (),
+        }
+        let session = &mut *session;
+        let after = session.client.get_state().data();
+        for (index, owner) in session.owners.iter_mut().enumerate() {
+            if before[index] != after[index] {
+                *owner = player;
+            }
+        }
+        Ok(view(id, session))
+    }
+
+    pub fn expire(&self, now: Instant) -> usize {
+        //! Drops the games idle for longer than the time to live; returns
+        //! how many were dropped
+        let ttl = match self.ttl {
+            Some(ttl) => ttl,
+            None => return 0,
+        };
+        let mut games = self.games.lock().unwrap();
+        let before = games.len();
+        games.retain(|_, session| {
+            let last_access = session.lock().unwrap().last_access;
+            now.saturating_duration_since(last_access) < ttl
+        });
+        let dropped = before - games.len();
+        // races lock before games
+        let alive: HashSet<u64> = games.keys().copied().collect();
+        drop(games);
+        let oldest = now.checked_sub(ttl).unwrap_or(now);
+        self.races
+            .lock()
+            .unwrap()
+            .expire(|game| alive.contains(&game), oldest);
+        dropped
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn registry() {
+        let registry = Registry::new(Some(Duration::from_secs(60)));
+        let id = registry
+            .create(&args("--height 2 --width 2 --bombs 0"))
+            .unwrap();
+        assert_eq!(registry.create(&args("--height 3")), Ok(2));
+        assert!(registry.create(&args("--mode countdown")).is_err());
+        assert!(registry.contains(id));
+        let game_state = |_, session: &Session| session.client.get_game_state();
+        assert_eq!(
+            registry.play(&Request::Dig(id, vec![0, 0], None), game_state),
+            Ok(GameState::Running)
+        );
+        assert_eq!(
+            registry.play(&Request::Submit(id), game_state),
+            Ok(GameState::Won)
+        );
+        assert_eq!(
+            registry.play(&Request::Flag(id, vec![0, 0], None), game_state),
+            Err(String::from("Game 1 is over"))
+        );
+        assert_eq!(
+            registry.play(&Request::State(id), game_state),
+            Ok(GameState::Won)
+        );
+        assert!(registry
+            .play(&Request::Dig(2, vec![5, 0], None), game_state)
+            .is_err());
+        assert!(registry.play(&Request::State(3), game_state).is_err());
+        assert!(registry.play(&Request::Quit, game_state).is_err());
+
+        // games are dropped after a minute without requests
+        let now = Instant::now();
+        assert_eq!(registry.expire(now), 0);
+        registry.play(&Request::State(id), game_state).unwrap();
+        assert_eq!(registry.expire(now + Duration::from_secs(61)), 2);
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn server_options() {
+        let registry = Registry::new(None);
+        assert_eq!(
+            registry.create(&args("--shape-file /etc/passwd")),
+            Err(String::from(
+                "Option not available on the server: --shape-file"
+            ))
+        );
+        assert!(registry
+            .create(&args("--shape --neighbours-mask /etc/hosts"))
+            .is_err());
+        assert!(registry
+            .create(&args("--height 4294967296 --width 4294967297"))
+            .is_err());
+        assert!(registry.create(&args("--width 101")).is_err());
+        assert!(registry.create(&args("--layers 11")).is_err());
+        assert!(registry.create_race(&args("--height 1000")).is_err());
+        assert!(registry.is_empty());
+        let options = GameOptions::parse(args("--height 4294967296 --width 4294967297"));
+        assert!(options.unwrap().new_client().is_err());
+    }
+
+    #[test]
+    fn threads() {
+        let registry = Arc::new(Registry::new(None));
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let registry = registry.clone();
+                std::thread::spawn(move || {
+                    let id = registry
+                        .create(&args("--height 4 --width 4 --bombs 3"))
+                        .unwrap();
+                    registry
+                        .play(&Request::Flag(id, vec![1, 1], None), |_, s| {
+                            s.client.get_flag_locations()
+                        })
+                        .unwrap()
+                })
+            })
+            .collect();
+        for worker in workers {
+            assert_eq!(worker.join().unwrap(), vec![vec![1, 1]]);
+        }
+        assert_eq!(registry.len(), 4);
+        assert_eq!(
+            registry.expire(Instant::now() + Duration::from_secs(3600)),
+            0
+        );
+    }
+}
@@ -0,0 +1,239 @@
+use crate::daily::Date;
+use crate::minefield::client::GameState;
+use crate::minefield::mode::{Game, Mode};
+use crate::options::GameOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Tables of the score store, one file each
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Table {
+    Games,
+    /// Daily challenges, kept apart from the other games
+    Daily,
+}
+
+impl Table {
+    fn file_name(self) -> &'static str {
+        match self {
+            Table::Games => "scores",
+            Table::Daily => "daily",
+        }
+    }
+}
+
+/// Result of a finished game
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    /// Day of the challenge for daily games, day played otherwise
+    pub date: String,
+    /// Board cleared, or at least one board cleared in time attacks
+    pub won: bool,
+    /// Points, or boards cleared in time attacks
+    pub score: usize,
+    pub seconds: u64,
+    /// Board options of the game
+    pub preset: String,
+}
+
+impl Record {
+    pub fn from_game(game: &Game, date: String, preset: String) -> Record {
+        //! Result of a game; time attacks count the boards cleared, over the
+        //! time of every board
+        let client = &game.client;
+        let now = Instant::now();
+        let (won, score, seconds) = match game.mode() {
+            Mode::TimeAttack(_) => {
+                let end = client.end_time().unwrap_or(now);
+                let seconds = game
+                    .start_time()
+                    .map_or(0, |start| end.saturating_duration_since(start).as_secs());
+                (game.boards_won() > 0, game.boards_won(), seconds)
+            }
+            _ => (
+                client.get_game_state() == GameState::Won,
+                client.score(),
+                client.elapsed(now).as_secs(),
+            ),
+        };
+        Record {
+            date,
+            won,
+            score,
+            seconds,
+            preset,
+        }
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{}\t{}\t{}\t{}\t{}",
+            self.date,
+            if self.won { "won" } else { "lost" },
+            self.score,
+            self.seconds,
+            self.preset
+        )
+    }
+
+    fn from_line(line: &str) -> Option<Record> {
+        let mut fields = line.splitn(5, '\t');
+        Some(Record {
+            date: fields.next()?.to_string(),
+            won: fields.next()? == "won",
+            score: fields.next()?.parse().ok()?,
+            seconds: fields.next()?.parse().ok()?,
+            preset: fields.next()?.to_string(),
+        })
+    }
+}
+
+/// Local store of game results, as tab separated lines
+pub struct ScoreStore {
+    dir: PathBuf,
+}
+
+impl ScoreStore {
+    pub fn new(dir: PathBuf) -> ScoreStore {
+        ScoreStore { dir }
+    }
+
+    pub fn default_dir() -> Option<PathBuf> {
+        //! `$MINESWEEPER_SCORES`, or `minesweeper` in the data directory
+        if let Some(path) = std::env::var_os("MINESWEEPER_SCORES") {
+            return Some(PathBuf::from(path));
+        }
+        let data_dir = match std::env::var_os("XDG_DATA_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?)
+                .join(".local")
+                .join("share"),
+        };
+        Some(data_dir.join("minesweeper"))
+    }
+
+    pub fn open() -> Result<ScoreStore, String> {
+        Self::default_dir()
+            .map(ScoreStore::new)
+            .ok_or_else(|| String::from("No directory to store the scores"))
+    }
+
+    pub fn add(&self, table: Table, record: &Record) -> Result<(), String> {
+        let path = self.dir.join(table.file_name());
+        std::fs::create_dir_all(&self.dir)
+            .and_then(|_| {
+                std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+            })
+            .and_then(|mut file| writeln!(file, "{}", record.to_line()))
+            .map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    pub fn records(&self, table: Table) -> Result<Vec<Record>, String> {
+        //! Stored results, oldest first; unreadable lines are skipped
+        let path = self.dir.join(table.file_name());
+        match std::fs::read_to_string(&path) {
+            Ok(text) => Ok(text.lines().filter_map(Record::from_line).collect()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(vec![]),
+            Err(e) => Err(format!("{}: {}", path.display(), e)),
+        }
+    }
+}
+
+pub fn save_game(
+    store: &ScoreStore,
+    options: &GameOptions,
+    game: &Game,
+) -> Result<Option<String>, String> {
+    //! Stores the result of a finished game, and sums up the results of its
+    //! day for daily challenges
+    //!
+    //! Games left before their end are not stored, and do not count as
+    //! attempts at the daily challenge.
+    if !game.is_over() {
+        return Ok(None);
+    }
+    let (table, date) = match options.daily {
+        Some(date) => (Table::Daily, date),
+        None => (Table::Games, Date::today()),
+    };
+    let record = Record::from_game(game, date.to_string(), options.preset());
+    store.add(table, &record)?;
+    if let Mode::TimeAttack(_) = game.mode() {
+        return Ok(Some(format!("Boards cleared: {}", record.score)));
+    }
+    if table == Table::Games {
+        return Ok(Some(format!("Score: {}", record.score)));
+    }
+    let same_day: Vec<Record> = store
+        .records(table)?
+        .into_iter()
+        .filter(|r| r.date == record.date && r.preset == record.preset)
+        .collect();
+    let best = same_day.iter().map(|r| r.score).max().unwrap_or(0);
+    Ok(Some(format!(
+        "Daily challenge {}: score {}, best {} over {} games",
+        record.date,
+        record.score,
+        best,
+        same_day.len()
+    )))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::minefield::client::Client;
+    use std::time::Duration;
+
+    #[test]
+    fn store() {
+        let dir = std::env::temp_dir().join(format!("minesweeper-scores-{}", std::process::id()));
+        let store = ScoreStore::new(dir.clone());
+        assert_eq!(store.records(Table::Daily), Ok(vec![]));
+        let game = Game::from_client(Client::new_random(2, 2, 0));
+        let record = Record::from_game(&game, String::from("2024-02-29"), String::from("2x2"));
+        assert_eq!(record.to_line(), "2024-02-29\tlost\t0\t0\t2x2");
+        store.add(Table::Daily, &record).unwrap();
+        store.add(Table::Games, &record).unwrap();
+        store.add(Table::Daily, &record).unwrap();
+        assert_eq!(store.records(Table::Daily).unwrap().len(), 2);
+        assert_eq!(store.records(Table::Games).unwrap(), vec![record]);
+
+        let args = vec![String::from("--date"), String::from("2024-02-29")];
+        let options = GameOptions::parse(args).unwrap();
+        let mut game = options.new_game().unwrap();
+        // abandoned games are not attempts
+        assert_eq!(save_game(&store, &options, &game), Ok(None));
+        assert_eq!(store.records(Table::Daily).unwrap().len(), 2);
+        game.client.submit().unwrap();
+        let summary = save_game(&store, &options, &game).unwrap();
+        assert_eq!(
+            summary,
+            Some(String::from(
+                "Daily challenge 2024-02-29: score 0, best 0 over 1 games"
+            ))
+        );
+        assert_eq!(store.records(Table::Daily).unwrap().len(), 3);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn time_attack() {
+        let mode = Mode::TimeAttack(Duration::from_secs(60));
+        let mut game = Game::new(mode, Box::new(|| Ok(Client::new_random(2, 2, 0)))).unwrap();
+        game.client.query_smart(&[0, 0]);
+        let start = game.start_time().unwrap();
+        game.client.submit().unwrap();
+        game.tick(start + Duration::from_secs(10));
+        game.client.flag(&[0, 0]);
+        game.tick(start + Duration::from_secs(61));
+        // the boards cleared, over the whole game
+        let record = Record::from_game(&game, String::from("2024-02-29"), String::from("2x2"));
+        assert_eq!((record.won, record.score), (true, 1));
+        assert!(record.seconds < 60);
+    }
+}
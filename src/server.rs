@@ -0,0 +1,294 @@
+use crate::protocol::{self, Request};
+use crate::registry::Registry;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::sync::Arc;
+use std::time::Instant;
+
+/// Longest request line, newline included
+const MAX_LINE: usize = 4096;
+
+/// Players a connection plays as, by game
+pub type Seats = HashMap<u64, usize>;
+
+pub fn handle(registry: &Registry, request: &Request, seats: &mut Seats) -> String {
+    //! Applies a request, and returns the response line
+    //!
+    //! Moves answer with the state of the game; errors with
+    //! `error MESSAGE`. After `join`, the moves of the connection on the
+    //! game are credited to the player, whose token lets other connections
+    //! play as them: `ok player GAME PLAYER TOKEN`. Entering a race answers
+    //! with the game of the racer and their token: `ok racer RACE GAME TOKEN`.
+    let response = match request {
+        Request::New(args) => registry.create(args).map(|id| format!("ok game {}", id)),
+        Request::Quit => Ok(String::from("ok bye")),
+        Request::Join(id, name, token) => {
+            registry
+                .join(*id, name, token.as_deref())
+                .map(|(player, token)| {
+                    seats.insert(*id, player);
+                    format!("ok player {} {} {}", id, player, token)
+                })
+        }
+        Request::Cursor(id, coords) => match seats.get(id) {
+            Some(&player) => registry
+                .set_cursor(*id, player, coords)
+                .map(|()| String::from("ok cursor")),
+            None => Err(format!("Join game {} first", id)),
+        },
+        Request::Players(_) => {
+            registry.play(request, |id, s| protocol::players_line(id, &s.players))
+        }
+        Request::Owners(_) => registry.play(request, |id, s| protocol::owners_line(id, &s.owners)),
+        Request::Race(args) => registry
+            .create_race(args)
+            .map(|id| format!("ok race {}", id)),
+        Request::Enter(id, name, token) => registry
+            .enter_race(*id, name, token.as_deref(), Instant::now())
+            .map(|(game, token)| {
+                // racers are the only player of their game
+                seats.insert(game, 0);
+                format!("ok racer {} {} {}", id, game, token)
+            }),
+        Request::Standings(id) => registry
+            .standings(*id, Instant::now())
+            .map(|standings| protocol::standings_line(&standings)),
+        Request::Options(_) => registry.play(request, |id, s| {
+            format!("ok options {} {}", id, s.args.join(" "))
+                .trim_end()
+                .to_string()
+        }),
+        _ => {
+            let player = request.game_id().and_then(|id| seats.get(&id).copied());
+            registry.play_as(request, player, |id, s| protocol::state_line(id, &s.client))
+        }
+    };
+    match response {
+        Ok(line) => line,
+        Err(e) => format!("error {}", e),
+    }
+}
+
+fn read_line(reader: &mut impl BufRead) -> std::io::Result<Option<Result<String, String>>> {
+    //! Reads a line of at most `MAX_LINE` bytes, or `None` at the end of the
+    //! connection
+    //!
+    //! Longer lines are refused, and skipped to their end a buffer at a time.
+    let mut line = vec![];
+    let read = (&mut *reader)
+        .take(MAX_LINE as u64)
+        .read_until(b'\n', &mut line)?;
+    if read == 0 {
+        return Ok(None);
+    }
+    if read == MAX_LINE && !line.ends_with(b"\n") {
+        loop {
+            let buffer = reader.fill_buf()?;
+            match buffer.iter().position(|&b| b == b'\n') {
+                Some(end) => {
+                    reader.consume(end + 1);
+                    break;
+                }
+                None if buffer.is_empty() => break,
+                None => {
+                    let len = buffer.len();
+                    reader.consume(len);
+                }
+            }
+        }
+        return Ok(Some(Err(String::from("Line too long"))));
+    }
+    let line = String::from_utf8(line).map_err(|_| String::from("Invalid text"));
+    Ok(Some(line.map(|line| {
+        line.trim_end_matches(&['\n', '\r'][..]).to_string()
+    })))
+}
+
+pub fn serve(stream: TcpStream, registry: Arc<Registry>) -> std::io::Result<()> {
+    //! Answers the requests of a connection, one per line, until it quits
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut seats = Seats::new();
+    while let Some(line) = read_line(&mut reader)? {
+        if line.as_ref().is_ok_and(|line| line.trim().is_empty()) {
+            continue;
+        }
+        let response = match line.and_then(|line| Request::parse(&line)) {
+            Ok(request) => {
+                let response = handle(&registry, &request, &mut seats);
+                if request == Request::Quit {
+                    writer.write_all(format!("{}\n", response).as_bytes())?;
+                    break;
+                }
+                response
+            }
+            Err(e) => format!("error {}", e),
+        };
+        writer.write_all(format!("{}\n", response).as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn handle_line(registry: &Registry, line: &str) -> String {
+        handle(registry, &Request::parse(line).unwrap(), &mut Seats::new())
+    }
+
+    #[test]
+    fn sessions() {
+        let registry = Registry::new(None);
+        assert_eq!(
+            handle_line(&registry, "new --height 2 --width 3 --bombs 0"),
+            "ok game 1"
+        );
+        assert_eq!(handle_line(&registry, "new --bombs 5"), "ok game 2");
+        assert_eq!(registry.len(), 2);
+        assert_eq!(
+            handle_line(&registry, "state 1"),
+            "ok state 1 Running 0 1 2x3 h h h h h h"
+        );
+        assert_eq!(
+            handle_line(&registry, "flag 1 0,1"),
+            "ok state 1 Running 0 1 2x3 h f h h h h"
+        );
+        // the flood fill stops at the flag
+        assert_eq!(
+            handle_line(&registry, "dig 1 1,2"),
+            "ok state 1 Running 0 1 2x3 0 f 0 0 0 0"
+        );
+        assert_eq!(
+            handle_line(&registry, "submit 1"),
+            "ok state 1 Lost 0 1 2x3 0 f 0 0 0 0"
+        );
+        assert_eq!(handle_line(&registry, "dig 1 0,0"), "error Game 1 is over");
+        assert_eq!(
+            handle_line(&registry, "dig 2 0,30"),
+            "error Invalid coordinates for game 2"
+        );
+        assert_eq!(handle_line(&registry, "state 3"), "error Unknown game: 3");
+        assert_eq!(
+            handle_line(&registry, "new --shape blob"),
+            "error Unknown shape: blob"
+        );
+        assert!(handle_line(&registry, "new --mode countdown").starts_with("error"));
+        assert_eq!(handle_line(&registry, "quit"), "ok bye");
+    }
+
+    #[test]
+    fn coop() {
+        let registry = Registry::new(None);
+        let (mut alice, mut bob) = (Seats::new(), Seats::new());
+        let play = |seats: &mut Seats, line: &str| {
+            handle(&registry, &Request::parse(line).unwrap(), seats)
+        };
+        play(&mut alice, "new --height 1 --width 3 --bombs 0 --seed 1");
+        assert_eq!(play(&mut bob, "cursor 1 0,0"), "error Join game 1 first");
+        assert!(play(&mut alice, "join 1 alice").starts_with("ok player 1 0 "));
+        let joined = play(&mut bob, "join 1 bob");
+        let token = joined.strip_prefix("ok player 1 1 ").unwrap().to_string();
+        assert_eq!(play(&mut bob, "cursor 1 0,2"), "ok cursor");
+        assert_eq!(play(&mut bob, "players 1"), "ok players 1 alice@- bob@0,2");
+        assert_eq!(
+            play(&mut bob, "options 1"),
+            "ok options 1 --height 1 --width 3 --bombs 0 --seed 1"
+        );
+        play(&mut alice, "flag 1 0,2 h");
+        // bob saw the cell hidden, and does not undo the flag of alice
+        assert_eq!(
+            play(&mut bob, "flag 1 0,2 h"),
+            "error Conflict: 0,2 was changed by alice"
+        );
+        play(&mut bob, "dig 1 0,0 h");
+        assert_eq!(play(&mut alice, "owners 1"), "ok owners 1 1 1 0");
+        // joining again with the token gives back the same player, and only
+        // with the token
+        assert_eq!(
+            play(&mut Seats::new(), "join 1 bob"),
+            "error bob already plays game 1"
+        );
+        assert_eq!(
+            play(&mut Seats::new(), &format!("join 1 bob {}", token)),
+            joined
+        );
+    }
+
+    #[test]
+    fn race() {
+        let registry = Registry::new(None);
+        let (mut alice, mut bob) = (Seats::new(), Seats::new());
+        let play = |seats: &mut Seats, line: &str| {
+            handle(&registry, &Request::parse(line).unwrap(), seats)
+        };
+        assert_eq!(
+            play(&mut alice, "race --height 1 --width 3 --bombs 0 --seed 1"),
+            "ok race 1"
+        );
+        assert!(play(&mut alice, "enter 1 alice").starts_with("ok racer 1 1 "));
+        assert_eq!(
+            play(&mut alice, "standings 1"),
+            "ok standings 1 waiting alice:0:0:Running -:0:0:Running"
+        );
+        assert_eq!(
+            play(&mut alice, "dig 1 0,0"),
+            "error Game 1 has not started"
+        );
+        assert!(play(&mut bob, "enter 1 bob").starts_with("ok racer 1 2 "));
+        // neither anonymous connections nor other players take the seat
+        let mut mallory = Seats::new();
+        assert_eq!(
+            play(&mut mallory, "enter 1 bob"),
+            "error bob already races in race 1"
+        );
+        assert_eq!(
+            play(&mut mallory, "join 2 mallory"),
+            "error Game 2 is part of race 1: enter the race"
+        );
+        assert_eq!(
+            play(&mut mallory, "dig 2 0,0"),
+            "error Enter race 1 to play game 2"
+        );
+        assert_eq!(
+            play(&mut mallory, "options 2"),
+            "ok options 2 --height 1 --width 3 --bombs 0"
+        );
+        assert!(play(&mut bob, "standings 1").starts_with("ok standings 1 starting:"));
+        assert!(play(&mut bob, "dig 2 0,0").starts_with("error Game 2 starts in"));
+        assert_eq!(play(&mut bob, "standings 7"), "error Unknown race: 7");
+    }
+
+    #[test]
+    fn tcp() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let registry = Arc::new(Registry::new(None));
+        let server_registry = registry.clone();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            serve(stream, server_registry).unwrap();
+        });
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream
+            .write_all(b"new --height 1 --width 2 --bombs 0\n\nnope\n")
+            .unwrap();
+        // overlong lines are refused without being kept whole
+        stream.write_all(&vec![b'a'; 10 * MAX_LINE]).unwrap();
+        stream.write_all(b"\nstate 1\r\nquit\n").unwrap();
+        let lines: Vec<String> = BufReader::new(stream).lines().map(|l| l.unwrap()).collect();
+        assert_eq!(
+            lines,
+            vec![
+                "ok game 1",
+                "error Unknown request: nope",
+                "error Line too long",
+                "ok state 1 Running 0 1 1x2 h h",
+                "ok bye"
+            ]
+        );
+        server.join().unwrap();
+        assert_eq!(registry.len(), 1);
+    }
+}
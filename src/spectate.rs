@@ -0,0 +1,277 @@
+use crate::minefield::client::{CellState, Event, GameState};
+use crate::minefield::grid::{Grid, Topology};
+use crate::protocol;
+use std::io::Write;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub fn board_line(state: &Grid<CellState>, lives: usize) -> String {
+    //! First line of a stream: `board DIMS TOPOLOGY LIVES [wrap]`, e.g.
+    //! `board 9x9 square 1`
+    let dims: Vec<String> = state.shape().iter().map(|d| d.to_string()).collect();
+    let topology = match state.topology() {
+        Topology::Square => "square",
+        Topology::Hex => "hex",
+    };
+    let wrap = if state.wraps() { " wrap" } else { "" };
+    format!("board {} {} {}{}", dims.join("x"), topology, lives, wrap)
+}
+
+pub fn parse_board_line(line: &str) -> Result<(Grid<CellState>, usize), String> {
+    //! Empty board and number of lives of a `board` line
+    let invalid = || format!("Invalid board: {}", line);
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let (dims, topology, lives, wrap) = match words.as_slice() {
+        ["board", dims, topology, lives] => (dims, topology, lives, false),
+        ["board", dims, topology, lives, "wrap"] => (dims, topology, lives, true),
+        _ => return Err(invalid()),
+    };
+    let dims = dims
+        .split('x')
+        .map(|d| d.parse().map_err(|_| invalid()))
+        .collect::<Result<Vec<usize>, String>>()?;
+    let topology = match *topology {
+        "square" => Topology::Square,
+        "hex" => Topology::Hex,
+        _ => return Err(invalid()),
+    };
+    let lives = lives.parse().map_err(|_| invalid())?;
+    let cells = vec![CellState::Hidden; dims.iter().product()];
+    let state = Grid::with_dims(&dims, cells)?
+        .with_topology(topology)
+        .with_wrap(wrap)?;
+    Ok((state, lives))
+}
+
+/// Lines waiting for a spectator; spectators further behind are dropped
+const BACKLOG: usize = 64;
+/// Longest wait for a spectator to take a line before they are dropped
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub fn event_line(event: &Event) -> String {
+    //! Line of the stream for a change of the game: `start`,
+    //! `reveal ROW,COL CELL`, `flag ROW,COL CELL` or `result STATE`, with
+    //! the cells in the words of the game server
+    match event {
+        Event::TimerStarted(_) => String::from("start"),
+        Event::Revealed(coords, cell_state) => format!(
+            "reveal {} {}",
+            protocol::format_coords(coords),
+            protocol::cellstate_token(*cell_state)
+        ),
+        Event::FlagChanged(coords, cell_state) => format!(
+            "flag {} {}",
+            protocol::format_coords(coords),
+            protocol::cellstate_token(*cell_state)
+        ),
+        Event::GameOver(game_state) => format!("result {:?}", game_state),
+    }
+}
+
+fn write_lines(mut stream: TcpStream, lines: Receiver<String>) {
+    //! Writes the lines of a spectator, until they leave, stop reading for
+    //! too long, or are dropped from the audience
+    let _ = stream.set_nodelay(true);
+    if stream.set_write_timeout(Some(WRITE_TIMEOUT)).is_err() {
+        return;
+    }
+    for line in lines {
+        if stream.write_all(format!("{}\n", line).as_bytes()).is_err() {
+            return;
+        }
+    }
+}
+
+/// Spectators of a game, and what they were last sent
+struct Audience {
+    /// Lines waiting for every spectator, written by a thread per spectator
+    spectators: Vec<SyncSender<String>>,
+    board: String,
+    latest: Option<String>,
+}
+
+impl Audience {
+    fn welcome(&mut self, stream: TcpStream) {
+        //! Adds a spectator, who gets the board line and the last state first
+        let (lines, lines_rx) = mpsc::sync_channel(BACKLOG);
+        let _ = lines.try_send(self.board.clone());
+        if let Some(latest) = &self.latest {
+            let _ = lines.try_send(latest.clone());
+        }
+        std::thread::spawn(move || write_lines(stream, lines_rx));
+        self.spectators.push(lines);
+    }
+
+    fn send(&mut self, line: &str) {
+        //! Queues a line for every spectator, and forgets those who left or
+        //! fell too far behind; never waits for a spectator
+        if line.starts_with("state ") {
+            self.latest = Some(line.to_string());
+        }
+        self.spectators
+            .retain(|spectator| spectator.try_send(line.to_string()).is_ok());
+    }
+}
+
+/// Live stream of a game, for spectators on a local socket
+///
+/// The stream is made of lines: `board DIMS TOPOLOGY LIVES [wrap]` first,
+/// then the changes of the game as they happen (see `event_line`), each
+/// followed by the board (`state STATE BOMBS LIVES DIMS CELLS...`, as on the
+/// game server). Spectators who arrive late get the board line and the last
+/// state.
+///
+/// Lines reach the spectators after a delay, so that they cannot help the
+/// player's opponent in a race. Spectators who stop reading are dropped.
+pub struct Broadcaster {
+    events: Sender<(Instant, String)>,
+    last_state: Option<String>,
+    address: SocketAddr,
+}
+
+impl Broadcaster {
+    pub fn bind(port: u16, board: String, delay: Duration) -> std::io::Result<Broadcaster> {
+        //! Listens for spectators on 127.0.0.1 (any free port for 0)
+        let listener = TcpListener::bind(("127.0.0.1", port))?;
+        let address = listener.local_addr()?;
+        let audience = Arc::new(Mutex::new(Audience {
+            spectators: vec![],
+            board,
+            latest: None,
+        }));
+        let arrivals = audience.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().filter_map(Result::ok) {
+                arrivals.lock().unwrap().welcome(stream);
+            }
+        });
+        let (events, events_rx) = mpsc::channel::<(Instant, String)>();
+        std::thread::spawn(move || {
+            for (time, line) in events_rx {
+                let due = time + delay;
+                let now = Instant::now();
+                if due > now {
+                    std::thread::sleep(due - now);
+                }
+                audience.lock().unwrap().send(&line);
+            }
+        });
+        Ok(Broadcaster {
+            events,
+            last_state: None,
+            address,
+        })
+    }
+
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    pub fn send_event(&mut self, event: &Event) {
+        //! Streams a change of the game, e.g. `reveal 1,2 3`
+        let _ = self.events.send((Instant::now(), event_line(event)));
+    }
+
+    pub fn send_state(
+        &mut self,
+        game_state: GameState,
+        num_bombs: usize,
+        lives_left: usize,
+        state: &Grid<CellState>,
+    ) {
+        //! Streams the board, if it changed since last time
+        let words = protocol::state_words(game_state, num_bombs, lives_left, state);
+        if self.last_state.as_ref() != Some(&words) {
+            let _ = self
+                .events
+                .send((Instant::now(), format!("state {}", words)));
+            self.last_state = Some(words);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::io::{BufRead, BufReader};
+
+    #[test]
+    fn boards() {
+        let state = Grid::with_dims(&[2, 4, 6], vec![CellState::Hidden; 48])
+            .unwrap()
+            .with_topology(Topology::Hex)
+            .with_wrap(true)
+            .unwrap();
+        let line = board_line(&state, 3);
+        assert_eq!(line, "board 2x4x6 hex 3 wrap");
+        let (parsed, lives) = parse_board_line(&line).unwrap();
+        assert_eq!(lives, 3);
+        assert_eq!(parsed.shape(), &[2, 4, 6]);
+        assert_eq!(parsed.topology(), Topology::Hex);
+        assert!(parsed.wraps());
+        assert!(parse_board_line("board 9x9 round 1").is_err());
+        assert!(parse_board_line("board 9x9 square").is_err());
+        assert!(parse_board_line("board 3x4 hex 1 wrap").is_err());
+    }
+
+    #[test]
+    fn stream() {
+        let state = Grid::new(1, 2, vec![CellState::Hidden; 2]).unwrap();
+        let delay = Duration::from_millis(50);
+        let mut broadcaster = Broadcaster::bind(0, board_line(&state, 1), delay).unwrap();
+        let early = TcpStream::connect(broadcaster.address()).unwrap();
+        let mut early = BufReader::new(early).lines().map(|l| l.unwrap());
+        assert_eq!(early.next().unwrap(), "board 1x2 square 1");
+        let start = Instant::now();
+        broadcaster.send_state(GameState::Running, 0, 1, &state);
+        // the same board is only sent once
+        broadcaster.send_state(GameState::Running, 0, 1, &state);
+        broadcaster.send_event(&Event::TimerStarted(start));
+        broadcaster.send_event(&Event::FlagChanged(vec![0, 1], CellState::Flagged));
+        let mut flagged = state.map(|&c| c);
+        flagged.set(&[0, 1], CellState::Flagged).unwrap();
+        broadcaster.send_state(GameState::Running, 0, 1, &flagged);
+        broadcaster.send_event(&Event::GameOver(GameState::Lost));
+        assert_eq!(early.next().unwrap(), "state Running 0 1 1x2 h h");
+        assert!(start.elapsed() >= delay);
+        assert_eq!(early.next().unwrap(), "start");
+        assert_eq!(early.next().unwrap(), "flag 0,1 f");
+        assert_eq!(early.next().unwrap(), "state Running 0 1 1x2 h f");
+        assert_eq!(early.next().unwrap(), "result Lost");
+
+        let late = TcpStream::connect(broadcaster.address()).unwrap();
+        let mut late = BufReader::new(late).lines().map(|l| l.unwrap());
+        assert_eq!(late.next().unwrap(), "board 1x2 square 1");
+        assert_eq!(late.next().unwrap(), "state Running 0 1 1x2 h f");
+    }
+
+    #[test]
+    fn stalled_spectator() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut audience = Audience {
+            spectators: vec![],
+            board: String::from("board 1x1 square 1"),
+            latest: None,
+        };
+        // one spectator never reads, the other reads everything
+        let _stalled = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        audience.welcome(listener.accept().unwrap().0);
+        let reader = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        audience.welcome(listener.accept().unwrap().0);
+        let lines = std::thread::spawn(move || BufReader::new(reader).lines().count());
+        let line = "x".repeat(1 << 16);
+        let mut sent = 0;
+        while audience.spectators.len() == 2 && sent < 2000 {
+            audience.send(&line);
+            sent += 1;
+            // leaves time to the reader to keep up
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        // the stalled spectator is dropped, and holds nobody up
+        assert_eq!(audience.spectators.len(), 1);
+        audience.spectators.clear();
+        assert_eq!(lines.join().unwrap(), sent + 1);
+    }
+}
@@ -0,0 +1,184 @@
+use crate::minefield::client::{CellState, Event, GameState};
+use crate::minefield::grid::Grid;
+use crate::minefield::mode::{Game, Mode};
+use crate::race::Standings;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+
+/// Player of a game shared with other players
+#[derive(Debug, Clone, PartialEq)]
+pub struct Teammate {
+    pub name: String,
+    pub cursor: Option<Vec<usize>>,
+    /// Number of cells last changed by the player
+    pub cells: usize,
+    /// Whether this is the player of the TUI
+    pub me: bool,
+}
+
+/// Game driven by the TUI: a local `Game`, or a game hosted on a server
+pub trait Backend {
+    /// Board as seen by the player
+    fn state(&self) -> &Grid<CellState>;
+    fn game_state(&self) -> GameState;
+    fn num_bombs(&self) -> usize;
+    fn lives(&self) -> usize;
+    fn lives_left(&self) -> usize;
+    /// Short description of the puzzle variant, if any
+    fn variant(&self) -> Option<String>;
+    /// Score of the game, when the backend knows it
+    fn score(&self) -> Option<usize>;
+    fn start_time(&self) -> Option<Instant>;
+
+    fn dig(&mut self, coords: &[usize]);
+    fn flag(&mut self, coords: &[usize]);
+    fn chord(&mut self, coords: &[usize]);
+    fn submit(&mut self);
+    /// Shows the whole board once the game is over
+    fn reveal(&mut self);
+    /// Catches up with the clocks and the answers of the server
+    fn tick(&mut self, now: Instant) -> GameState;
+
+    fn num_flags(&self) -> usize {
+        self.state()
+            .data()
+            .iter()
+            .map(|cell_state| cell_state.num_flags() as usize)
+            .sum()
+    }
+
+    fn mode(&self) -> Mode {
+        Mode::Classic
+    }
+
+    fn boards_won(&self) -> usize {
+        0
+    }
+
+    /// Time left before the game is lost, for timed modes
+    fn remaining(&self, _now: Instant) -> Option<Duration> {
+        None
+    }
+
+    /// Cells dug but not confirmed yet, drawn as pressed
+    fn pending(&self) -> Vec<Vec<usize>> {
+        vec![]
+    }
+
+    /// Whether answers are awaited, so that the TUI looks for them more often
+    fn busy(&self) -> bool {
+        false
+    }
+
+    /// Connection problems and refused moves, shown below the board
+    fn status(&self) -> Option<String> {
+        None
+    }
+
+    /// Name of the game, for games hosted on a server
+    fn title(&self) -> Option<String> {
+        None
+    }
+
+    /// Shows the other players of a shared game where the player looks
+    fn set_cursor(&mut self, _coords: &[usize]) {}
+
+    /// Players of a shared game
+    fn teammates(&self) -> Vec<Teammate> {
+        vec![]
+    }
+
+    /// Player who last changed a cell of a shared game
+    fn owner(&self, _coords: &[usize]) -> Option<String> {
+        None
+    }
+
+    /// Progress of the racers, for games that are part of a race
+    fn standings(&self) -> Option<&Standings> {
+        None
+    }
+
+    /// Number of the player among the racers
+    fn racer(&self) -> Option<usize> {
+        None
+    }
+
+    /// Changes of the game as they happen, for backends that play it
+    fn subscribe(&mut self) -> Option<Receiver<Event>> {
+        None
+    }
+}
+
+impl Backend for Game {
+    fn state(&self) -> &Grid<CellState> {
+        self.client.get_state()
+    }
+
+    fn game_state(&self) -> GameState {
+        self.client.get_game_state()
+    }
+
+    fn num_bombs(&self) -> usize {
+        self.client.num_bombs()
+    }
+
+    fn lives(&self) -> usize {
+        self.client.lives()
+    }
+
+    fn lives_left(&self) -> usize {
+        self.client.lives_left()
+    }
+
+    fn variant(&self) -> Option<String> {
+        self.client.minefield.variant()
+    }
+
+    fn score(&self) -> Option<usize> {
+        Some(self.client.score())
+    }
+
+    fn start_time(&self) -> Option<Instant> {
+        Game::start_time(self)
+    }
+
+    fn dig(&mut self, coords: &[usize]) {
+        self.client.query_smart(coords);
+    }
+
+    fn flag(&mut self, coords: &[usize]) {
+        self.client.flag(coords);
+    }
+
+    fn chord(&mut self, coords: &[usize]) {
+        self.client.chord(coords);
+    }
+
+    fn submit(&mut self) {
+        self.client.submit().unwrap();
+    }
+
+    fn reveal(&mut self) {
+        self.client.reveal(true);
+    }
+
+    fn tick(&mut self, now: Instant) -> GameState {
+        Game::tick(self, now)
+    }
+
+    fn mode(&self) -> Mode {
+        Game::mode(self)
+    }
+
+    fn boards_won(&self) -> usize {
+        Game::boards_won(self)
+    }
+
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        Game::remaining(self, now)
+    }
+
+    fn subscribe(&mut self) -> Option<Receiver<Event>> {
+        Some(Game::subscribe(self))
+    }
+}
@@ -0,0 +1,588 @@
+use std::collections::HashMap;
+use std::path::PathBuf;
+use termion::event::Key;
+
+/// Commands that can be bound to keys
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyCommand {
+    Left,
+    Right,
+    Up,
+    Down,
+    LineStart,
+    LineEnd,
+    Top,
+    Bottom,
+    NextHidden,
+    PreviousHidden,
+    LayerAbove,
+    LayerBelow,
+    Dig,
+    Flag,
+    Chord,
+    Submit,
+    Zoom,
+    Autoplay,
+    Faster,
+    Slower,
+    Help,
+    Suspend,
+    Exit,
+}
+
+const ALL_COMMANDS: [KeyCommand; 23] = [
+    KeyCommand::Left,
+    KeyCommand::Right,
+    KeyCommand::Up,
+    KeyCommand::Down,
+    KeyCommand::LineStart,
+    KeyCommand::LineEnd,
+    KeyCommand::Top,
+    KeyCommand::Bottom,
+    KeyCommand::NextHidden,
+    KeyCommand::PreviousHidden,
+    KeyCommand::LayerAbove,
+    KeyCommand::LayerBelow,
+    KeyCommand::Dig,
+    KeyCommand::Flag,
+    KeyCommand::Chord,
+    KeyCommand::Submit,
+    KeyCommand::Zoom,
+    KeyCommand::Autoplay,
+    KeyCommand::Faster,
+    KeyCommand::Slower,
+    KeyCommand::Help,
+    KeyCommand::Suspend,
+    KeyCommand::Exit,
+];
+
+impl KeyCommand {
+    /// Name used in the configuration file
+    pub fn name(self) -> &'static str {
+        match self {
+            KeyCommand::Left => "left",
+            KeyCommand::Right => "right",
+            KeyCommand::Up => "up",
+            KeyCommand::Down => "down",
+            KeyCommand::LineStart => "line_start",
+            KeyCommand::LineEnd => "line_end",
+            KeyCommand::Top => "top",
+            KeyCommand::Bottom => "bottom",
+            KeyCommand::NextHidden => "next_hidden",
+            KeyCommand::PreviousHidden => "previous_hidden",
+            KeyCommand::LayerAbove => "layer_above",
+            KeyCommand::LayerBelow => "layer_below",
+            KeyCommand::Dig => "dig",
+            KeyCommand::Flag => "flag",
+            KeyCommand::Chord => "chord",
+            KeyCommand::Submit => "submit",
+            KeyCommand::Zoom => "zoom",
+            KeyCommand::Autoplay => "autoplay",
+            KeyCommand::Faster => "faster",
+            KeyCommand::Slower => "slower",
+            KeyCommand::Help => "help",
+            KeyCommand::Suspend => "suspend",
+            KeyCommand::Exit => "exit",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<KeyCommand> {
+        ALL_COMMANDS.iter().cloned().find(|c| c.name() == name)
+    }
+
+    /// Short description displayed in the help overlay
+    pub fn description(self) -> &'static str {
+        match self {
+            KeyCommand::Left => "move left",
+            KeyCommand::Right => "move right",
+            KeyCommand::Up => "move up",
+            KeyCommand::Down => "move down",
+            KeyCommand::LineStart => "first column",
+            KeyCommand::LineEnd => "last column",
+            KeyCommand::Top => "first row",
+            KeyCommand::Bottom => "last row",
+            KeyCommand::NextHidden => "next hidden cell",
+            KeyCommand::PreviousHidden => "previous hidden cell",
+            KeyCommand::LayerAbove => "layer above (3D)",
+            KeyCommand::LayerBelow => "layer below (3D)",
+            KeyCommand::Dig => "dig",
+            KeyCommand::Flag => "flag",
+            KeyCommand::Chord => "chord",
+            KeyCommand::Submit => "submit flags",
+            KeyCommand::Zoom => "zoom",
+            KeyCommand::Autoplay => "let the solver play",
+            KeyCommand::Faster => "faster solver",
+            KeyCommand::Slower => "slower solver",
+            KeyCommand::Help => "toggle help",
+            KeyCommand::Suspend => "suspend",
+            KeyCommand::Exit => "quit",
+        }
+    }
+
+    /// Movements can be repeated with a count prefix, e.g. `5l`
+    pub fn is_movement(self) -> bool {
+        !matches!(
+            self,
+            KeyCommand::Dig
+                | KeyCommand::Flag
+                | KeyCommand::Chord
+                | KeyCommand::Submit
+                | KeyCommand::Zoom
+                | KeyCommand::Autoplay
+                | KeyCommand::Faster
+                | KeyCommand::Slower
+                | KeyCommand::Help
+                | KeyCommand::Suspend
+                | KeyCommand::Exit
+        )
+    }
+}
+
+/// Result of feeding a key to a `KeySequence`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyInput {
+    /// A command was recognized, with its repeat count
+    Command(KeyCommand, usize),
+    /// More keys are needed (count prefix, or start of a sequence like `gg`)
+    Pending,
+    /// Sequence does not match any binding
+    Unbound,
+}
+
+/// Association between key sequences and commands
+#[derive(Debug, Clone, PartialEq)]
+pub struct Keymap {
+    bindings: HashMap<KeyCommand, Vec<Vec<Key>>>,
+}
+
+fn key_sequences(commands: &[(KeyCommand, &str)]) -> Keymap {
+    let mut keymap = Keymap {
+        bindings: HashMap::new(),
+    };
+    for &(command, keys) in commands {
+        let sequences = keys
+            .split_whitespace()
+            .map(|seq| parse_key_sequence(seq).unwrap())
+            .collect();
+        keymap.bindings.insert(command, sequences);
+    }
+    keymap
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        //! Arrow keys and vim-style bindings
+        key_sequences(&[
+            (KeyCommand::Left, "<Left> h"),
+            (KeyCommand::Right, "<Right> l"),
+            (KeyCommand::Up, "<Up> k"),
+            (KeyCommand::Down, "<Down> j"),
+            (KeyCommand::LineStart, "0 ^ <Home>"),
+            (KeyCommand::LineEnd, "$ <End>"),
+            (KeyCommand::Top, "gg"),
+            (KeyCommand::Bottom, "G"),
+            (KeyCommand::NextHidden, "n <Tab>"),
+            (KeyCommand::PreviousHidden, "N"),
+            (KeyCommand::LayerAbove, "[ <PageUp>"),
+            (KeyCommand::LayerBelow, "] <PageDown>"),
+            (KeyCommand::Dig, "<Space> d"),
+            (KeyCommand::Flag, "f"),
+            (KeyCommand::Chord, "c"),
+            (KeyCommand::Submit, "s"),
+            (KeyCommand::Zoom, "z"),
+            (KeyCommand::Autoplay, "p"),
+            (KeyCommand::Faster, "+"),
+            (KeyCommand::Slower, "-"),
+            (KeyCommand::Help, "?"),
+            (KeyCommand::Suspend, "<C-z>"),
+            (KeyCommand::Exit, "q <C-c>"),
+        ])
+    }
+}
+
+impl Keymap {
+    pub fn wasd() -> Keymap {
+        //! WASD movements, for players who keep their left hand on the keyboard
+        key_sequences(&[
+            (KeyCommand::Left, "<Left> a"),
+            (KeyCommand::Right, "<Right> d"),
+            (KeyCommand::Up, "<Up> w"),
+            (KeyCommand::Down, "<Down> s"),
+            (KeyCommand::LineStart, "A <Home>"),
+            (KeyCommand::LineEnd, "D <End>"),
+            (KeyCommand::Top, "W"),
+            (KeyCommand::Bottom, "S"),
+            (KeyCommand::NextHidden, "<Tab>"),
+            (KeyCommand::PreviousHidden, "<BackTab>"),
+            (KeyCommand::LayerAbove, "[ <PageUp>"),
+            (KeyCommand::LayerBelow, "] <PageDown>"),
+            (KeyCommand::Dig, "<Space> e"),
+            (KeyCommand::Flag, "f"),
+            (KeyCommand::Chord, "c"),
+            (KeyCommand::Submit, "<Enter>"),
+            (KeyCommand::Zoom, "z"),
+            (KeyCommand::Autoplay, "p"),
+            (KeyCommand::Faster, "+"),
+            (KeyCommand::Slower, "-"),
+            (KeyCommand::Help, "?"),
+            (KeyCommand::Suspend, "<C-z>"),
+            (KeyCommand::Exit, "q <C-c>"),
+        ])
+    }
+
+    pub fn bind(&mut self, command: KeyCommand, sequences: Vec<Vec<Key>>) -> Result<(), String> {
+        //! Replaces the key sequences bound to a command, unless another
+        //! command uses one of them
+        let old = self.bindings.insert(command, sequences);
+        let checked = self.check();
+        if checked.is_err() {
+            match old {
+                Some(old) => self.bindings.insert(command, old),
+                None => self.bindings.remove(&command),
+            };
+        }
+        checked
+    }
+
+    fn check(&self) -> Result<(), String> {
+        //! Refuses key sequences bound to two commands, which would run one
+        //! or the other at random
+        for (i, &command) in ALL_COMMANDS.iter().enumerate() {
+            for &other in &ALL_COMMANDS[i + 1..] {
+                let (sequences, others) =
+                    match (self.bindings.get(&command), self.bindings.get(&other)) {
+                        (Some(sequences), Some(others)) => (sequences, others),
+                        _ => continue,
+                    };
+                if let Some(seq) = sequences.iter().find(|seq| others.contains(seq)) {
+                    return Err(format!(
+                        "{} is bound to both {} and {}",
+                        seq.iter().map(key_to_string).collect::<String>(),
+                        command.name(),
+                        other.name()
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn parse(config: &str) -> Result<Keymap, String> {
+        //! Reads a keymap configuration
+        //!
+        //! Each line binds a command to whitespace-separated key sequences,
+        //! and replaces the default bindings of that command:
+        //! ```text
+        //! # start from the WASD layout instead of the default one
+        //! preset = wasd
+        //! dig = <Space> x
+        //! top = gg
+        //! ```
+        //! Special keys are written `<Left>`, `<Space>`, `<Enter>`, `<C-x>`...
+        let mut keymap = Keymap::default();
+        for (line_num, line) in config.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let mut parts = line.splitn(2, '=');
+            let name = parts.next().unwrap().trim();
+            let value = parts
+                .next()
+                .ok_or(format!("line {}: expected 'command = keys'", line_num + 1))?
+                .trim();
+            if name == "preset" {
+                keymap = match value {
+                    "default" | "vim" => Keymap::default(),
+                    "wasd" => Keymap::wasd(),
+                    _ => return Err(format!("line {}: unknown preset {}", line_num + 1, value)),
+                };
+                continue;
+            }
+            let command = KeyCommand::from_name(name).ok_or(format!(
+                "line {}: unknown command {}",
+                line_num + 1,
+                name
+            ))?;
+            let sequences = value
+                .split_whitespace()
+                .map(parse_key_sequence)
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(|e| format!("line {}: {}", line_num + 1, e))?;
+            keymap.bindings.insert(command, sequences);
+        }
+        // checked once every line is read, so that keys can be swapped
+        keymap.check()?;
+        Ok(keymap)
+    }
+
+    pub fn config_path() -> Option<PathBuf> {
+        //! `$MINESWEEPER_KEYMAP`, or `keymap` in the minesweeper configuration directory
+        if let Some(path) = std::env::var_os("MINESWEEPER_KEYMAP") {
+            return Some(PathBuf::from(path));
+        }
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_dir.join("minesweeper").join("keymap"))
+    }
+
+    pub fn load() -> Result<Keymap, String> {
+        //! Loads the keymap from the configuration file, if there is one
+        match Self::config_path() {
+            Some(path) if path.exists() => {
+                let config = std::fs::read_to_string(&path)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+                Self::parse(&config).map_err(|e| format!("{}: {}", path.display(), e))
+            }
+            _ => Ok(Keymap::default()),
+        }
+    }
+
+    fn lookup(&self, keys: &[Key]) -> KeyInput {
+        let mut is_prefix = false;
+        for (&command, sequences) in self.bindings.iter() {
+            for seq in sequences {
+                if seq[..] == *keys {
+                    return KeyInput::Command(command, 1);
+                }
+                if seq.len() > keys.len() && seq[..keys.len()] == *keys {
+                    is_prefix = true;
+                }
+            }
+        }
+        if is_prefix {
+            KeyInput::Pending
+        } else {
+            KeyInput::Unbound
+        }
+    }
+
+    pub fn keys_for(&self, command: KeyCommand) -> Option<String> {
+        //! First key sequence bound to a command, for hints
+        let seq = self.bindings.get(&command)?.first()?;
+        Some(seq.iter().map(key_to_string).collect())
+    }
+
+    pub fn describe(&self) -> Vec<(String, &'static str)> {
+        //! Active bindings as (keys, description), in a stable order
+        ALL_COMMANDS
+            .iter()
+            .filter_map(|&command| {
+                let sequences = self.bindings.get(&command)?;
+                if sequences.is_empty() {
+                    return None;
+                }
+                let keys = sequences
+                    .iter()
+                    .map(|seq| seq.iter().map(key_to_string).collect::<String>())
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                Some((keys, command.description()))
+            })
+            .collect()
+    }
+}
+
+/// Keys typed so far, waiting to be resolved into a command
+#[derive(Debug, Default)]
+pub struct KeySequence {
+    count: Option<usize>,
+    keys: Vec<Key>,
+}
+
+impl KeySequence {
+    pub fn push(&mut self, keymap: &Keymap, key: Key) -> KeyInput {
+        //! Feeds a key, and returns the command once a full sequence was typed
+        if let Key::Char(c) = key {
+            // a leading '0' is a command, not a count
+            let is_count_digit = c.is_ascii_digit() && (c != '0' || self.count.is_some());
+            if self.keys.is_empty() && is_count_digit {
+                let digit = c.to_digit(10).unwrap() as usize;
+                self.count = Some((self.count.unwrap_or(0) * 10 + digit).min(999));
+                return KeyInput::Pending;
+            }
+        }
+        self.keys.push(key);
+        match keymap.lookup(&self.keys) {
+            KeyInput::Command(command, _) => {
+                let count = self.count.unwrap_or(1);
+                self.clear();
+                KeyInput::Command(command, count)
+            }
+            KeyInput::Pending => KeyInput::Pending,
+            KeyInput::Unbound => {
+                self.clear();
+                KeyInput::Unbound
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.count = None;
+        self.keys.clear();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count.is_none() && self.keys.is_empty()
+    }
+}
+
+pub fn parse_key_sequence(input: &str) -> Result<Vec<Key>, String> {
+    //! Parses a sequence like `gg`, `<Left>` or `<C-c>`
+    let mut keys = vec![];
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        if c != '<' {
+            keys.push(Key::Char(c));
+            continue;
+        }
+        let name: String = chars.by_ref().take_while(|&c| c != '>').collect();
+        let key = match &name[..] {
+            "" => Key::Char('<'),
+            "Left" => Key::Left,
+            "Right" => Key::Right,
+            "Up" => Key::Up,
+            "Down" => Key::Down,
+            "Home" => Key::Home,
+            "End" => Key::End,
+            "PageUp" => Key::PageUp,
+            "PageDown" => Key::PageDown,
+            "Tab" => Key::Char('\t'),
+            "BackTab" => Key::BackTab,
+            "Enter" => Key::Char('\n'),
+            "Space" => Key::Char(' '),
+            "Esc" => Key::Esc,
+            "Backspace" => Key::Backspace,
+            "Delete" => Key::Delete,
+            other if other.starts_with("C-") && other.chars().count() == 3 => {
+                Key::Ctrl(other.chars().nth(2).unwrap())
+            }
+            other if other.starts_with("A-") && other.chars().count() == 3 => {
+                Key::Alt(other.chars().nth(2).unwrap())
+            }
+            other => return Err(format!("unknown key <{}>", other)),
+        };
+        keys.push(key);
+    }
+    if keys.is_empty() {
+        Err(String::from("empty key sequence"))
+    } else {
+        Ok(keys)
+    }
+}
+
+pub fn key_to_string(key: &Key) -> String {
+    match key {
+        Key::Char(' ') => String::from("<Space>"),
+        Key::Char('\t') => String::from("<Tab>"),
+        Key::Char('\n') => String::from("<Enter>"),
+        Key::Char('<') => String::from("<>"),
+        Key::Char(c) => c.to_string(),
+        Key::Ctrl(c) => format!("<C-{}>", c),
+        Key::Alt(c) => format!("<A-{}>", c),
+        Key::Esc => String::from("<Esc>"),
+        other => format!("<{:?}>", other),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn key_sequences() {
+        assert_eq!(
+            parse_key_sequence("gg"),
+            Ok(vec![Key::Char('g'), Key::Char('g')])
+        );
+        assert_eq!(
+            parse_key_sequence("<C-c>x<Left>"),
+            Ok(vec![Key::Ctrl('c'), Key::Char('x'), Key::Left])
+        );
+        assert!(parse_key_sequence("<Foo>").is_err());
+        for seq in &["<Space>", "<Enter>", "<Tab>", "<Left>", "<C-c>", "$", "<>"] {
+            let keys = parse_key_sequence(seq).unwrap();
+            assert_eq!(&keys.iter().map(key_to_string).collect::<String>(), seq);
+        }
+    }
+
+    #[test]
+    fn counts_and_sequences() {
+        let keymap = Keymap::default();
+        let mut seq = KeySequence::default();
+        assert_eq!(
+            seq.push(&keymap, Key::Char('l')),
+            KeyInput::Command(KeyCommand::Right, 1)
+        );
+        assert_eq!(seq.push(&keymap, Key::Char('1')), KeyInput::Pending);
+        assert_eq!(seq.push(&keymap, Key::Char('0')), KeyInput::Pending);
+        assert_eq!(
+            seq.push(&keymap, Key::Char('j')),
+            KeyInput::Command(KeyCommand::Down, 10)
+        );
+        assert!(seq.is_empty());
+        // leading zero goes to the first column
+        assert_eq!(
+            seq.push(&keymap, Key::Char('0')),
+            KeyInput::Command(KeyCommand::LineStart, 1)
+        );
+        assert_eq!(seq.push(&keymap, Key::Char('g')), KeyInput::Pending);
+        assert_eq!(
+            seq.push(&keymap, Key::Char('g')),
+            KeyInput::Command(KeyCommand::Top, 1)
+        );
+        assert_eq!(seq.push(&keymap, Key::Char('g')), KeyInput::Pending);
+        assert_eq!(seq.push(&keymap, Key::Char('x')), KeyInput::Unbound);
+        assert!(seq.is_empty());
+        assert_eq!(
+            seq.push(&keymap, Key::Ctrl('z')),
+            KeyInput::Command(KeyCommand::Suspend, 1)
+        );
+    }
+
+    #[test]
+    fn config() {
+        let keymap = Keymap::parse(
+            "# comment\n\
+             preset = wasd\n\
+             \n\
+             dig = x <Space>\n\
+             top = gg",
+        )
+        .unwrap();
+        let mut seq = KeySequence::default();
+        assert_eq!(
+            seq.push(&keymap, Key::Char('w')),
+            KeyInput::Command(KeyCommand::Up, 1)
+        );
+        assert_eq!(
+            seq.push(&keymap, Key::Char('x')),
+            KeyInput::Command(KeyCommand::Dig, 1)
+        );
+        assert_eq!(seq.push(&keymap, Key::Char('e')), KeyInput::Unbound);
+        let description = keymap.describe();
+        assert!(description.contains(&(String::from("x <Space>"), "dig")));
+        assert!(description.contains(&(String::from("gg"), "first row")));
+
+        assert!(Keymap::parse("dig").is_err());
+        assert!(Keymap::parse("explode = x").is_err());
+        assert!(Keymap::parse("preset = emacs").is_err());
+        assert!(Keymap::parse("dig = <Nope>").is_err());
+
+        // keys bound to two commands are refused, once every line is read
+        assert_eq!(
+            Keymap::parse("flag = d"),
+            Err(String::from("d is bound to both dig and flag"))
+        );
+        assert!(Keymap::parse("flag = d\ndig = f").is_ok());
+        let mut keymap = Keymap::default();
+        let f = parse_key_sequence("f").unwrap();
+        assert!(keymap.bind(KeyCommand::Chord, vec![f.clone()]).is_err());
+        assert_eq!(keymap.keys_for(KeyCommand::Chord), Some(String::from("c")));
+        assert!(keymap.bind(KeyCommand::Flag, vec![f]).is_ok());
+        assert!(Keymap::default().check().is_ok());
+        assert!(Keymap::wasd().check().is_ok());
+    }
+}
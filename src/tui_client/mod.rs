@@ -0,0 +1,1591 @@
+use crate::minefield;
+extern crate termion;
+
+pub mod backend;
+pub mod keymap;
+pub mod remote;
+pub mod screen;
+pub mod spectator;
+pub mod terminal;
+
+use crate::bot::{self, Action, Bot, Solver, Step};
+use crate::glyphs::{circled_char, count_char, WRAP_EDGES};
+use crate::race::{RaceState, Standings};
+use crate::spectate::Broadcaster;
+use backend::{Backend, Teammate};
+use keymap::{KeyCommand, KeyInput, KeySequence, Keymap};
+use minefield::client::{self, CellState, GameState};
+use minefield::field::Cell;
+use minefield::grid::Topology;
+use minefield::mode::{Game, Mode};
+use screen::{Renderer, Screen};
+use std::string::ToString;
+use std::sync::mpsc::Receiver;
+use std::time::{Duration, Instant};
+use terminal::{Input, Inputs, TerminalGuard};
+use termion::color;
+use termion::event::{Event, MouseButton, MouseEvent};
+
+const GRID_OFFSET: (u16, u16) = (5, 3); // (row, col)
+const BOX_CHARS: [char; 10] = ['+', '-', '+', '|', '━', '┃', '┏', '┓', '┗', '┛'];
+/// Dashed borders around boards whose edges wrap around
+const WRAP_BOX_CHARS: [char; 10] = [
+    '+',
+    '-',
+    '+',
+    '|',
+    WRAP_EDGES.0,
+    WRAP_EDGES.1,
+    '┏',
+    '┓',
+    '┗',
+    '┛',
+];
+/// Screen footprint of a grid cell for each zoom level, as (rows, cols)
+const ZOOM_LEVELS: [(u16, u16); 4] = [(1, 1), (1, 2), (1, 3), (2, 3)];
+/// Longest wait between two redraws of the clocks
+const TICK: Duration = Duration::from_millis(250);
+/// Wait between two looks at the answers of a remote game
+const BUSY_TICK: Duration = Duration::from_millis(20);
+/// Wait between two steps of the solver in autoplay, from the slowest speed
+/// to the fastest
+const AUTOPLAY_DELAYS: [Duration; 5] = [
+    Duration::from_millis(2000),
+    Duration::from_millis(1000),
+    Duration::from_millis(500),
+    Duration::from_millis(250),
+    Duration::from_millis(100),
+];
+
+/// Built-in solver playing the game in place of the player
+///
+/// Every move is announced first, with the cell highlighted and the deduction
+/// below the board, and played on the next step.
+struct Autoplay {
+    solver: Solver,
+    /// Move announced, to be played on the next step
+    next: Option<Step>,
+    /// Time of the next step
+    due: Instant,
+    /// Move announced and its deduction, shown below the board
+    message: String,
+}
+
+pub struct TuiClient<B = Game> {
+    /// Cursor position in grid coordinates (row, col), within the shown layer
+    current_cursor: (usize, usize),
+    /// Layer shown on three-dimensional boards, as counted by `Grid::layer_coords`
+    layer: usize,
+    /// Board being played: a local game with the rules of its mode, or a
+    /// remote one
+    backend: B,
+    /// Index in ZOOM_LEVELS
+    zoom: usize,
+    /// Mouse buttons currently held down
+    mouse_buttons: Vec<MouseButton>,
+    /// Cell under the mouse while a button is held down
+    pressed_cell: Option<(usize, usize)>,
+    keymap: Keymap,
+    /// Keys typed so far (count prefix, start of a sequence)
+    key_sequence: KeySequence,
+    show_help: bool,
+    renderer: Renderer,
+    /// Displayed below the grid
+    status_message: Option<String>,
+    /// Stream of the game for spectators
+    broadcaster: Option<Broadcaster>,
+    /// Changes of the game, for the stream
+    events: Option<Receiver<client::Event>>,
+    /// Solver playing instead of the player, until they take back control
+    autoplay: Option<Autoplay>,
+    /// Index in AUTOPLAY_DELAYS
+    autoplay_speed: usize,
+}
+
+#[derive(PartialEq, Debug)]
+enum TuiAction {
+    Exit,
+    Flag,
+    Query,
+    Chord,
+    Submit,
+    Zoom,
+    Suspend,
+    None,
+}
+
+fn cellstate_style(c: &CellState) -> (String, char) {
+    //! Color prefix and symbol used to draw a cell state
+    match c {
+        CellState::Hidden => (format!("{}", color::Bg(color::White)), ' '),
+        CellState::Flagged => (
+            format!("{}{}", color::Fg(color::Black), color::Bg(color::White)),
+            '¶',
+        ),
+        CellState::Flags(k) => (
+            format!("{}{}", color::Fg(color::Black), color::Bg(color::White)),
+            circled_char(*k, true),
+        ),
+        CellState::Revealed(r) => match r {
+            Cell::Bomb => (String::new(), '*'),
+            Cell::Mines(k) => (String::new(), circled_char(*k, false)),
+            Cell::AntiMine => (format!("{}", color::Fg(color::LightCyan)), '⊖'),
+            Cell::Clean(0) => (String::new(), ' '),
+            Cell::Clean(i) => (
+                match i {
+                    1 => format!("{}", color::Fg(color::Blue)),
+                    2 => format!("{}", color::Fg(color::Green)),
+                    3 => format!("{}", color::Fg(color::Red)),
+                    4 => format!("{}", color::Fg(color::Cyan)),
+                    5 => format!("{}", color::Fg(color::LightGreen)),
+                    6 => format!("{}", color::Fg(color::Magenta)),
+                    i if *i < 0 => format!("{}", color::Fg(color::Yellow)),
+                    _ => format!("{}", color::Fg(color::White)),
+                },
+                count_char(*i),
+            ),
+        },
+        CellState::Marked => (String::new(), '▟'),
+        CellState::Exploded(cell) => {
+            let (_, symbol) = cellstate_style(&CellState::Revealed(*cell));
+            (
+                format!("{}{}", color::Fg(color::Black), color::Bg(color::Red)),
+                symbol,
+            )
+        }
+    }
+}
+
+fn autoplay_style() -> String {
+    //! Background marking the cell the solver is about to play
+    format!("{}{}", color::Fg(color::Black), color::Bg(color::Yellow))
+}
+
+fn player_style(player: usize) -> String {
+    //! Background marking the cursor of a player of a shared game
+    let background = match player % 5 {
+        0 => format!("{}", color::Bg(color::LightMagenta)),
+        1 => format!("{}", color::Bg(color::LightCyan)),
+        2 => format!("{}", color::Bg(color::LightYellow)),
+        3 => format!("{}", color::Bg(color::LightGreen)),
+        _ => format!("{}", color::Bg(color::LightBlue)),
+    };
+    format!("{}{}", color::Fg(color::Black), background)
+}
+
+impl TuiClient<Game> {
+    pub fn new(client: minefield::client::Client) -> TuiClient {
+        TuiClient::from_game(Game::from_client(client))
+    }
+
+    pub fn from_game(game: Game) -> TuiClient {
+        TuiClient::from_backend(game)
+    }
+
+    pub fn game(&self) -> &Game {
+        &self.backend
+    }
+}
+
+impl<B: Backend> TuiClient<B> {
+    fn cell_size(&self) -> (u16, u16) {
+        let (cell_h, cell_w) = ZOOM_LEVELS[self.zoom];
+        if self.is_hex() {
+            // hexagonal rows are shifted by half a cell
+            (cell_h, cell_w.max(2))
+        } else {
+            (cell_h, cell_w)
+        }
+    }
+
+    fn is_hex(&self) -> bool {
+        self.backend.state().topology() == Topology::Hex
+    }
+
+    fn row_shift(&self, row: usize) -> u16 {
+        //! Horizontal offset of a grid row, in screen columns
+        if self.is_hex() && row % 2 == 1 {
+            self.cell_size().1 / 2
+        } else {
+            0
+        }
+    }
+
+    fn cell_coords(&self, (row, col): (usize, usize)) -> Vec<usize> {
+        //! Full coordinates of a cell of the shown layer
+        self.backend
+            .state()
+            .layer_coords(self.layer, row, col)
+            .unwrap()
+    }
+
+    fn to_grid_coordinates(&self, cursor_row: u16, cursor_col: u16) -> Option<(usize, usize)> {
+        let (grid_h, grid_w) = self.backend.state().layer_shape();
+        let (cell_h, cell_w) = self.cell_size();
+        let first_row = GRID_OFFSET.0 + 1;
+        if cursor_row < first_row {
+            return None;
+        }
+        let grid_row: usize = ((cursor_row - first_row) / cell_h) as usize;
+        let first_col = GRID_OFFSET.1 + 1 + self.row_shift(grid_row);
+        if cursor_col < first_col {
+            return None;
+        }
+        let grid_col: usize = ((cursor_col - first_col) / cell_w) as usize;
+        if grid_row < grid_h && grid_col < grid_w {
+            Some((grid_row, grid_col))
+        } else {
+            None
+        }
+    }
+
+    fn to_screen_coordinates(&self, row: usize, col: usize) -> (u16, u16) {
+        //! Screen position (row, col) of the symbol of a grid cell
+        let (cell_h, cell_w) = self.cell_size();
+        (
+            GRID_OFFSET.0 + 1 + cell_h * row as u16 + (cell_h - 1) / 2,
+            GRID_OFFSET.1 + 1 + self.row_shift(row) + cell_w * col as u16 + (cell_w - 1) / 2,
+        )
+    }
+
+    pub fn from_backend(backend: B) -> TuiClient<B> {
+        TuiClient {
+            current_cursor: (0, 0),
+            layer: 0,
+            backend,
+            zoom: 0,
+            mouse_buttons: vec![],
+            pressed_cell: None,
+            keymap: Keymap::default(),
+            key_sequence: KeySequence::default(),
+            show_help: false,
+            renderer: Renderer::new(),
+            status_message: None,
+            broadcaster: None,
+            events: None,
+            autoplay: None,
+            autoplay_speed: 2,
+        }
+    }
+
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+        self.key_sequence.clear();
+    }
+
+    pub fn set_broadcaster(&mut self, broadcaster: Broadcaster) {
+        self.events = self.backend.subscribe();
+        self.broadcaster = Some(broadcaster);
+    }
+
+    fn broadcast(&mut self) {
+        //! Streams the changes of the game since last time, then the board if
+        //! it changed
+        let (broadcaster, events) = match (&mut self.broadcaster, &self.events) {
+            (Some(broadcaster), Some(events)) => (broadcaster, events),
+            _ => return,
+        };
+        for event in events.try_iter() {
+            broadcaster.send_event(&event);
+        }
+        broadcaster.send_state(
+            self.backend.game_state(),
+            self.backend.num_bombs(),
+            self.backend.lives_left(),
+            self.backend.state(),
+        );
+    }
+
+    fn draw_box(
+        screen: &mut Screen,
+        box_chars: &[char; 10],
+        origin_x: u16,
+        origin_y: u16,
+        height: u16,
+        width: u16,
+    ) {
+        assert!(height >= 2 && width >= 2);
+        let h_line_width: usize = (width - 2).into();
+        let u_line = format!(
+            "{ul_corner}{line}{ur_corner}",
+            ul_corner = box_chars[6],
+            ur_corner = box_chars[7],
+            line = box_chars[4].to_string().repeat(h_line_width)
+        );
+        screen.put_str(origin_x, origin_y, &u_line, "");
+        for x in 1..(height - 1) {
+            screen.put(origin_x + x, origin_y, box_chars[5], "");
+            screen.put(origin_x + x, origin_y + width - 1, box_chars[5], "");
+        }
+        let l_line = format!(
+            "{ll_corner}{line}{lr_corner}",
+            ll_corner = box_chars[8],
+            lr_corner = box_chars[9],
+            line = box_chars[4].to_string().repeat(h_line_width)
+        );
+        screen.put_str(origin_x + height - 1, origin_y, &l_line, "");
+    }
+
+    fn frame(&self) -> Screen {
+        //! Draws the whole interface in memory
+        let mut screen = Screen::new();
+        let (grid_h, grid_w) = self.backend.state().layer_shape();
+        let num_flags = self.backend.num_flags();
+        let num_bombs = self.backend.num_bombs();
+        // write header
+        let header_style = format!("{}{}", color::Bg(color::White), color::Fg(color::Black));
+        screen.put_str(1, 1, " *  MINESWEEPER ¶ ", &header_style);
+        let mut header_col = 20;
+        if let Some(variant) = self.backend.variant() {
+            let variant = format!("[{}]", variant);
+            screen.put_str(1, header_col, &variant, "");
+            header_col += variant.chars().count() as u16 + 1;
+        }
+        if let Some(title) = self.backend.title() {
+            screen.put_str(1, header_col, &title, "");
+        }
+        let helper_message = match self.keymap.keys_for(KeyCommand::Submit) {
+            Some(keys) if num_bombs == num_flags => format!("(Submit with '{}')", keys),
+            _ => String::new(),
+        };
+        let lives = match self.backend.lives() {
+            1 => String::new(),
+            lives => format!("Lives: {}/{} ", self.backend.lives_left(), lives),
+        };
+        let flag_status = format!(
+            "{}Flagged bombs: {}/{} {}",
+            lives, num_flags, num_bombs, helper_message
+        );
+        screen.put_str(2, 1, &flag_status, "");
+        let now = Instant::now();
+        if let Some(remaining) = self.backend.remaining(now) {
+            // rounded up, so that the game ends on 0
+            let secs = (remaining.as_millis() as u64).div_ceil(1000);
+            let boards = match self.backend.mode() {
+                Mode::TimeAttack(_) => format!("  Boards cleared: {}", self.backend.boards_won()),
+                _ => String::new(),
+            };
+            screen.put_str(3, 1, &format!("Time left: {}s{}", secs, boards), "");
+        } else if let Some(start_time) = self.backend.start_time() {
+            let duration = now - start_time;
+            screen.put_str(3, 1, &format!("Time: {}s", duration.as_secs()), "");
+        } else if let Some(keys) = self.keymap.keys_for(KeyCommand::Help) {
+            screen.put_str(3, 1, &format!("Press '{}' for help", keys), "");
+        }
+        if self.backend.state().num_layers() > 1 {
+            self.draw_layer_hints(&mut screen, 4);
+        }
+        // write grid borders
+        let state = self.backend.state();
+        let (cell_h, cell_w) = self.cell_size();
+        let box_h: u16 = (grid_h as u16) * cell_h + 2;
+        let max_shift = if grid_h > 1 { self.row_shift(1) } else { 0 };
+        let box_w: u16 = (grid_w as u16) * cell_w + 2 + max_shift;
+        let box_chars = if state.wraps() {
+            &WRAP_BOX_CHARS
+        } else {
+            &BOX_CHARS
+        };
+        Self::draw_box(
+            &mut screen,
+            box_chars,
+            GRID_OFFSET.0,
+            GRID_OFFSET.1,
+            box_h,
+            box_w,
+        );
+        // write grid
+        let previewed_cells = self.previewed_cells();
+        let pending_cells = self.backend.pending();
+        let teammates = self.backend.teammates();
+        let announced = match self.autoplay.as_ref().and_then(|a| a.next.as_ref()) {
+            Some(Step {
+                action: Action::Dig(coords) | Action::Flag(coords) | Action::Chord(coords),
+                ..
+            }) => Some(coords.clone()),
+            _ => None,
+        };
+        let layer_cells = (0..grid_h).flat_map(|i| (0..grid_w).map(move |j| (i, j)));
+        for (i, j) in layer_cells {
+            let coords = self.cell_coords((i, j));
+            if !state.exists(&coords) {
+                // holes are left blank
+                continue;
+            }
+            let mut cell_state = state.get(&coords).unwrap();
+            let pending = pending_cells.contains(&coords);
+            if cell_state == CellState::Hidden && (pending || previewed_cells.contains(&(i, j))) {
+                // pressed cells look like empty ones until the button is released,
+                // and dug cells until the server answers
+                cell_state = CellState::Revealed(Cell::Clean(0));
+            }
+            let (mut style, symbol) = cellstate_style(&cell_state);
+            let looking = teammates
+                .iter()
+                .position(|t| !t.me && t.cursor.as_ref() == Some(&coords));
+            if let Some(player) = looking {
+                // cursors of the other players
+                style = player_style(player);
+            }
+            if announced.as_ref() == Some(&coords) {
+                style = autoplay_style();
+            }
+            let first_row = GRID_OFFSET.0 + 1 + (i as u16) * cell_h;
+            let first_col = GRID_OFFSET.1 + 1 + self.row_shift(i) + (j as u16) * cell_w;
+            for line in 0..cell_h {
+                for k in 0..cell_w {
+                    screen.put(first_row + line, first_col + k, ' ', &style);
+                }
+            }
+            let (symbol_row, symbol_col) = self.to_screen_coordinates(i, j);
+            screen.put(symbol_row, symbol_col, symbol, &style);
+        }
+        if let Some(message) = self
+            .status_message
+            .clone()
+            .or_else(|| {
+                let autoplay = self.autoplay.as_ref()?;
+                Some(self.autoplay_status(&autoplay.message))
+            })
+            .or_else(|| self.backend.status())
+        {
+            screen.put_str(GRID_OFFSET.0 + box_h + 1, 1, &message, "");
+        }
+        if self.show_help {
+            self.draw_help(&mut screen, GRID_OFFSET.1 + box_w + 2);
+        } else if let Some(standings) = self.backend.standings() {
+            self.draw_race(&mut screen, standings, GRID_OFFSET.1 + box_w + 2);
+        } else if !teammates.is_empty() {
+            self.draw_players(&mut screen, &teammates, GRID_OFFSET.1 + box_w + 2);
+        }
+        // put cursor to right position
+        let cursor_pos = self.to_screen_coordinates(self.current_cursor.0, self.current_cursor.1);
+        screen.set_cursor(cursor_pos.0, cursor_pos.1);
+        screen
+    }
+
+    fn draw<T>(&mut self, stdout: &mut T) -> Result<(), std::io::Error>
+    where
+        T: std::io::Write,
+    {
+        let frame = self.frame();
+        self.renderer.render(frame, stdout)
+    }
+
+    fn draw_help(&self, screen: &mut Screen, origin_col: u16) {
+        //! Lists the active key bindings in a box
+        let lines: Vec<String> = self
+            .keymap
+            .describe()
+            .iter()
+            .map(|(keys, description)| format!("{:>12}  {}", keys, description))
+            .collect();
+        let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as u16;
+        Self::draw_box(
+            screen,
+            &BOX_CHARS,
+            GRID_OFFSET.0,
+            origin_col,
+            lines.len() as u16 + 2,
+            width + 4,
+        );
+        for (i, line) in lines.iter().enumerate() {
+            screen.put_str(GRID_OFFSET.0 + 1 + i as u16, origin_col + 2, line, "");
+        }
+    }
+
+    fn draw_players(&self, screen: &mut Screen, teammates: &[Teammate], origin_col: u16) {
+        //! Lists the players of a shared game, with the number of cells each
+        //! one changed last, and who changed the cell under the cursor
+        let mut row = GRID_OFFSET.0;
+        screen.put_str(row, origin_col, "Players", "");
+        for (player, teammate) in teammates.iter().enumerate() {
+            row += 1;
+            screen.put(row, origin_col, ' ', &player_style(player));
+            let you = if teammate.me { " (you)" } else { "" };
+            let line = format!("{}{}: {} cells", teammate.name, you, teammate.cells);
+            screen.put_str(row, origin_col + 2, &line, "");
+        }
+        if let Some(owner) = self.backend.owner(&self.cell_coords(self.current_cursor)) {
+            let line = format!("Cell changed by {}", owner);
+            screen.put_str(row + 2, origin_col, &line, "");
+        }
+    }
+
+    fn draw_race(&self, screen: &mut Screen, standings: &Standings, origin_col: u16) {
+        //! Shows how the race goes, with the progress and strikes of every
+        //! racer
+        let stage = match standings.state {
+            RaceState::Waiting => String::from("waiting for racers"),
+            RaceState::Starting(left) => {
+                format!("starts in {}s", (left.as_millis() as u64).div_ceil(1000))
+            }
+            RaceState::Racing => String::from("go!"),
+            RaceState::Won(_) => format!("{} won", standings.winner().unwrap_or_default()),
+            RaceState::Draw => String::from("nobody won"),
+        };
+        let mut row = GRID_OFFSET.0;
+        screen.put_str(row, origin_col, &format!("Race: {}", stage), "");
+        for (racer, progress) in standings.racers.iter().enumerate() {
+            let you = if self.backend.racer() == Some(racer) {
+                " (you)"
+            } else {
+                ""
+            };
+            let lost = match progress.game_state {
+                GameState::Lost => " (lost)",
+                _ => "",
+            };
+            screen.put_str(
+                row + 1,
+                origin_col,
+                &format!("{}{}{}", progress.name, you, lost),
+                "",
+            );
+            let filled = progress.progress.min(100) / 10;
+            let bar = format!(
+                "[{}{}] {:>3}% Strikes: {}",
+                "#".repeat(filled),
+                " ".repeat(10 - filled),
+                progress.progress,
+                progress.strikes
+            );
+            screen.put_str(row + 2, origin_col + 2, &bar, "");
+            row += 2;
+        }
+    }
+
+    fn draw_layer_hints(&self, screen: &mut Screen, row: u16) {
+        //! Shows the layer number, and the cells above and below the cursor
+        let state = self.backend.state();
+        let text = format!("Layer {}/{}", self.layer + 1, state.num_layers());
+        screen.put_str(row, 1, &text, "");
+        let mut col = text.chars().count() as u16 + 3;
+        for (label, layer) in &[
+            ("above: ", self.layer.checked_sub(1)),
+            ("below: ", Some(self.layer + 1)),
+        ] {
+            let (cursor_row, cursor_col) = self.current_cursor;
+            let cell = layer
+                .and_then(|layer| state.layer_coords(layer, cursor_row, cursor_col))
+                .filter(|coords| state.exists(coords))
+                .and_then(|coords| state.get(&coords));
+            screen.put_str(row, col, label, "");
+            col += label.chars().count() as u16;
+            match cell {
+                Some(cell_state) => {
+                    let (style, symbol) = cellstate_style(&cell_state);
+                    screen.put(row, col, symbol, &style);
+                }
+                None => screen.put(row, col, '-', ""),
+            }
+            col += 3;
+        }
+    }
+
+    fn parse_event(&mut self, c: Event) -> TuiAction {
+        let (grid_h, grid_w) = self.backend.state().layer_shape();
+        let mut target_cursor = Some(self.current_cursor);
+        let action = match c {
+            Event::Key(key) => match self.key_sequence.push(&self.keymap, key) {
+                KeyInput::Command(KeyCommand::LayerAbove, count) => {
+                    self.move_layer(-(count as isize));
+                    TuiAction::None
+                }
+                KeyInput::Command(KeyCommand::LayerBelow, count) => {
+                    self.move_layer(count as isize);
+                    TuiAction::None
+                }
+                KeyInput::Command(command, count) if command.is_movement() => {
+                    target_cursor = Some(self.move_cursor(command, count));
+                    TuiAction::None
+                }
+                KeyInput::Command(command, _) => self.key_command_action(command),
+                KeyInput::Pending | KeyInput::Unbound => TuiAction::None,
+            },
+
+            Event::Mouse(mouse_event) => {
+                let (action, mouse_cursor) = self.parse_mouse_event(mouse_event);
+                target_cursor = mouse_cursor;
+                action
+            }
+
+            _ => TuiAction::None,
+        };
+        let playing = matches!(
+            action,
+            TuiAction::Query | TuiAction::Flag | TuiAction::Chord | TuiAction::Submit
+        );
+        if playing {
+            // playing takes back control from the solver
+            self.autoplay = None;
+        }
+
+        // check validity of cursor, update state
+        if let Some((target_row, target_col)) = target_cursor {
+            if target_row < grid_h && target_col < grid_w {
+                self.current_cursor = (target_row, target_col);
+            }
+        }
+        action
+    }
+
+    fn key_command_action(&mut self, command: KeyCommand) -> TuiAction {
+        match command {
+            KeyCommand::Dig => TuiAction::Query,
+            KeyCommand::Flag => TuiAction::Flag,
+            KeyCommand::Chord => TuiAction::Chord,
+            KeyCommand::Submit => TuiAction::Submit,
+            KeyCommand::Zoom => TuiAction::Zoom,
+            KeyCommand::Exit => TuiAction::Exit,
+            KeyCommand::Suspend => TuiAction::Suspend,
+            KeyCommand::Help => {
+                self.show_help = !self.show_help;
+                TuiAction::None
+            }
+            KeyCommand::Autoplay if self.autoplay.is_some() => {
+                self.autoplay = None;
+                TuiAction::None
+            }
+            KeyCommand::Autoplay => {
+                self.autoplay = Some(Autoplay {
+                    solver: Solver::new(rand::random()),
+                    next: None,
+                    due: Instant::now(),
+                    message: String::from("thinking..."),
+                });
+                TuiAction::None
+            }
+            KeyCommand::Faster | KeyCommand::Slower => {
+                self.autoplay_speed = match command {
+                    KeyCommand::Faster => (self.autoplay_speed + 1).min(AUTOPLAY_DELAYS.len() - 1),
+                    _ => self.autoplay_speed.saturating_sub(1),
+                };
+                if let Some(autoplay) = &mut self.autoplay {
+                    let due = Instant::now() + AUTOPLAY_DELAYS[self.autoplay_speed];
+                    autoplay.due = autoplay.due.min(due);
+                }
+                TuiAction::None
+            }
+            _ => TuiAction::None,
+        }
+    }
+
+    fn move_cursor(&self, command: KeyCommand, count: usize) -> (usize, usize) {
+        //! Target of a cursor movement repeated `count` times, clamped to the grid
+        //!
+        //! On boards whose edges wrap around, the cursor goes across them instead.
+        let (grid_h, grid_w) = self.backend.state().layer_shape();
+        let (row, col) = self.current_cursor;
+        if self.backend.state().wraps() {
+            match command {
+                KeyCommand::Left => return (row, (col + grid_w - count % grid_w) % grid_w),
+                KeyCommand::Right => return (row, (col + count) % grid_w),
+                KeyCommand::Up => return ((row + grid_h - count % grid_h) % grid_h, col),
+                KeyCommand::Down => return ((row + count) % grid_h, col),
+                _ => (),
+            }
+        }
+        match command {
+            KeyCommand::Left => (row, col.saturating_sub(count)),
+            KeyCommand::Right => (row, (col + count).min(grid_w - 1)),
+            KeyCommand::Up => (row.saturating_sub(count), col),
+            KeyCommand::Down => ((row + count).min(grid_h - 1), col),
+            KeyCommand::LineStart => (row, 0),
+            KeyCommand::LineEnd => (row, grid_w - 1),
+            KeyCommand::Top => (0, col),
+            KeyCommand::Bottom => (grid_h - 1, col),
+            KeyCommand::NextHidden | KeyCommand::PreviousHidden => {
+                let forward = command == KeyCommand::NextHidden;
+                let mut cursor = self.current_cursor;
+                for _ in 0..count {
+                    cursor = self.find_hidden(cursor, forward).unwrap_or(cursor);
+                }
+                cursor
+            }
+            _ => self.current_cursor,
+        }
+    }
+
+    fn move_layer(&mut self, offset: isize) {
+        //! Shows another layer, clamped to the grid unless its edges wrap around
+        let num_layers = self.backend.state().num_layers() as isize;
+        let layer = self.layer as isize + offset;
+        self.layer = if self.backend.state().wraps() {
+            layer.rem_euclid(num_layers) as usize
+        } else {
+            layer.max(0).min(num_layers - 1) as usize
+        };
+    }
+
+    fn find_hidden(&self, from: (usize, usize), forward: bool) -> Option<(usize, usize)> {
+        //! Next hidden cell of the layer in reading order, wrapping around
+        let state = self.backend.state();
+        let (grid_h, grid_w) = state.layer_shape();
+        let len = grid_h * grid_w;
+        let from_ix = from.0 * grid_w + from.1;
+        (1..len)
+            .map(|offset| {
+                if forward {
+                    (from_ix + offset) % len
+                } else {
+                    (from_ix + len - offset) % len
+                }
+            })
+            .map(|ix| (ix / grid_w, ix % grid_w))
+            .find(|&cell| {
+                let coords = self.cell_coords(cell);
+                state.exists(&coords) && state.get(&coords) == Some(CellState::Hidden)
+            })
+    }
+
+    fn parse_mouse_event(&mut self, event: MouseEvent) -> (TuiAction, Option<(usize, usize)>) {
+        //! Mouse buttons act on release, like in the classic game
+        //!
+        //! - left: dig
+        //! - right: flag
+        //! - middle, or left and right together: chord
+        //!
+        //! While a button is held down, the cell below the mouse is previewed.
+        //! Releasing outside of the grid cancels the action.
+        //!
+        //! The wheel shows the layers above and below.
+        match event {
+            MouseEvent::Press(MouseButton::WheelUp, _, _) => {
+                self.move_layer(-1);
+                (TuiAction::None, None)
+            }
+            MouseEvent::Press(MouseButton::WheelDown, _, _) => {
+                self.move_layer(1);
+                (TuiAction::None, None)
+            }
+            MouseEvent::Press(button, x, y) => {
+                if !self.mouse_buttons.contains(&button) {
+                    self.mouse_buttons.push(button);
+                }
+                self.pressed_cell = self.to_grid_coordinates(y, x);
+                (TuiAction::None, self.pressed_cell)
+            }
+            MouseEvent::Hold(x, y) => {
+                if !self.mouse_buttons.is_empty() {
+                    self.pressed_cell = self.to_grid_coordinates(y, x);
+                }
+                (TuiAction::None, self.pressed_cell)
+            }
+            MouseEvent::Release(x, y) => {
+                let target = self.to_grid_coordinates(y, x);
+                let action = if target.is_none() {
+                    TuiAction::None
+                } else if self.mouse_chording() {
+                    TuiAction::Chord
+                } else if self.mouse_buttons.contains(&MouseButton::Left) {
+                    TuiAction::Query
+                } else if self.mouse_buttons.contains(&MouseButton::Right) {
+                    TuiAction::Flag
+                } else {
+                    TuiAction::None
+                };
+                // terminals report one release per button: the first one ends the click
+                self.mouse_buttons.clear();
+                self.pressed_cell = None;
+                (action, target)
+            }
+        }
+    }
+
+    fn mouse_chording(&self) -> bool {
+        let buttons = &self.mouse_buttons;
+        buttons.contains(&MouseButton::Middle)
+            || (buttons.contains(&MouseButton::Left) && buttons.contains(&MouseButton::Right))
+    }
+
+    fn previewed_cells(&self) -> Vec<(usize, usize)> {
+        //! Cells of the shown layer drawn as pressed while a mouse button is held down
+        let pressed_cell = match self.pressed_cell {
+            Some(cell) => cell,
+            None => return vec![],
+        };
+        let mut cells = vec![pressed_cell];
+        if self.mouse_chording() {
+            let state = self.backend.state();
+            let n = state.shape().len();
+            let neighbours = state.neighbours(&self.cell_coords(pressed_cell));
+            cells.extend(
+                neighbours
+                    .iter()
+                    .filter(|coords| state.layer_of(coords) == self.layer)
+                    .map(|coords| (coords[n - 2], coords[n - 1])),
+            );
+        } else if !self.mouse_buttons.contains(&MouseButton::Left) {
+            // flagging does not press the cell
+            cells.clear();
+        }
+        cells
+    }
+
+    fn tick_length(&self) -> Duration {
+        //! Answers of remote games are looked for more often than the clocks,
+        //! and the solver plays on time
+        match &self.autoplay {
+            _ if self.backend.busy() => BUSY_TICK,
+            Some(autoplay) => autoplay
+                .due
+                .saturating_duration_since(Instant::now())
+                .min(TICK),
+            None => TICK,
+        }
+    }
+
+    fn autoplay_status(&self, message: &str) -> String {
+        //! Speed of the solver, how to stop it and its last move
+        let stop = match self.keymap.keys_for(KeyCommand::Autoplay) {
+            Some(keys) => format!(", '{}' to take over", keys),
+            None => String::new(),
+        };
+        format!(
+            "[autoplay {}/{}{}] {}",
+            self.autoplay_speed + 1,
+            AUTOPLAY_DELAYS.len(),
+            stop,
+            message
+        )
+    }
+
+    fn show_cell(&mut self, coords: &[usize]) {
+        //! Moves the cursor to a cell, on its layer
+        let n = coords.len();
+        self.layer = self.backend.state().layer_of(coords);
+        self.current_cursor = (coords[n - 2], coords[n - 1]);
+    }
+
+    fn autoplay_action(&mut self, now: Instant) -> TuiAction {
+        //! Next step of the solver, once it is due: announces a move, or
+        //! plays the move it announced at the cursor
+        let mut autoplay = match self.autoplay.take() {
+            Some(autoplay) => autoplay,
+            None => return TuiAction::None,
+        };
+        if now < autoplay.due || self.backend.busy() {
+            self.autoplay = Some(autoplay);
+            return TuiAction::None;
+        }
+        autoplay.due = now + AUTOPLAY_DELAYS[self.autoplay_speed];
+        let action = match autoplay.next.take() {
+            Some(step) => match step.action {
+                Action::Dig(coords) => {
+                    self.show_cell(&coords);
+                    TuiAction::Query
+                }
+                Action::Flag(coords) => {
+                    self.show_cell(&coords);
+                    TuiAction::Flag
+                }
+                Action::Chord(coords) => {
+                    self.show_cell(&coords);
+                    TuiAction::Chord
+                }
+                Action::Submit => TuiAction::Submit,
+            },
+            None => {
+                let step = autoplay
+                    .solver
+                    .step(self.backend.state(), self.backend.num_bombs());
+                let announce = match &step.action {
+                    Action::Dig(coords) => format!("dig {}", bot::cell_name(coords)),
+                    Action::Flag(coords) => format!("flag {}", bot::cell_name(coords)),
+                    Action::Chord(coords) => format!("chord {}", bot::cell_name(coords)),
+                    Action::Submit => String::from("submit"),
+                };
+                if let Action::Dig(coords) | Action::Flag(coords) | Action::Chord(coords) =
+                    &step.action
+                {
+                    self.show_cell(coords);
+                }
+                autoplay.message = format!("{}: {}", announce, step.reason);
+                autoplay.next = Some(step);
+                TuiAction::None
+            }
+        };
+        self.autoplay = Some(autoplay);
+        action
+    }
+
+    fn cycle_zoom(&mut self) {
+        self.zoom = (self.zoom + 1) % ZOOM_LEVELS.len();
+    }
+    pub fn mainloop(&mut self) {
+        let mut terminal = TerminalGuard::new().unwrap();
+        let inputs = Inputs::spawn().unwrap();
+        let mut stdout = std::io::stdout();
+        let mut request_exit = false;
+        let mut score = String::new();
+
+        self.renderer.invalidate();
+        self.draw(&mut stdout).unwrap();
+        while let Some(input) = inputs.next_timeout(self.tick_length()) {
+            let action = match input {
+                Input::Event(event) => self.parse_event(event),
+                Input::Terminate => TuiAction::Exit,
+                Input::Suspend => TuiAction::Suspend,
+                Input::Redraw => {
+                    self.renderer.invalidate();
+                    TuiAction::None
+                }
+                Input::Tick => TuiAction::None,
+            };
+            let action = match action {
+                TuiAction::None => self.autoplay_action(Instant::now()),
+                action => action,
+            };
+            match action {
+                TuiAction::Flag => {
+                    self.backend.flag(&self.cell_coords(self.current_cursor));
+                }
+                TuiAction::Query => {
+                    self.backend.dig(&self.cell_coords(self.current_cursor));
+                }
+                TuiAction::Chord => {
+                    self.backend.chord(&self.cell_coords(self.current_cursor));
+                }
+                TuiAction::Zoom => self.cycle_zoom(),
+                TuiAction::Exit => {
+                    request_exit = true;
+                }
+                TuiAction::Submit => {
+                    self.backend.submit();
+                }
+                TuiAction::Suspend => {
+                    terminal.suspend().unwrap();
+                    self.renderer.invalidate();
+                }
+                TuiAction::None => (),
+            }
+            self.backend
+                .set_cursor(&self.cell_coords(self.current_cursor));
+            let game_state = self.backend.tick(Instant::now());
+            self.broadcast();
+            if game_state != GameState::Running || request_exit {
+                score = match self.backend.mode() {
+                    Mode::TimeAttack(_) => format!("Boards cleared: {}", self.backend.boards_won()),
+                    _ => match self.backend.score() {
+                        Some(score) => format!("Score: {}", score),
+                        None => String::new(),
+                    },
+                };
+                self.backend.reveal();
+                self.broadcast();
+                if let Some(winner) = self.backend.standings().and_then(Standings::winner) {
+                    score = format!("{} won the race", winner);
+                }
+                break;
+            }
+            self.draw(&mut stdout).unwrap();
+        }
+        let goodbye_sentence = match self.backend.game_state() {
+            GameState::Lost if request_exit => "No time anymore ? See you soon !",
+            GameState::Lost => "Another time !",
+            GameState::Won => "Congratulations ! You're the best! ",
+            _ => "How do you do that ????",
+        };
+        if !request_exit {
+            // leave time to look at the board before leaving the alternate screen
+            self.status_message = Some(format!(
+                "{} {} (press any key to exit)",
+                goodbye_sentence, score
+            ));
+            self.draw(&mut stdout).unwrap();
+            while let Some(input) = inputs.next() {
+                match input {
+                    Input::Event(Event::Key(_)) | Input::Terminate => break,
+                    Input::Suspend => terminal.suspend().unwrap(),
+                    _ => (),
+                }
+                self.renderer.invalidate();
+                self.draw(&mut stdout).unwrap();
+            }
+        }
+        drop(inputs);
+        drop(terminal);
+        println!("{}", goodbye_sentence);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use minefield::field::test::generate_test_minefield;
+    use termion::event::Key;
+
+    #[test]
+    fn coordinates() {
+        let (field, _bomb_locations) = generate_test_minefield();
+        let (h, w) = (field.shape()[0], field.shape()[1]);
+        let client = minefield::client::Client::from_minefield(field);
+        let mut t_client = TuiClient::new(client);
+
+        // basic conversion checks
+        assert_eq!(
+            t_client.to_grid_coordinates(GRID_OFFSET.0 + 1, GRID_OFFSET.1 + 1),
+            Some((0, 0))
+        );
+        assert_eq!(
+            t_client.to_grid_coordinates(GRID_OFFSET.0 + (h as u16), GRID_OFFSET.1 + (w as u16)),
+            Some((2, 4))
+        );
+        assert_eq!(
+            t_client
+                .to_grid_coordinates(GRID_OFFSET.0 + 1 + (h as u16), GRID_OFFSET.1 + (w as u16)),
+            None
+        );
+        assert_eq!(
+            t_client
+                .to_grid_coordinates(GRID_OFFSET.0 + (h as u16), GRID_OFFSET.1 + 1 + (w as u16)),
+            None
+        );
+        assert_eq!(
+            t_client.to_grid_coordinates(GRID_OFFSET.0, GRID_OFFSET.1),
+            None
+        );
+
+        // Checks that the event parsing method does not allow
+        // the cursor to leave the grid
+
+        // Upper left corner
+        let upp_left_cursor = t_client.current_cursor;
+        assert_eq!(upp_left_cursor, (0, 0));
+        assert_eq!(t_client.parse_event(Event::Key(Key::Left)), TuiAction::None);
+        assert_eq!(t_client.current_cursor, upp_left_cursor); // cannot go left
+
+        assert_eq!(t_client.parse_event(Event::Key(Key::Up)), TuiAction::None);
+        assert_eq!(t_client.current_cursor, upp_left_cursor); // cannot go up
+
+        assert_eq!(t_client.parse_event(Event::Key(Key::Down)), TuiAction::None);
+        assert_eq!(t_client.current_cursor, (1, 0)); // down is allowed
+
+        // Bottom right corner
+        let bott_right_screen = (GRID_OFFSET.1 + (w as u16), GRID_OFFSET.0 + (h as u16));
+        assert_eq!(
+            t_client.parse_event(Event::Mouse(MouseEvent::Release(
+                bott_right_screen.0,
+                bott_right_screen.1
+            ))),
+            TuiAction::None
+        ); // goto corner
+        let bott_right_cursor = (h - 1, w - 1);
+        assert_eq!(t_client.current_cursor, bott_right_cursor);
+        assert_eq!(
+            t_client.parse_event(Event::Key(Key::Right)),
+            TuiAction::None
+        );
+        assert_eq!(t_client.current_cursor, bott_right_cursor); // cannot go right
+        assert_eq!(t_client.parse_event(Event::Key(Key::Down)), TuiAction::None);
+        assert_eq!(t_client.current_cursor, bott_right_cursor); // cannot go down
+        assert_eq!(t_client.parse_event(Event::Key(Key::Left)), TuiAction::None);
+        assert_eq!(t_client.current_cursor, (h - 1, w - 2)); // left is allowed
+
+        // Clicking outside of the grid does not move the cursor
+        t_client.parse_event(Event::Mouse(MouseEvent::Release(1, 1)));
+        assert_eq!(t_client.current_cursor, (h - 1, w - 2));
+    }
+
+    #[test]
+    fn zoom() {
+        let (field, _bomb_locations) = generate_test_minefield();
+        let client = minefield::client::Client::from_minefield(field);
+        let mut t_client = TuiClient::new(client);
+
+        assert_eq!(t_client.parse_event(Event::Key(Key::Char('z'))), TuiAction::Zoom);
+        t_client.cycle_zoom();
+        t_client.cycle_zoom();
+        t_client.cycle_zoom();
+        // (2, 3) cells: cell (1, 2) spans rows +2..+3 and columns +6..+8
+        assert_eq!(t_client.cell_size(), (2, 3));
+        let (row0, col0) = (GRID_OFFSET.0 + 1, GRID_OFFSET.1 + 1);
+        assert_eq!(t_client.to_grid_coordinates(row0 + 2, col0 + 6), Some((1, 2)));
+        assert_eq!(t_client.to_grid_coordinates(row0 + 3, col0 + 8), Some((1, 2)));
+        assert_eq!(t_client.to_grid_coordinates(row0 + 4, col0 + 9), Some((2, 3)));
+        assert_eq!(t_client.to_grid_coordinates(row0 + 6, col0), None);
+        assert_eq!(t_client.to_screen_coordinates(1, 2), (row0 + 2, col0 + 7));
+
+        // mouse clicks are mapped with the current zoom level
+        t_client.parse_event(Event::Mouse(MouseEvent::Release(col0 + 14, row0 + 5)));
+        assert_eq!(t_client.current_cursor, (2, 4));
+
+        // zoom cycles back to single character cells
+        t_client.cycle_zoom();
+        assert_eq!(t_client.cell_size(), (1, 1));
+        assert_eq!(t_client.to_screen_coordinates(2, 4), (row0 + 2, col0 + 4));
+    }
+
+    fn type_keys<B: Backend>(t_client: &mut TuiClient<B>, keys: &str) -> TuiAction {
+        keymap::parse_key_sequence(keys)
+            .unwrap()
+            .into_iter()
+            .map(|k| t_client.parse_event(Event::Key(k)))
+            .last()
+            .unwrap()
+    }
+
+    #[test]
+    fn keymap() {
+        // X22X2
+        // 2X33X
+        // 12X21
+        let (field, _bomb_locations) = generate_test_minefield();
+        let client = minefield::client::Client::from_minefield(field);
+        let mut t_client = TuiClient::new(client);
+        type_keys(&mut t_client, "3l");
+        type_keys(&mut t_client, "j");
+        assert_eq!(t_client.current_cursor, (1, 3));
+        type_keys(&mut t_client, "9l");
+        assert_eq!(t_client.current_cursor, (1, 4)); // counts stop at the border
+        type_keys(&mut t_client, "0");
+        assert_eq!(t_client.current_cursor, (1, 0));
+        type_keys(&mut t_client, "$G");
+        assert_eq!(t_client.current_cursor, (2, 4));
+        type_keys(&mut t_client, "gg");
+        assert_eq!(t_client.current_cursor, (0, 4));
+        assert_eq!(type_keys(&mut t_client, "d"), TuiAction::Query);
+        assert_eq!(type_keys(&mut t_client, "?"), TuiAction::None);
+        assert!(t_client.show_help);
+
+        // jump to the next hidden cell, wrapping around the grid
+        t_client.backend.client.query_update(&[0, 0]);
+        t_client.backend.client.query_update(&[0, 1]);
+        type_keys(&mut t_client, "0n");
+        assert_eq!(t_client.current_cursor, (0, 2));
+        type_keys(&mut t_client, "2N");
+        assert_eq!(t_client.current_cursor, (2, 3));
+
+        // remapped keys
+        t_client.set_keymap(Keymap::wasd());
+        assert_eq!(type_keys(&mut t_client, "w"), TuiAction::None);
+        assert_eq!(type_keys(&mut t_client, "<Enter>"), TuiAction::Submit);
+        assert_eq!(type_keys(&mut t_client, "d"), TuiAction::None);
+        assert_eq!(t_client.current_cursor, (1, 4));
+    }
+
+    #[test]
+    fn autoplay() {
+        // X22X2
+        // 2X33X
+        // 12X21
+        let (field, _bomb_locations) = generate_test_minefield();
+        let client = minefield::client::Client::from_minefield(field);
+        let mut t_client = TuiClient::new(client);
+        t_client.backend.client.query_update(&[2, 0]);
+        t_client.backend.client.query_update(&[1, 0]);
+        t_client.backend.client.query_update(&[2, 1]);
+        assert_eq!(t_client.autoplay_action(Instant::now()), TuiAction::None);
+        assert_eq!(type_keys(&mut t_client, "p"), TuiAction::None);
+        let now = Instant::now();
+        // the move is announced first, with the deduction
+        assert_eq!(t_client.autoplay_action(now), TuiAction::None);
+        assert_eq!(t_client.current_cursor, (1, 1));
+        let frame = t_client.frame();
+        assert_eq!(
+            frame.line(GRID_OFFSET.0 + 6),
+            "[autoplay 3/5, 'p' to take over] flag b1: a2 shows 1 and has 1 hidden neighbour left"
+        );
+        let (row, col) = t_client.to_screen_coordinates(1, 1);
+        assert_eq!(frame.get(row, col).unwrap().style, autoplay_style());
+        // then played once due, faster after '+'
+        type_keys(&mut t_client, "+");
+        assert_eq!(t_client.autoplay_action(now), TuiAction::None);
+        let due = Instant::now() + AUTOPLAY_DELAYS[3];
+        assert_eq!(t_client.autoplay_action(due), TuiAction::Flag);
+        assert_eq!(t_client.current_cursor, (1, 1));
+        // playing takes back control
+        type_keys(&mut t_client, "gg");
+        assert_eq!(type_keys(&mut t_client, "d"), TuiAction::Query);
+        assert!(t_client.autoplay.is_none());
+        assert_eq!(t_client.autoplay_action(due + TICK), TuiAction::None);
+        type_keys(&mut t_client, "p");
+        type_keys(&mut t_client, "p");
+        assert!(t_client.autoplay.is_none());
+    }
+
+    #[test]
+    fn mouse() {
+        let (field, _bomb_locations) = generate_test_minefield();
+        let client = minefield::client::Client::from_minefield(field);
+        let mut t_client = TuiClient::new(client);
+        let (row0, col0) = (GRID_OFFSET.0 + 1, GRID_OFFSET.1 + 1);
+        let press = |button, row: u16, col: u16| {
+            Event::Mouse(MouseEvent::Press(button, col0 + col, row0 + row))
+        };
+        let release =
+            |row: u16, col: u16| Event::Mouse(MouseEvent::Release(col0 + col, row0 + row));
+
+        // left click digs, the pressed cell is previewed until release
+        assert_eq!(
+            t_client.parse_event(press(MouseButton::Left, 2, 0)),
+            TuiAction::None
+        );
+        assert_eq!(t_client.previewed_cells(), vec![(2, 0)]);
+        let hold = Event::Mouse(MouseEvent::Hold(col0 + 1, row0 + 2));
+        assert_eq!(t_client.parse_event(hold), TuiAction::None);
+        assert_eq!(t_client.previewed_cells(), vec![(2, 1)]);
+        assert_eq!(t_client.parse_event(release(2, 0)), TuiAction::Query);
+        assert_eq!(t_client.current_cursor, (2, 0));
+        assert_eq!(t_client.previewed_cells(), vec![]);
+
+        // right click flags, without preview
+        assert_eq!(
+            t_client.parse_event(press(MouseButton::Right, 1, 1)),
+            TuiAction::None
+        );
+        assert_eq!(t_client.previewed_cells(), vec![]);
+        assert_eq!(t_client.parse_event(release(1, 1)), TuiAction::Flag);
+        assert_eq!(t_client.current_cursor, (1, 1));
+
+        // middle click chords and previews the neighbourhood
+        t_client.parse_event(press(MouseButton::Middle, 0, 0));
+        assert_eq!(
+            t_client.previewed_cells(),
+            vec![(0, 0), (0, 1), (1, 0), (1, 1)]
+        );
+        assert_eq!(t_client.parse_event(release(0, 0)), TuiAction::Chord);
+
+        // both buttons chord; the second release is a plain cursor move
+        t_client.parse_event(press(MouseButton::Left, 2, 0));
+        t_client.parse_event(press(MouseButton::Right, 2, 0));
+        assert_eq!(t_client.parse_event(release(2, 0)), TuiAction::Chord);
+        assert_eq!(t_client.parse_event(release(2, 0)), TuiAction::None);
+
+        // releasing outside of the grid cancels the click
+        t_client.parse_event(press(MouseButton::Left, 2, 0));
+        let outside = Event::Mouse(MouseEvent::Release(1, 1));
+        assert_eq!(t_client.parse_event(outside), TuiAction::None);
+        assert_eq!(t_client.current_cursor, (2, 0));
+
+        // wheel is ignored
+        assert_eq!(
+            t_client.parse_event(press(MouseButton::WheelUp, 1, 1)),
+            TuiAction::None
+        );
+        assert_eq!(t_client.previewed_cells(), vec![]);
+    }
+
+    #[test]
+    fn frame() {
+        let (field, _bomb_locations) = generate_test_minefield();
+        let client = minefield::client::Client::from_minefield(field);
+        let mut t_client = TuiClient::new(client);
+        t_client.backend.client.flag(&[0, 0]);
+        t_client.backend.client.query_update(&[1, 0]);
+        t_client.backend.client.query_update(&[0, 2]);
+
+        let frame = t_client.frame();
+        assert_eq!(frame.line(1), " *  MINESWEEPER ¶");
+        assert_eq!(frame.line(2), "Flagged bombs: 1/5");
+        assert_eq!(frame.line(GRID_OFFSET.0), "  ┏━━━━━┓");
+        assert_eq!(frame.line(GRID_OFFSET.0 + 1), "  ┃¶ 2  ┃");
+        assert_eq!(frame.line(GRID_OFFSET.0 + 2), "  ┃2    ┃");
+        assert_eq!(frame.line(GRID_OFFSET.0 + 4), "  ┗━━━━━┛");
+        assert_eq!(frame.cursor(), Some((GRID_OFFSET.0 + 1, GRID_OFFSET.1 + 1)));
+
+        t_client.cycle_zoom();
+        let frame = t_client.frame();
+        assert_eq!(frame.line(GRID_OFFSET.0 + 1), "  ┃¶   2     ┃");
+
+        // only the changes are sent to the terminal after the first frame
+        let mut out: Vec<u8> = vec![];
+        t_client.draw(&mut out).unwrap();
+        let first_draw_len = out.len();
+        let mut out: Vec<u8> = vec![];
+        t_client.draw(&mut out).unwrap();
+        let same_draw_len = out.len();
+        t_client.backend.client.query_update(&[2, 4]);
+        let mut out: Vec<u8> = vec![];
+        t_client.draw(&mut out).unwrap();
+        assert!(same_draw_len < out.len());
+        assert!(out.len() < first_draw_len / 10);
+        assert!(String::from_utf8(out).unwrap().contains('1'));
+    }
+
+    #[test]
+    fn hex() {
+        let (field, _bomb_locations) = generate_test_minefield();
+        let layout = field.layout(()).with_topology(Topology::Hex);
+        let field = minefield::field::Minefield::from_layout(&layout, 0).unwrap();
+        let mut client = minefield::client::Client::from_minefield(field);
+        client.query_smart(&[0, 0]);
+        let mut t_client = TuiClient::new(client);
+
+        // cells are at least 2 columns wide, odd rows are shifted by one column
+        assert_eq!(t_client.cell_size(), (1, 2));
+        let (row0, col0) = (GRID_OFFSET.0 + 1, GRID_OFFSET.1 + 1);
+        assert_eq!(t_client.to_screen_coordinates(0, 1), (row0, col0 + 2));
+        assert_eq!(t_client.to_screen_coordinates(1, 1), (row0 + 1, col0 + 3));
+        assert_eq!(t_client.to_grid_coordinates(row0 + 1, col0), None);
+        assert_eq!(
+            t_client.to_grid_coordinates(row0 + 1, col0 + 1),
+            Some((1, 0))
+        );
+        assert_eq!(
+            t_client.to_grid_coordinates(row0 + 1, col0 + 10),
+            Some((1, 4))
+        );
+        assert_eq!(t_client.to_grid_coordinates(row0 + 1, col0 + 11), None);
+        assert_eq!(t_client.to_grid_coordinates(row0, col0 + 9), Some((0, 4)));
+
+        let frame = t_client.frame();
+        assert_eq!(frame.line(GRID_OFFSET.0), "  ┏━━━━━━━━━━━┓");
+        assert_eq!(frame.line(row0), "  ┃           ┃");
+        assert_eq!(frame.line(row0 + 2), "  ┃           ┃");
+        assert_eq!(frame.get(row0 + 1, col0).unwrap().symbol, ' ');
+        assert_eq!(frame.get(row0 + 1, col0 + 10).unwrap().symbol, ' ');
+        assert_eq!(frame.line(GRID_OFFSET.0 + 4), "  ┗━━━━━━━━━━━┛");
+
+        // the mouse previews the 6 neighbours
+        t_client.backend.client = minefield::client::Client::from_minefield(
+            minefield::field::Minefield::from_layout(&layout, 0).unwrap(),
+        );
+        t_client.parse_event(Event::Mouse(MouseEvent::Press(
+            MouseButton::Middle,
+            col0 + 3,
+            row0 + 1,
+        )));
+        assert_eq!(t_client.previewed_cells().len(), 7);
+    }
+
+    #[test]
+    fn wrap() {
+        let (field, _bomb_locations) = generate_test_minefield();
+        let layout = field.layout(()).with_wrap(true).unwrap();
+        let field = minefield::field::Minefield::from_layout(&layout, 0).unwrap();
+        let client = minefield::client::Client::from_minefield(field);
+        let mut t_client = TuiClient::new(client);
+
+        // the cursor goes across the edges
+        type_keys(&mut t_client, "h");
+        assert_eq!(t_client.current_cursor, (0, 4));
+        type_keys(&mut t_client, "l");
+        assert_eq!(t_client.current_cursor, (0, 0));
+        type_keys(&mut t_client, "k");
+        assert_eq!(t_client.current_cursor, (2, 0));
+        type_keys(&mut t_client, "7j");
+        assert_eq!(t_client.current_cursor, (0, 0));
+        type_keys(&mut t_client, "G$");
+        assert_eq!(t_client.current_cursor, (2, 4));
+
+        // dashed borders show the wrapping edges
+        let frame = t_client.frame();
+        assert_eq!(frame.line(GRID_OFFSET.0), "  ┏┅┅┅┅┅┓");
+        assert_eq!(frame.line(GRID_OFFSET.0 + 1), "  ┇     ┇");
+        assert_eq!(frame.line(GRID_OFFSET.0 + 4), "  ┗┅┅┅┅┅┛");
+    }
+
+    #[test]
+    fn layers() {
+        let layout = minefield::grid::Grid::with_dims(&[3, 2, 3], vec![(); 18]).unwrap();
+        let field = minefield::field::Minefield::from_layout(&layout, 0).unwrap();
+        let mut t_client = TuiClient::new(minefield::client::Client::from_minefield(field));
+
+        let frame = t_client.frame();
+        assert_eq!(frame.line(4), "Layer 1/3  above: -  below:");
+        type_keys(&mut t_client, "]");
+        assert_eq!(t_client.layer, 1);
+        type_keys(&mut t_client, "5]");
+        assert_eq!(t_client.layer, 2);
+        type_keys(&mut t_client, "<PageUp>");
+        assert_eq!(t_client.layer, 1);
+        t_client.parse_event(Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 1, 1)));
+        assert_eq!(t_client.layer, 0);
+
+        // actions apply to the shown layer
+        type_keys(&mut t_client, "]lf");
+        assert_eq!(t_client.current_cursor, (0, 1));
+        t_client
+            .backend
+            .client
+            .flag(&t_client.cell_coords(t_client.current_cursor));
+        assert_eq!(
+            t_client.backend.client.get_flag_locations(),
+            vec![vec![1, 0, 1]]
+        );
+        type_keys(&mut t_client, "[");
+        let frame = t_client.frame();
+        assert_eq!(frame.line(4), "Layer 1/3  above: -  below: ¶");
+        type_keys(&mut t_client, "n");
+        assert_eq!(t_client.current_cursor, (0, 2));
+
+        // nothing happens beyond the first and last layers
+        type_keys(&mut t_client, "[");
+        assert_eq!(t_client.layer, 0);
+        // digging floods every layer
+        t_client.backend.client.flag(&[1, 0, 1]);
+        t_client.backend.client.query_smart(&[0, 0, 0]);
+        type_keys(&mut t_client, "2]");
+        let frame = t_client.frame();
+        assert_eq!(frame.line(GRID_OFFSET.0 + 1), "  ┃   ┃");
+    }
+
+    #[test]
+    fn holes() {
+        // # . #
+        // # # #
+        let layout = minefield::grid::Grid::new(2, 3, vec![(); 6])
+            .unwrap()
+            .with_mask(&[true, false, true, true, true, true])
+            .unwrap();
+        let field = minefield::field::Minefield::from_layout(&layout, 0).unwrap();
+        let mut t_client = TuiClient::new(minefield::client::Client::from_minefield(field));
+        let frame = t_client.frame();
+        let (row0, col0) = (GRID_OFFSET.0 + 1, GRID_OFFSET.1 + 1);
+        assert_eq!(frame.get(row0, col0 + 1).unwrap().style, "");
+        assert_ne!(frame.get(row0, col0 + 2).unwrap().style, "");
+        // jumping to hidden cells skips the hole
+        type_keys(&mut t_client, "n");
+        assert_eq!(t_client.current_cursor, (0, 2));
+    }
+
+    #[test]
+    fn variants() {
+        let layout = minefield::grid::Grid::new(1, 3, vec![(); 3]).unwrap();
+        let field = minefield::field::Minefield::from_layout(&layout, 0)
+            .unwrap()
+            .with_anti_mines(&[vec![0, 0]])
+            .unwrap()
+            .with_liars();
+        let mut t_client = TuiClient::new(minefield::client::Client::from_minefield(field));
+        t_client.backend.client.reveal(true);
+        let frame = t_client.frame();
+        assert!(frame
+            .line(1)
+            .ends_with("[1 anti-mines, numbers off by one]"));
+        let (row0, col0) = (GRID_OFFSET.0 + 1, GRID_OFFSET.1 + 1);
+        assert_eq!(frame.get(row0, col0).unwrap().symbol, '⊖');
+        let (_, negative) = cellstate_style(&CellState::Revealed(Cell::Clean(-2)));
+        assert_eq!(negative, '⑵');
+    }
+
+    #[test]
+    fn lives() {
+        let (field, _bomb_locations) = generate_test_minefield();
+        let client = minefield::client::Client::from_minefield(field).with_lives(2);
+        let mut t_client = TuiClient::new(client);
+        t_client.backend.client.query_update(&[0, 0]);
+        let frame = t_client.frame();
+        assert_eq!(frame.line(2), "Lives: 1/2 Flagged bombs: 1/5");
+        let exploded = frame.get(GRID_OFFSET.0 + 1, GRID_OFFSET.1 + 1).unwrap();
+        assert_eq!(exploded.symbol, '*');
+        assert!(exploded.style.contains(&color::Bg(color::Red).to_string()));
+    }
+
+    #[test]
+    fn shared() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let registry = std::sync::Arc::new(crate::registry::Registry::new(None));
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let registry = registry.clone();
+                std::thread::spawn(move || crate::server::serve(stream.unwrap(), registry));
+            }
+        });
+        let args: Vec<String> = vec!["--height", "2", "--width", "3", "--bombs", "0"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let alice = remote::RemoteBackend::host(&address, &args, "alice").unwrap();
+        let mut bob = remote::RemoteBackend::join(&address, alice.id(), "bob").unwrap();
+        let mut t_client = TuiClient::from_backend(alice);
+        assert_eq!(type_keys(&mut t_client, "lf"), TuiAction::Flag);
+        t_client.backend.flag(&[0, 1]);
+        bob.set_cursor(&[1, 2]);
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while (bob.busy() || t_client.backend.teammates().len() < 2) && Instant::now() < deadline {
+            bob.tick(Instant::now());
+            t_client.backend.tick(Instant::now());
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        t_client.backend.set_cursor(&[0, 1]);
+        let frame = t_client.frame();
+        assert!(frame.line(1).ends_with("Game 1 as alice"));
+        let (row, col) = t_client.to_screen_coordinates(1, 2);
+        let bob_style = player_style(1);
+        assert_eq!(frame.get(row, col).unwrap().style, bob_style);
+        assert!(frame
+            .line(GRID_OFFSET.0 + 1)
+            .ends_with("alice (you): 1 cells"));
+        assert!(frame.line(GRID_OFFSET.0 + 2).ends_with("bob: 0 cells"));
+        assert!(frame
+            .line(GRID_OFFSET.0 + 4)
+            .ends_with("Cell changed by alice"));
+    }
+
+    #[test]
+    fn race() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let registry = std::sync::Arc::new(crate::registry::Registry::new(None));
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let registry = registry.clone();
+                std::thread::spawn(move || crate::server::serve(stream.unwrap(), registry));
+            }
+        });
+        let args: Vec<String> = vec!["--height", "2", "--width", "3", "--bombs", "0"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let alice = remote::RemoteBackend::race(&address, &args, "alice").unwrap();
+        let mut t_client = TuiClient::from_backend(alice);
+        let frame = t_client.frame();
+        assert!(frame.line(1).ends_with("Race 1 as alice"));
+        assert!(frame
+            .line(GRID_OFFSET.0)
+            .ends_with("Race: waiting for racers"));
+        assert!(frame.line(GRID_OFFSET.0 + 1).ends_with("alice (you)"));
+        assert!(frame
+            .line(GRID_OFFSET.0 + 2)
+            .ends_with("[          ]   0% Strikes: 0"));
+        // moves wait for the other racer
+        t_client.backend.dig(&[0, 0]);
+        assert!(!t_client.backend.busy());
+
+        let mut bob = remote::RemoteBackend::enter(&address, 1, "bob").unwrap();
+        assert!(bob.start_time().unwrap() > Instant::now());
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let racers = |t: &TuiClient<remote::RemoteBackend>| {
+            t.backend.standings().unwrap().racers[1].name.clone()
+        };
+        while racers(&t_client) != "bob" && Instant::now() < deadline {
+            t_client
+                .backend
+                .tick(Instant::now() + Duration::from_secs(1));
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        let frame = t_client.frame();
+        assert!(frame.line(GRID_OFFSET.0).ends_with("Race: starts in 3s"));
+        assert!(frame.line(GRID_OFFSET.0 + 3).ends_with("bob"));
+        bob.dig(&[0, 0]);
+        assert!(!bob.busy());
+    }
+
+    #[test]
+    fn modes() {
+        let new_board = || Ok(minefield::client::Client::new_random(2, 2, 0));
+        let game = Game::new(
+            Mode::TimeAttack(Duration::from_secs(60)),
+            Box::new(new_board),
+        );
+        let mut t_client = TuiClient::from_game(game.unwrap());
+        assert_eq!(
+            t_client.frame().line(3),
+            "Time left: 60s  Boards cleared: 0"
+        );
+        t_client.backend.client.flag(&[0, 0]);
+        assert!(t_client.frame().line(3).starts_with("Time left: "));
+
+        let mut t_client = TuiClient::new(minefield::client::Client::new_random(2, 2, 0));
+        t_client.backend.client.flag(&[0, 0]);
+        assert_eq!(t_client.frame().line(3), "Time: 0s");
+    }
+}
@@ -0,0 +1,901 @@
+use super::backend::{Backend, Teammate};
+use crate::minefield::client::{CellState, Event, GameState};
+use crate::minefield::field::describe_variant;
+use crate::minefield::grid::Grid;
+use crate::options::GameOptions;
+use crate::protocol::{self, Request, StateLine};
+use crate::race::{RaceState, Standings};
+use crate::registry::Player;
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::time::{Duration, Instant};
+
+/// Longest wait for an answer before the connection is considered lost
+const ANSWER_TIMEOUT: Duration = Duration::from_secs(5);
+/// Longest wait between two attempts to reconnect
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(2);
+/// Wait between two looks at the moves of the other players of a shared game,
+/// or at the progress of the other racers
+const SYNC_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Line protocol connection to a game server
+struct Connection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Connection {
+    fn open(address: &str) -> std::io::Result<Connection> {
+        let stream = TcpStream::connect(address)?;
+        stream.set_read_timeout(Some(ANSWER_TIMEOUT))?;
+        stream.set_nodelay(true)?;
+        Ok(Connection {
+            writer: stream.try_clone()?,
+            reader: BufReader::new(stream),
+        })
+    }
+
+    fn exchange(&mut self, request: &Request) -> std::io::Result<String> {
+        //! Sends a request and waits for its answer
+        // one write per line, sent right away
+        self.writer.write_all(format!("{}\n", request).as_bytes())?;
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(line.trim_end().to_string())
+    }
+}
+
+/// Request sent to the server, with what it expects to change
+#[derive(Debug, Clone)]
+struct Move {
+    request: Request,
+    /// Index and state of the cell before the move
+    before: Option<(usize, CellState)>,
+}
+
+impl Move {
+    fn applied(&self, state: &StateLine) -> bool {
+        //! Whether the server played the move before the connection dropped
+        //!
+        //! Flags toggle, so moves cannot just be sent again.
+        match (&self.request, self.before) {
+            (Request::Submit(_), _) => state.game_state != GameState::Running,
+            (_, Some((index, before))) => {
+                state.game_state != GameState::Running || state.cells[index] != Some(before)
+            }
+            // chords and state requests can be repeated
+            _ => false,
+        }
+    }
+}
+
+/// Messages of the connection thread
+enum Update {
+    Answer(String),
+    Disconnected(String),
+    Reconnected,
+}
+
+fn reconnect(address: &str, updates: &Sender<Update>) -> Option<Connection> {
+    //! Tries to connect again, waiting longer after every failure; gives up
+    //! when the backend is dropped
+    let mut delay = Duration::from_millis(100);
+    loop {
+        std::thread::sleep(delay);
+        match Connection::open(address) {
+            Ok(connection) => {
+                updates.send(Update::Reconnected).ok()?;
+                return Some(connection);
+            }
+            Err(e) => updates.send(Update::Disconnected(e.to_string())).ok()?,
+        }
+        delay = (delay * 2).min(MAX_RETRY_DELAY);
+    }
+}
+
+fn run_connection(
+    address: String,
+    mut connection: Connection,
+    id: u64,
+    hello: Option<Request>,
+    moves: Receiver<Move>,
+    updates: Sender<Update>,
+) {
+    //! Sends the moves one at a time, and forwards the answers
+    //!
+    //! After a dropped connection, `hello` is sent again (e.g. to play as the
+    //! same player), and the state of the game tells whether the move was
+    //! played; if not, it is sent again.
+    for mv in moves {
+        let mut request = mv.request.clone();
+        loop {
+            match connection.exchange(&request) {
+                Ok(answer) => {
+                    if updates.send(Update::Answer(answer)).is_err() {
+                        return;
+                    }
+                    break;
+                }
+                Err(e) => {
+                    if updates.send(Update::Disconnected(e.to_string())).is_err() {
+                        return;
+                    }
+                    connection = match reconnect(&address, &updates) {
+                        Some(connection) => connection,
+                        None => return,
+                    };
+                    if let Some(hello) = &hello {
+                        // a failure shows up with the next request
+                        let _ = connection.exchange(hello);
+                    }
+                    let state = connection
+                        .exchange(&Request::State(id))
+                        .ok()
+                        .and_then(|answer| StateLine::parse(&answer).ok());
+                    request = match state {
+                        Some(state) if mv.applied(&state) => Request::State(id),
+                        _ => mv.request.clone(),
+                    };
+                }
+            }
+        }
+    }
+}
+
+fn cannot_connect(address: &str) -> impl Fn(std::io::Error) -> String + '_ {
+    move |e| format!("Cannot connect to {}: {}", address, e)
+}
+
+fn new_game(connection: &mut Connection, args: &[String]) -> std::io::Result<Result<u64, String>> {
+    //! Starts a game, and returns its id
+    let answer = connection.exchange(&Request::New(args.to_vec()))?;
+    Ok(match answer.strip_prefix("ok game ") {
+        Some(id) => id
+            .parse()
+            .map_err(|_| format!("Invalid answer: {}", answer)),
+        None => Err(answer.trim_start_matches("error ").to_string()),
+    })
+}
+
+fn game_options(
+    connection: &mut Connection,
+    id: u64,
+) -> std::io::Result<Result<Vec<String>, String>> {
+    //! Options a game was started with, e.g. to play the game of another
+    //! player
+    let answer = connection.exchange(&Request::Options(id))?;
+    Ok(match answer.strip_prefix(&format!("ok options {}", id)) {
+        Some(args) => Ok(args.split_whitespace().map(String::from).collect()),
+        None => Err(answer.trim_start_matches("error ").to_string()),
+    })
+}
+
+/// Game hosted by a game server, played through its line protocol
+///
+/// Moves are drawn right away and sent in the background: flags show up
+/// before the server confirms them, and dug cells look pressed. The answers
+/// of the server replace the board as they come.
+pub struct RemoteBackend {
+    id: u64,
+    options: GameOptions,
+    state: Grid<CellState>,
+    game_state: GameState,
+    num_bombs: usize,
+    lives_left: usize,
+    start_time: Option<Instant>,
+    /// Moves sent, waiting for their answer, oldest first
+    in_flight: VecDeque<Move>,
+    status: Option<String>,
+    /// Name of the player, on games shared with other players and races
+    name: Option<String>,
+    /// Race the game is part of
+    race: Option<u64>,
+    standings: Option<Standings>,
+    players: Vec<Player>,
+    /// Player who last changed each cell, by index in the grid
+    owners: Vec<Option<usize>>,
+    /// Cell shown to the other players
+    cursor: Option<Vec<usize>>,
+    /// Cells of the last answer of the server, to tell what changed
+    confirmed: Vec<Option<CellState>>,
+    subscribers: Vec<Sender<Event>>,
+    last_sync: Option<Instant>,
+    moves: Sender<Move>,
+    updates: Receiver<Update>,
+}
+
+impl RemoteBackend {
+    pub fn connect(address: &str, args: &[String]) -> Result<RemoteBackend, String> {
+        //! Starts a game on a server, with the options of the clients
+        let mut connection = Connection::open(address).map_err(cannot_connect(address))?;
+        let id = new_game(&mut connection, args).map_err(cannot_connect(address))??;
+        RemoteBackend::start(address, connection, id, args, None, None, None)
+    }
+
+    pub fn host(address: &str, args: &[String], name: &str) -> Result<RemoteBackend, String> {
+        //! Starts a game on a server, that other players can join
+        let mut connection = Connection::open(address).map_err(cannot_connect(address))?;
+        let id = new_game(&mut connection, args).map_err(cannot_connect(address))??;
+        RemoteBackend::start(address, connection, id, args, Some(name), None, None)
+    }
+
+    pub fn join(address: &str, id: u64, name: &str) -> Result<RemoteBackend, String> {
+        //! Plays a game started by another player
+        let mut connection = Connection::open(address).map_err(cannot_connect(address))?;
+        let args = game_options(&mut connection, id).map_err(cannot_connect(address))??;
+        RemoteBackend::start(address, connection, id, &args, Some(name), None, None)
+    }
+
+    pub fn race(address: &str, args: &[String], name: &str) -> Result<RemoteBackend, String> {
+        //! Starts a race on a server, and enters it
+        let mut connection = Connection::open(address).map_err(cannot_connect(address))?;
+        let answer = connection
+            .exchange(&Request::Race(args.to_vec()))
+            .map_err(cannot_connect(address))?;
+        let race = match answer.strip_prefix("ok race ") {
+            Some(race) => race
+                .parse()
+                .map_err(|_| format!("Invalid answer: {}", answer))?,
+            None => return Err(answer.trim_start_matches("error ").to_string()),
+        };
+        RemoteBackend::enter_with(address, connection, race, name)
+    }
+
+    pub fn enter(address: &str, race: u64, name: &str) -> Result<RemoteBackend, String> {
+        //! Enters a race started by another player
+        let connection = Connection::open(address).map_err(cannot_connect(address))?;
+        RemoteBackend::enter_with(address, connection, race, name)
+    }
+
+    fn enter_with(
+        address: &str,
+        mut connection: Connection,
+        race: u64,
+        name: &str,
+    ) -> Result<RemoteBackend, String> {
+        let answer = connection
+            .exchange(&Request::Enter(race, name.to_string(), None))
+            .map_err(cannot_connect(address))?;
+        // `ok racer RACE GAME TOKEN`
+        let racer = match answer.strip_prefix(&format!("ok racer {} ", race)) {
+            Some(racer) => racer.split_whitespace().collect::<Vec<_>>(),
+            None => return Err(answer.trim_start_matches("error ").to_string()),
+        };
+        let invalid = || format!("Invalid answer: {}", answer);
+        let (id, token) = match racer[..] {
+            [game, token] => (game.parse().map_err(|_| invalid())?, token.to_string()),
+            _ => return Err(invalid()),
+        };
+        // the options of race games leave the seed out
+        let args = game_options(&mut connection, id).map_err(cannot_connect(address))??;
+        RemoteBackend::start(
+            address,
+            connection,
+            id,
+            &args,
+            Some(name),
+            Some(race),
+            Some(token),
+        )
+    }
+
+    fn start(
+        address: &str,
+        mut connection: Connection,
+        id: u64,
+        args: &[String],
+        name: Option<&str>,
+        race: Option<u64>,
+        token: Option<String>,
+    ) -> Result<RemoteBackend, String> {
+        //! Joins the game (or the race) as a player if named, then plays it
+        //! in the background
+        //!
+        //! The token plays as a player who already joined.
+        let options = GameOptions::parse(args.iter().cloned())?;
+        let mut hello = name.map(|name| match race {
+            Some(race) => Request::Enter(race, name.to_string(), token),
+            None => Request::Join(id, name.to_string(), token),
+        });
+        if let Some(hello) = &mut hello {
+            let answer = connection
+                .exchange(hello)
+                .map_err(cannot_connect(address))?;
+            if let Some(e) = answer.strip_prefix("error ") {
+                return Err(e.to_string());
+            }
+            // `ok player GAME PLAYER TOKEN` or `ok racer RACE GAME TOKEN`:
+            // the token plays as the same player after reconnecting
+            if let Request::Join(_, _, token) | Request::Enter(_, _, token) = hello {
+                *token = answer.split_whitespace().nth(4).map(String::from);
+            }
+        }
+        let answer = connection
+            .exchange(&Request::State(id))
+            .map_err(cannot_connect(address))?;
+        let state = StateLine::parse(&answer)?;
+        let standings = match race {
+            Some(race) => {
+                let answer = connection
+                    .exchange(&Request::Standings(race))
+                    .map_err(cannot_connect(address))?;
+                Some(Standings::parse(&answer)?)
+            }
+            None => None,
+        };
+        let (moves, moves_rx) = mpsc::channel();
+        let (updates_tx, updates) = mpsc::channel();
+        let thread_address = address.to_string();
+        std::thread::spawn(move || {
+            run_connection(thread_address, connection, id, hello, moves_rx, updates_tx)
+        });
+        let layout = options.layout()?;
+        let mut backend = RemoteBackend {
+            id,
+            state: layout.map(|_| CellState::Hidden),
+            owners: vec![None; layout.len()],
+            confirmed: state.cells.clone(),
+            subscribers: vec![],
+            options,
+            game_state: GameState::Running,
+            num_bombs: 0,
+            lives_left: 0,
+            start_time: None,
+            in_flight: VecDeque::new(),
+            status: None,
+            name: name.map(String::from),
+            race,
+            standings: None,
+            players: vec![],
+            cursor: None,
+            last_sync: None,
+            moves,
+            updates,
+        };
+        backend.update(&state)?;
+        if let Some(standings) = standings {
+            backend.update_standings(standings);
+        }
+        Ok(backend)
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    fn emit(&mut self, event: Event) {
+        self.subscribers
+            .retain(|events| events.send(event.clone()).is_ok());
+    }
+
+    fn start_clock(&mut self, start: Instant) {
+        if self.start_time.is_none() {
+            self.emit(Event::TimerStarted(start));
+        }
+        self.start_time = Some(start);
+    }
+
+    fn update(&mut self, state: &StateLine) -> Result<(), String> {
+        //! Takes the state of the server, then replays the flags still in
+        //! flight over it
+        //!
+        //! The cells that changed since the last answer make the events of
+        //! the game.
+        if state.dims != self.state.shape() {
+            return Err(String::from("The server answered with another board"));
+        }
+        for index in 0..state.cells.len() {
+            let cell_state = state.cells[index];
+            if cell_state == self.confirmed[index] {
+                continue;
+            }
+            let coords = self.state.index_rev(index).unwrap();
+            match cell_state {
+                Some(CellState::Revealed(cell)) => {
+                    self.emit(Event::Revealed(coords, CellState::Revealed(cell)))
+                }
+                Some(CellState::Exploded(cell)) => {
+                    self.emit(Event::Revealed(coords, CellState::Exploded(cell)))
+                }
+                Some(flags) => self.emit(Event::FlagChanged(coords, flags)),
+                None => (),
+            }
+        }
+        self.confirmed = state.cells.clone();
+        if self.game_state == GameState::Running && state.game_state != GameState::Running {
+            self.emit(Event::GameOver(state.game_state));
+        }
+        self.game_state = state.game_state;
+        self.num_bombs = state.num_bombs;
+        self.lives_left = state.lives_left;
+        for (index, cell_state) in state.cells.iter().enumerate() {
+            let coords = self.state.index_rev(index).unwrap();
+            self.state
+                .set(&coords, cell_state.unwrap_or(CellState::Hidden))?;
+        }
+        let max_flags = self.options.max_per_cell;
+        for mv in &self.in_flight {
+            if let Request::Flag(_, coords, _) = &mv.request {
+                let cell_state = self.state.get(coords).unwrap();
+                if Some(cell_state) == mv.before.map(|(_, before)| before) {
+                    self.state.set(coords, cell_state.next_flag(max_flags))?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn update_standings(&mut self, standings: Standings) {
+        //! Takes the progress of the racers; the clock follows the start of
+        //! the race
+        let now = Instant::now();
+        match standings.state {
+            RaceState::Starting(left) => self.start_clock(now + left),
+            _ if standings.started() && self.start_time.is_none() => self.start_clock(now),
+            _ => (),
+        }
+        self.standings = Some(standings);
+    }
+
+    fn send(&mut self, request: Request) {
+        //! Queues a move, remembering the cell it changes
+        let before = match &request {
+            Request::Dig(_, coords, _) | Request::Flag(_, coords, _) => {
+                self.state.index(coords).zip(self.state.get(coords))
+            }
+            _ => None,
+        };
+        let mv = Move { request, before };
+        if self.moves.send(mv.clone()).is_ok() {
+            self.in_flight.push_back(mv);
+        }
+    }
+
+    fn sync(&mut self, now: Instant) {
+        //! Looks at the moves and cursors of the other players, once in a
+        //! while on shared games, or at the progress of the other racers
+        let due = self
+            .last_sync
+            .is_none_or(|last_sync| now.saturating_duration_since(last_sync) >= SYNC_INTERVAL);
+        if self.name.is_none() || self.busy() || !due {
+            return;
+        }
+        self.last_sync = Some(now);
+        let id = self.id;
+        let requests = match self.race {
+            Some(race) => vec![Request::State(id), Request::Standings(race)],
+            None => vec![
+                Request::State(id),
+                Request::Players(id),
+                Request::Owners(id),
+            ],
+        };
+        for request in requests {
+            self.send(request);
+        }
+    }
+
+    fn playable(&mut self, coords: &[usize]) -> bool {
+        //! Whether a move on the cell can be sent; starts the clock
+        if self.game_state != GameState::Running || !self.state.exists(coords) {
+            return false;
+        }
+        if let Some(standings) = &self.standings {
+            // the server refuses moves before the start of the race
+            return standings.started();
+        }
+        if self.start_time.is_none() {
+            self.start_clock(Instant::now());
+        }
+        true
+    }
+
+    fn receive(&mut self, update: Update) {
+        match update {
+            Update::Answer(answer) => {
+                self.in_flight.pop_front();
+                let answered = if answer.starts_with("ok state ") {
+                    StateLine::parse(&answer).and_then(|state| self.update(&state))
+                } else if answer.starts_with("ok players ") {
+                    protocol::parse_players_line(&answer).map(|players| self.players = players)
+                } else if answer.starts_with("ok owners ") {
+                    protocol::parse_owners_line(&answer).map(|owners| self.owners = owners)
+                } else if answer.starts_with("ok standings ") {
+                    Standings::parse(&answer).map(|standings| self.update_standings(standings))
+                } else if answer.starts_with("ok ") {
+                    Ok(())
+                } else {
+                    // undo the optimistic drawing of the refused move
+                    let request = Request::State(self.id);
+                    self.send(request);
+                    Err(answer.trim_start_matches("error ").to_string())
+                };
+                if let Err(e) = answered {
+                    self.status = Some(e);
+                }
+            }
+            Update::Disconnected(e) => {
+                self.status = Some(format!("Connection lost ({}), reconnecting...", e));
+            }
+            Update::Reconnected => self.status = None,
+        }
+    }
+}
+
+impl Backend for RemoteBackend {
+    fn state(&self) -> &Grid<CellState> {
+        &self.state
+    }
+
+    fn game_state(&self) -> GameState {
+        self.game_state
+    }
+
+    fn num_bombs(&self) -> usize {
+        self.num_bombs
+    }
+
+    fn lives(&self) -> usize {
+        self.options.lives
+    }
+
+    fn lives_left(&self) -> usize {
+        self.lives_left
+    }
+
+    fn variant(&self) -> Option<String> {
+        describe_variant(self.options.num_anti_mines, self.options.liar)
+    }
+
+    fn score(&self) -> Option<usize> {
+        None
+    }
+
+    fn start_time(&self) -> Option<Instant> {
+        self.start_time
+    }
+
+    fn dig(&mut self, coords: &[usize]) {
+        if self.playable(coords) {
+            let expected = self.state.get(coords);
+            self.send(Request::Dig(self.id, coords.to_vec(), expected));
+        }
+    }
+
+    fn flag(&mut self, coords: &[usize]) {
+        if self.playable(coords) {
+            let cell_state = self.state.get(coords).unwrap();
+            self.send(Request::Flag(self.id, coords.to_vec(), Some(cell_state)));
+            let flagged = cell_state.next_flag(self.options.max_per_cell);
+            self.state.set(coords, flagged).unwrap();
+        }
+    }
+
+    fn chord(&mut self, coords: &[usize]) {
+        if self.playable(coords) {
+            let expected = self.state.get(coords);
+            self.send(Request::Chord(self.id, coords.to_vec(), expected));
+        }
+    }
+
+    fn submit(&mut self) {
+        if self.game_state == GameState::Running {
+            self.send(Request::Submit(self.id));
+        }
+    }
+
+    fn reveal(&mut self) {
+        //! The server keeps the board hidden until the game is over, and then
+        //! only shows the mines; races tell who won
+        let request = Request::State(self.id);
+        self.send(request);
+        if let Some(race) = self.race {
+            self.send(Request::Standings(race));
+        }
+        let deadline = Instant::now() + ANSWER_TIMEOUT;
+        while self.busy() && Instant::now() < deadline {
+            self.tick(Instant::now());
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn tick(&mut self, now: Instant) -> GameState {
+        while let Ok(update) = self.updates.try_recv() {
+            self.receive(update);
+        }
+        self.sync(now);
+        self.game_state
+    }
+
+    fn pending(&self) -> Vec<Vec<usize>> {
+        self.in_flight
+            .iter()
+            .filter_map(|mv| match &mv.request {
+                Request::Dig(_, coords, _) | Request::Chord(_, coords, _) => Some(coords.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn busy(&self) -> bool {
+        !self.in_flight.is_empty()
+    }
+
+    fn status(&self) -> Option<String> {
+        self.status.clone()
+    }
+
+    fn title(&self) -> Option<String> {
+        Some(match (&self.name, self.race) {
+            (Some(name), Some(race)) => format!("Race {} as {}", race, name),
+            (Some(name), None) => format!("Game {} as {}", self.id, name),
+            _ => format!("Game {}", self.id),
+        })
+    }
+
+    fn set_cursor(&mut self, coords: &[usize]) {
+        if self.name.is_some() && self.race.is_none() && self.cursor.as_deref() != Some(coords) {
+            self.cursor = Some(coords.to_vec());
+            self.send(Request::Cursor(self.id, coords.to_vec()));
+        }
+    }
+
+    fn teammates(&self) -> Vec<Teammate> {
+        self.players
+            .iter()
+            .enumerate()
+            .map(|(player, p)| Teammate {
+                name: p.name.clone(),
+                cursor: p.cursor.clone(),
+                cells: self.owners.iter().filter(|&&o| o == Some(player)).count(),
+                me: self.name.as_ref() == Some(&p.name),
+            })
+            .collect()
+    }
+
+    fn owner(&self, coords: &[usize]) -> Option<String> {
+        let player = self.owners.get(self.state.index(coords)?).copied()??;
+        self.players.get(player).map(|p| p.name.clone())
+    }
+
+    fn standings(&self) -> Option<&Standings> {
+        self.standings.as_ref()
+    }
+
+    fn racer(&self) -> Option<usize> {
+        let name = self.name.as_ref()?;
+        self.standings
+            .as_ref()?
+            .racers
+            .iter()
+            .position(|r| &r.name == name)
+    }
+
+    fn subscribe(&mut self) -> Option<Receiver<Event>> {
+        //! Changes of the game as confirmed by the server
+        let (events, receiver) = mpsc::channel();
+        self.subscribers.push(events);
+        Some(receiver)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::registry::Registry;
+    use crate::server;
+    use std::net::TcpListener;
+    use std::sync::Arc;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    fn wait(backend: &mut RemoteBackend) {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while backend.busy() && Instant::now() < deadline {
+            backend.tick(Instant::now());
+            std::thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    fn spawn_server(listener: TcpListener, registry: Arc<Registry>, connections: usize) {
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(connections) {
+                // every connection is served to the end before the next one
+                let _ = server::serve(stream.unwrap(), registry.clone());
+            }
+        });
+    }
+
+    #[test]
+    fn remote() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        spawn_server(listener, Arc::new(Registry::new(None)), 1);
+        let mut backend =
+            RemoteBackend::connect(&address, &args("--height 2 --width 3 --bombs 0 --lives 2"))
+                .unwrap();
+        assert_eq!(backend.state().shape(), &[2, 3]);
+        assert_eq!((backend.lives(), backend.lives_left()), (2, 2));
+        let events = backend.subscribe().unwrap();
+        // flags show up before the server answers
+        backend.flag(&[0, 1]);
+        assert_eq!(backend.state().get(&[0, 1]), Some(CellState::Flagged));
+        backend.dig(&[1, 2]);
+        assert_eq!(backend.pending(), vec![vec![1, 2]]);
+        wait(&mut backend);
+        assert!(backend.pending().is_empty());
+        assert_eq!(backend.state().get(&[0, 1]), Some(CellState::Flagged));
+        assert_eq!(
+            backend.state().get(&[0, 0]),
+            Some(CellState::Revealed(crate::minefield::field::Cell::Clean(0)))
+        );
+        assert!(backend.start_time().is_some());
+        backend.submit();
+        wait(&mut backend);
+        assert_eq!(backend.game_state(), GameState::Lost);
+        assert_eq!(backend.status(), None);
+        // the events follow the answers of the server
+        let events: Vec<Event> = events.try_iter().collect();
+        assert!(matches!(events[0], Event::TimerStarted(_)));
+        assert_eq!(
+            events[1],
+            Event::FlagChanged(vec![0, 1], CellState::Flagged)
+        );
+        let revealed = events
+            .iter()
+            .filter(|event| matches!(event, Event::Revealed(..)))
+            .count();
+        assert_eq!(revealed, 5);
+        assert_eq!(events.last(), Some(&Event::GameOver(GameState::Lost)));
+    }
+
+    #[test]
+    fn mines() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        spawn_server(listener, Arc::new(Registry::new(None)), 1);
+        let mut backend =
+            RemoteBackend::connect(&address, &args("--height 2 --width 3 --bombs 2 --seed 3"))
+                .unwrap();
+        let mines = |backend: &RemoteBackend| {
+            backend
+                .state()
+                .data()
+                .iter()
+                .filter(|&&cell_state| {
+                    cell_state == CellState::Revealed(crate::minefield::field::Cell::Bomb)
+                })
+                .count()
+        };
+        // the mines show up once the game is lost
+        assert_eq!(mines(&backend), 0);
+        backend.submit();
+        backend.reveal();
+        assert_eq!(backend.game_state(), GameState::Lost);
+        assert_eq!(mines(&backend), 2);
+    }
+
+    #[test]
+    fn reconnection() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let registry = Arc::new(Registry::new(None));
+        let mut backend = {
+            // the first connection only starts the game
+            let server_registry = registry.clone();
+            let server = std::thread::spawn(move || {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut writer = stream;
+                let mut seats = server::Seats::new();
+                for _ in 0..2 {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let request = Request::parse(&line).unwrap();
+                    writeln!(
+                        writer,
+                        "{}",
+                        server::handle(&server_registry, &request, &mut seats)
+                    )
+                    .unwrap();
+                }
+                listener
+            });
+            let backend = RemoteBackend::connect(&address, &args("--height 1 --width 3 --bombs 0"));
+            spawn_server(server.join().unwrap(), registry.clone(), 1);
+            backend.unwrap()
+        };
+        backend.flag(&[0, 2]);
+        wait(&mut backend);
+        assert_eq!(backend.status(), None);
+        // the flag was placed once, although the first connection dropped
+        assert_eq!(backend.state().get(&[0, 2]), Some(CellState::Flagged));
+        let flags = registry
+            .play(&Request::State(backend.id()), |_, s| {
+                s.client.get_flag_locations()
+            })
+            .unwrap();
+        assert_eq!(flags, vec![vec![0, 2]]);
+    }
+
+    #[test]
+    fn coop() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let registry = Arc::new(Registry::new(None));
+        let server_registry = registry.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(2) {
+                let registry = server_registry.clone();
+                std::thread::spawn(move || server::serve(stream.unwrap(), registry));
+            }
+        });
+        let args = args("--height 2 --width 3 --bombs 0 --hex");
+        let mut alice = RemoteBackend::host(&address, &args, "alice").unwrap();
+        let mut bob = RemoteBackend::join(&address, alice.id(), "bob").unwrap();
+        assert_eq!(bob.title(), Some(String::from("Game 1 as bob")));
+        // the joining player gets the board options from the server
+        assert_eq!(
+            bob.state().topology(),
+            crate::minefield::grid::Topology::Hex
+        );
+        assert!(RemoteBackend::join(&address, 7, "carol").is_err());
+
+        alice.flag(&[0, 2]);
+        bob.set_cursor(&[1, 1]);
+        wait(&mut alice);
+        wait(&mut bob);
+        // both see the flag of alice, and the cursor of bob, after a sync
+        for backend in [&mut alice, &mut bob].iter_mut() {
+            backend.tick(Instant::now() + SYNC_INTERVAL);
+            wait(backend);
+            assert_eq!(backend.state().get(&[0, 2]), Some(CellState::Flagged));
+        }
+        assert_eq!(bob.owner(&[0, 2]), Some(String::from("alice")));
+        let teammates = alice.teammates();
+        assert_eq!(teammates.len(), 2);
+        assert!(teammates[0].me && !teammates[1].me);
+        assert_eq!(teammates[0].cells, 1);
+        assert_eq!(teammates[1].cursor, Some(vec![1, 1]));
+
+        // bob digs a cell that alice flagged in the meantime
+        registry
+            .play(&Request::Flag(1, vec![1, 0], None), |_, _| ())
+            .unwrap();
+        bob.dig(&[1, 0]);
+        wait(&mut bob);
+        assert_eq!(
+            bob.status(),
+            Some(String::from("Conflict: 1,0 has changed"))
+        );
+        wait(&mut bob);
+        assert_eq!(bob.state().get(&[1, 0]), Some(CellState::Flagged));
+    }
+
+    #[test]
+    fn resending() {
+        let state = StateLine::parse("ok state 1 Running 0 1 1x2 f h").unwrap();
+        let flag = |before| Move {
+            request: Request::Flag(1, vec![0, 0], None),
+            before: Some((0, before)),
+        };
+        assert!(flag(CellState::Hidden).applied(&state));
+        assert!(!flag(CellState::Flagged).applied(&state));
+        assert!(!Move {
+            request: Request::Submit(1),
+            before: None
+        }
+        .applied(&state));
+        assert!(!Move {
+            request: Request::Chord(1, vec![0, 1], None),
+            before: None
+        }
+        .applied(&state));
+    }
+}
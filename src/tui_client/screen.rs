@@ -0,0 +1,235 @@
+use std::io::Write;
+use termion::{clear, color, cursor};
+
+/// A character on screen, with the escape sequence setting its colors
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScreenCell {
+    pub symbol: char,
+    pub style: String,
+}
+
+impl Default for ScreenCell {
+    fn default() -> ScreenCell {
+        ScreenCell {
+            symbol: ' ',
+            style: String::new(),
+        }
+    }
+}
+
+/// In-memory frame, drawn before being sent to the terminal
+///
+/// Coordinates are (row, col) and start at 1, like `cursor::Goto`.
+/// The frame grows to fit whatever is drawn on it.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Screen {
+    lines: Vec<Vec<ScreenCell>>,
+    cursor: Option<(u16, u16)>,
+}
+
+impl Screen {
+    pub fn new() -> Screen {
+        Screen::default()
+    }
+
+    pub fn put(&mut self, row: u16, col: u16, symbol: char, style: &str) {
+        assert!(row >= 1 && col >= 1, "screen coordinates start at 1");
+        let (row, col) = ((row - 1) as usize, (col - 1) as usize);
+        if self.lines.len() <= row {
+            self.lines.resize(row + 1, vec![]);
+        }
+        let line = &mut self.lines[row];
+        if line.len() <= col {
+            line.resize(col + 1, ScreenCell::default());
+        }
+        line[col] = ScreenCell {
+            symbol,
+            style: String::from(style),
+        };
+    }
+
+    pub fn put_str(&mut self, row: u16, col: u16, text: &str, style: &str) {
+        for (i, c) in text.chars().enumerate() {
+            self.put(row, col + i as u16, c, style);
+        }
+    }
+
+    pub fn get(&self, row: u16, col: u16) -> Option<&ScreenCell> {
+        self.lines
+            .get((row as usize).checked_sub(1)?)?
+            .get((col as usize).checked_sub(1)?)
+    }
+
+    pub fn set_cursor(&mut self, row: u16, col: u16) {
+        self.cursor = Some((row, col));
+    }
+
+    pub fn cursor(&self) -> Option<(u16, u16)> {
+        self.cursor
+    }
+
+    pub fn height(&self) -> u16 {
+        self.lines.len() as u16
+    }
+
+    pub fn line(&self, row: u16) -> String {
+        //! Text of a line, without colors nor trailing spaces
+        let text: String = match (row as usize)
+            .checked_sub(1)
+            .and_then(|r| self.lines.get(r))
+        {
+            Some(line) => line.iter().map(|c| c.symbol).collect(),
+            None => String::new(),
+        };
+        String::from(text.trim_end())
+    }
+
+    fn width(&self, row: usize) -> usize {
+        self.lines.get(row).map_or(0, |l| l.len())
+    }
+}
+
+/// Sends frames to the terminal, only writing what changed since the last one
+#[derive(Debug, Default)]
+pub struct Renderer {
+    previous: Option<Screen>,
+}
+
+impl Renderer {
+    pub fn new() -> Renderer {
+        Renderer::default()
+    }
+
+    pub fn invalidate(&mut self) {
+        //! Forgets the previous frame: the next one is drawn from scratch
+        self.previous = None;
+    }
+
+    pub fn render<T>(&mut self, frame: Screen, out: &mut T) -> Result<(), std::io::Error>
+    where
+        T: Write,
+    {
+        let blank = ScreenCell::default();
+        let empty = Screen::new();
+        let previous = match &self.previous {
+            Some(previous) => previous,
+            None => {
+                write!(out, "{}", clear::All)?;
+                &empty
+            }
+        };
+        let num_lines = frame.lines.len().max(previous.lines.len());
+        for row in 0..num_lines {
+            let width = frame.width(row).max(previous.width(row));
+            // current style of the terminal, None after a cursor move
+            let mut written_style: Option<&str> = None;
+            for col in 0..width {
+                let new = frame.lines.get(row).and_then(|l| l.get(col));
+                let old = previous.lines.get(row).and_then(|l| l.get(col));
+                let new = new.unwrap_or(&blank);
+                if new == old.unwrap_or(&blank) {
+                    written_style = None;
+                    continue;
+                }
+                if written_style.is_none() {
+                    write!(out, "{}", cursor::Goto(col as u16 + 1, row as u16 + 1))?;
+                }
+                if written_style != Some(&new.style[..]) {
+                    write!(
+                        out,
+                        "{}{}{}",
+                        color::Fg(color::Reset),
+                        color::Bg(color::Reset),
+                        new.style
+                    )?;
+                    written_style = Some(&new.style);
+                }
+                write!(out, "{}", new.symbol)?;
+            }
+            if written_style.is_some() {
+                write!(
+                    out,
+                    "{}{}",
+                    color::Fg(color::Reset),
+                    color::Bg(color::Reset)
+                )?;
+            }
+        }
+        if let Some((row, col)) = frame.cursor {
+            write!(out, "{}", cursor::Goto(col, row))?;
+        }
+        self.previous = Some(frame);
+        out.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn screen() {
+        let mut screen = Screen::new();
+        screen.put_str(2, 3, "abc", "");
+        screen.put(2, 4, 'X', "style");
+        assert_eq!(screen.height(), 2);
+        assert_eq!(screen.line(1), "");
+        assert_eq!(screen.line(2), "  aXc");
+        assert_eq!(screen.get(2, 4).unwrap().style, "style");
+        assert_eq!(screen.get(2, 10), None);
+        assert_eq!(screen.line(3), "");
+    }
+
+    #[test]
+    fn incremental_render() {
+        let mut renderer = Renderer::new();
+        let mut screen = Screen::new();
+        screen.put_str(1, 1, "hello", "");
+        screen.put_str(2, 1, "world", "");
+        screen.set_cursor(1, 1);
+
+        let mut out: Vec<u8> = vec![];
+        renderer.render(screen.clone(), &mut out).unwrap();
+        let first = String::from_utf8(out).unwrap();
+        assert!(first.starts_with(&format!("{}", clear::All)));
+        assert!(first.contains("hello") && first.contains("world"));
+
+        // same frame: only the cursor is written
+        let mut out: Vec<u8> = vec![];
+        renderer.render(screen.clone(), &mut out).unwrap();
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!("{}", cursor::Goto(1, 1))
+        );
+
+        // a single change: a single cell is written
+        screen.put(2, 5, 'D', "");
+        let mut out: Vec<u8> = vec![];
+        renderer.render(screen.clone(), &mut out).unwrap();
+        let resets = format!("{}{}", color::Fg(color::Reset), color::Bg(color::Reset));
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            format!(
+                "{}{}D{}{}",
+                cursor::Goto(5, 2),
+                resets,
+                resets,
+                cursor::Goto(1, 1)
+            )
+        );
+
+        // removed content is blanked
+        let mut smaller = Screen::new();
+        smaller.put_str(1, 1, "hello", "");
+        let mut out: Vec<u8> = vec![];
+        renderer.render(smaller, &mut out).unwrap();
+        let erase = String::from_utf8(out).unwrap();
+        assert!(erase.starts_with(&format!("{}{}     ", cursor::Goto(1, 2), resets)));
+
+        // invalidating forces a full redraw
+        renderer.invalidate();
+        let mut out: Vec<u8> = vec![];
+        renderer.render(screen, &mut out).unwrap();
+        assert!(String::from_utf8(out).unwrap().contains("hello"));
+    }
+}
@@ -0,0 +1,201 @@
+use super::backend::Backend;
+use crate::minefield::client::{CellState, GameState};
+use crate::minefield::grid::Grid;
+use crate::protocol::StateLine;
+use crate::spectate;
+use std::io::{BufRead, BufReader};
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver};
+use std::time::Instant;
+
+/// Game of another player, watched through the stream of a `Broadcaster`
+///
+/// The board follows the `state` lines of the stream; the moves of the TUI
+/// do nothing.
+pub struct SpectatorBackend {
+    address: String,
+    state: Grid<CellState>,
+    game_state: GameState,
+    num_bombs: usize,
+    lives: usize,
+    lives_left: usize,
+    start_time: Option<Instant>,
+    /// Last move of the player, or the end of the stream
+    status: Option<String>,
+    /// Lines of the stream, `None` once it ended
+    lines: Receiver<Option<String>>,
+}
+
+impl SpectatorBackend {
+    pub fn watch(address: &str) -> Result<SpectatorBackend, String> {
+        //! Connects to the stream of a game
+        let cannot_connect = |e: std::io::Error| format!("Cannot connect to {}: {}", address, e);
+        let stream = TcpStream::connect(address).map_err(cannot_connect)?;
+        let mut reader = BufReader::new(stream);
+        let mut board = String::new();
+        reader.read_line(&mut board).map_err(cannot_connect)?;
+        let (state, lives) = spectate::parse_board_line(board.trim_end())?;
+        let (lines_tx, lines) = mpsc::channel();
+        std::thread::spawn(move || {
+            for line in reader.lines().map_while(Result::ok) {
+                if lines_tx.send(Some(line)).is_err() {
+                    return;
+                }
+            }
+            let _ = lines_tx.send(None);
+        });
+        Ok(SpectatorBackend {
+            address: address.to_string(),
+            state,
+            game_state: GameState::Running,
+            num_bombs: 0,
+            lives,
+            lives_left: lives,
+            start_time: None,
+            status: None,
+            lines,
+        })
+    }
+
+    fn receive(&mut self, line: &str) -> Result<(), String> {
+        let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+        match kind {
+            "state" => {
+                let state = StateLine::parse(&format!("ok state 0 {}", rest))?;
+                if state.dims != self.state.shape() {
+                    return Err(String::from("The stream changed boards"));
+                }
+                let mask: Vec<bool> = state.cells.iter().map(Option::is_some).collect();
+                let cells = state
+                    .cells
+                    .iter()
+                    .map(|cell_state| cell_state.unwrap_or(CellState::Hidden))
+                    .collect();
+                self.state = Grid::with_dims(&state.dims, cells)?
+                    .with_mask(&mask)?
+                    .with_topology(self.state.topology())
+                    .with_wrap(self.state.wraps())?;
+                self.game_state = state.game_state;
+                self.num_bombs = state.num_bombs;
+                self.lives_left = state.lives_left;
+            }
+            "start" => {
+                self.start_time.get_or_insert_with(Instant::now);
+            }
+            "reveal" | "flag" => self.status = Some(format!("Last move: {}", line)),
+            "result" => self.status = Some(format!("The player {}", rest.to_lowercase())),
+            _ => return Err(format!("Invalid event: {}", line)),
+        }
+        Ok(())
+    }
+}
+
+impl Backend for SpectatorBackend {
+    fn state(&self) -> &Grid<CellState> {
+        &self.state
+    }
+
+    fn game_state(&self) -> GameState {
+        self.game_state
+    }
+
+    fn num_bombs(&self) -> usize {
+        self.num_bombs
+    }
+
+    fn lives(&self) -> usize {
+        self.lives
+    }
+
+    fn lives_left(&self) -> usize {
+        self.lives_left
+    }
+
+    fn variant(&self) -> Option<String> {
+        None
+    }
+
+    fn score(&self) -> Option<usize> {
+        None
+    }
+
+    fn start_time(&self) -> Option<Instant> {
+        self.start_time
+    }
+
+    fn dig(&mut self, _coords: &[usize]) {}
+
+    fn flag(&mut self, _coords: &[usize]) {}
+
+    fn chord(&mut self, _coords: &[usize]) {}
+
+    fn submit(&mut self) {}
+
+    fn reveal(&mut self) {}
+
+    fn tick(&mut self, _now: Instant) -> GameState {
+        while let Ok(line) = self.lines.try_recv() {
+            let received = match line {
+                Some(line) => self.receive(&line),
+                None => Err(String::from("The stream ended")),
+            };
+            if let Err(e) = received {
+                self.status = Some(e);
+            }
+        }
+        self.game_state
+    }
+
+    fn status(&self) -> Option<String> {
+        self.status.clone()
+    }
+
+    fn title(&self) -> Option<String> {
+        Some(format!("Watching {}", self.address))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::minefield::client::Event;
+    use crate::minefield::field::Cell;
+    use crate::spectate::Broadcaster;
+    use std::time::Duration;
+
+    #[test]
+    fn spectator() {
+        let mut state = Grid::new(1, 3, vec![CellState::Hidden; 3])
+            .unwrap()
+            .with_mask(&[true, true, false])
+            .unwrap();
+        let board = spectate::board_line(&state, 2);
+        let mut broadcaster = Broadcaster::bind(0, board, Duration::from_millis(0)).unwrap();
+        let mut spectator = SpectatorBackend::watch(&broadcaster.address().to_string()).unwrap();
+        assert_eq!(spectator.lives(), 2);
+        let revealed = CellState::Revealed(Cell::Clean(1));
+        broadcaster.send_event(&Event::TimerStarted(Instant::now()));
+        broadcaster.send_event(&Event::Revealed(vec![0, 0], revealed));
+        state.set(&[0, 0], revealed).unwrap();
+        broadcaster.send_state(GameState::Running, 1, 2, &state);
+        broadcaster.send_event(&Event::GameOver(GameState::Won));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while spectator.status() != Some(String::from("The player won"))
+            && Instant::now() < deadline
+        {
+            spectator.tick(Instant::now());
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(
+            spectator.state().get(&[0, 0]),
+            Some(CellState::Revealed(Cell::Clean(1)))
+        );
+        // holes come with the board
+        assert!(!spectator.state().exists(&[0, 2]));
+        assert_eq!(spectator.num_bombs(), 1);
+        assert!(spectator.start_time().is_some());
+        // watching does not play
+        spectator.flag(&[0, 1]);
+        assert_eq!(spectator.state().get(&[0, 1]), Some(CellState::Hidden));
+    }
+}
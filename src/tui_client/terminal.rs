@@ -0,0 +1,314 @@
+use signal_hook::consts::signal::{SIGCONT, SIGINT, SIGTERM, SIGTSTP, SIGWINCH};
+use signal_hook::iterator::{Handle, Signals};
+use std::io::Write;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Mutex, Once};
+use std::time::Duration;
+use termion::event::Event;
+use termion::input::TermRead;
+use termion::{cursor, screen};
+
+/// Terminal in raw mode, and what it takes to put it back
+struct Saved {
+    fd: libc::c_int,
+    /// Attributes before entering raw mode
+    termios: libc::termios,
+    /// Where the escape sequences of the terminal go
+    out: Box<dyn Write + Send>,
+}
+
+/// Set from the moment the terminal enters raw mode until `restore` puts it
+/// back, so that restoring twice does nothing the second time
+static SAVED: Mutex<Option<Saved>> = Mutex::new(None);
+static PANIC_HOOK: Once = Once::new();
+
+// same sequences as termion's MouseTerminal
+const ENTER_MOUSE_SEQUENCE: &str = "\x1b[?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h";
+const EXIT_MOUSE_SEQUENCE: &str = "\x1b[?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l";
+// back to the cursor shape configured by the user
+const DEFAULT_CURSOR_SHAPE: &str = "\x1b[0 q";
+
+fn saved() -> std::sync::MutexGuard<'static, Option<Saved>> {
+    // a panic may have happened while the lock was held
+    match SAVED.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+fn enter_on(fd: libc::c_int, mut out: Box<dyn Write + Send>) -> Result<(), std::io::Error> {
+    //! Puts the terminal of `fd` in raw mode, and `out` on the alternate
+    //! screen with mouse tracking
+    //!
+    //! The attributes are saved once raw mode is set, and not before: if
+    //! anything fails earlier, the terminal is left as it was and there is
+    //! nothing to restore. Entering again before restoring does nothing, so
+    //! that the raw attributes never replace the original ones.
+    let mut saved = saved();
+    if saved.is_some() {
+        return Ok(());
+    }
+    // SAFETY: termios is a C struct of integers and arrays of integers, for
+    // which all zeroes is a valid value; tcgetattr overwrites it on success
+    let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+    // SAFETY: the pointer is to a live termios that tcgetattr may write; a fd
+    // that is not a terminal makes the call fail with ENOTTY
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let original = termios;
+    // SAFETY: cfmakeraw only changes the flags of the termios it is given
+    unsafe { libc::cfmakeraw(&mut termios) };
+    // SAFETY: tcsetattr only reads the termios, which tcgetattr filled
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let sequences = format!(
+        "{}{}{}",
+        screen::ToAlternateScreen,
+        ENTER_MOUSE_SEQUENCE,
+        cursor::BlinkingBlock
+    );
+    let written = out
+        .write_all(sequences.as_bytes())
+        .and_then(|_| out.flush());
+    // saved even if writing failed, since raw mode is on
+    *saved = Some(Saved {
+        fd,
+        termios: original,
+        out,
+    });
+    written
+}
+
+fn enter() -> Result<(), std::io::Error> {
+    enter_on(libc::STDIN_FILENO, Box::new(std::io::stdout()))
+}
+
+fn restore() {
+    //! Leaves raw mode, mouse tracking and the alternate screen
+    //!
+    //! Does nothing if the terminal is already restored, so it is safe to call
+    //! from both the panic hook and `Drop`.
+    if let Some(mut saved) = saved().take() {
+        let sequences = format!(
+            "{}{}{}{}",
+            EXIT_MOUSE_SEQUENCE,
+            DEFAULT_CURSOR_SHAPE,
+            cursor::Show,
+            screen::ToMainScreen
+        );
+        let _ = saved.out.write_all(sequences.as_bytes());
+        let _ = saved.out.flush();
+        // SAFETY: tcsetattr only reads the termios, saved by enter_on from
+        // the same fd
+        unsafe { libc::tcsetattr(saved.fd, libc::TCSANOW, &saved.termios) };
+    }
+}
+
+fn install_panic_hook() {
+    //! Restores the terminal before the panic message is printed, once for
+    //! the whole program
+    PANIC_HOOK.call_once(|| {
+        let previous_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            // restore first, so that the message is readable
+            restore();
+            previous_hook(info);
+        }));
+    });
+}
+
+/// Keeps the terminal in raw mode, on the alternate screen, with mouse tracking
+///
+/// The terminal is restored when the guard is dropped, when the program panics,
+/// and while the program is suspended.
+pub struct TerminalGuard {
+    _private: (),
+}
+
+impl TerminalGuard {
+    pub fn new() -> Result<TerminalGuard, std::io::Error> {
+        install_panic_hook();
+        enter()?;
+        Ok(TerminalGuard { _private: () })
+    }
+
+    pub fn suspend(&mut self) -> Result<(), std::io::Error> {
+        //! Restores the terminal and stops the process, like Ctrl-Z in a shell
+        //!
+        //! Returns once the process is resumed, with the terminal set up again.
+        restore();
+        signal_hook::low_level::emulate_default_handler(SIGTSTP)?;
+        enter()
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+/// What the main loop reacts to
+#[derive(Debug, PartialEq)]
+pub enum Input {
+    Event(Event),
+    /// Quit cleanly (SIGINT, SIGTERM)
+    Terminate,
+    /// Suspend the program (SIGTSTP)
+    Suspend,
+    /// Screen must be drawn again (SIGCONT, SIGWINCH)
+    Redraw,
+    /// Nothing happened for a while
+    Tick,
+}
+
+/// Stream of inputs from the keyboard, the mouse and signals
+pub struct Inputs {
+    receiver: Receiver<Input>,
+    signals: Handle,
+}
+
+impl Inputs {
+    pub fn spawn() -> Result<Inputs, std::io::Error> {
+        let (sender, receiver) = mpsc::channel();
+        let event_sender = sender.clone();
+        std::thread::spawn(move || {
+            for event in std::io::stdin().events() {
+                let sent = match event {
+                    Ok(event) => event_sender.send(Input::Event(event)).is_ok(),
+                    Err(_) => false,
+                };
+                if !sent {
+                    break;
+                }
+            }
+        });
+        let mut signals = Signals::new([SIGINT, SIGTERM, SIGTSTP, SIGCONT, SIGWINCH])?;
+        let handle = signals.handle();
+        std::thread::spawn(move || {
+            for signal in signals.forever() {
+                let input = match signal {
+                    SIGINT | SIGTERM => Input::Terminate,
+                    SIGTSTP => Input::Suspend,
+                    _ => Input::Redraw,
+                };
+                if sender.send(input).is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(Inputs {
+            receiver,
+            signals: handle,
+        })
+    }
+
+    pub fn next(&self) -> Option<Input> {
+        //! Waits for the next input
+        self.receiver.recv().ok()
+    }
+
+    pub fn next_timeout(&self, timeout: Duration) -> Option<Input> {
+        //! Waits for the next input, or for `timeout` to tick the clocks
+        match self.receiver.recv_timeout(timeout) {
+            Ok(input) => Some(input),
+            Err(RecvTimeoutError::Timeout) => Some(Input::Tick),
+            Err(RecvTimeoutError::Disconnected) => None,
+        }
+    }
+}
+
+impl Drop for Inputs {
+    fn drop(&mut self) {
+        // give the default signal behaviours back
+        self.signals.close();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Arc;
+
+    /// Escape sequences written to the terminal
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Output {
+        fn take(&self) -> String {
+            String::from_utf8(std::mem::take(&mut *self.0.lock().unwrap())).unwrap()
+        }
+    }
+
+    fn local_flags(fd: libc::c_int) -> libc::tcflag_t {
+        // SAFETY: as in enter_on
+        let mut termios: libc::termios = unsafe { std::mem::zeroed() };
+        assert_eq!(unsafe { libc::tcgetattr(fd, &mut termios) }, 0);
+        termios.c_lflag
+    }
+
+    #[test]
+    fn restore_terminal() {
+        // a pseudo-terminal stands for the one of the player
+        // SAFETY: ptsname points to a nul-terminated name, read by open
+        // before any other call to ptsname
+        let (master, fd) = unsafe {
+            let master = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+            assert!(master >= 0);
+            assert_eq!(libc::grantpt(master), 0);
+            assert_eq!(libc::unlockpt(master), 0);
+            let fd = libc::open(libc::ptsname(master), libc::O_RDWR | libc::O_NOCTTY);
+            assert!(fd >= 0);
+            (master, fd)
+        };
+        let cooked = local_flags(fd);
+        assert_ne!(cooked & libc::ICANON, 0);
+        let output = Output::default();
+        enter_on(fd, Box::new(output.clone())).unwrap();
+        assert_eq!(local_flags(fd) & libc::ICANON, 0);
+        assert!(output
+            .take()
+            .contains(&screen::ToAlternateScreen.to_string()));
+        // entering again keeps the original attributes
+        enter_on(fd, Box::new(output.clone())).unwrap();
+        assert_eq!(output.take(), "");
+
+        restore();
+        assert_eq!(local_flags(fd), cooked);
+        assert!(output.take().ends_with(&screen::ToMainScreen.to_string()));
+        // restoring twice does nothing
+        restore();
+        assert_eq!(output.take(), "");
+
+        // a panic restores the terminal before the message is printed
+        install_panic_hook();
+        enter_on(fd, Box::new(output.clone())).unwrap();
+        assert!(std::panic::catch_unwind(|| panic!("boom")).is_err());
+        assert_eq!(local_flags(fd), cooked);
+        assert!(output.take().contains(EXIT_MOUSE_SEQUENCE));
+
+        // nothing is saved when the fd is not a terminal
+        let file = std::fs::File::open("Cargo.toml").unwrap();
+        let not_a_tty = std::os::unix::io::AsRawFd::as_raw_fd(&file);
+        assert!(enter_on(not_a_tty, Box::new(output.clone())).is_err());
+        assert!(saved().is_none());
+        // SAFETY: both fds are open and owned by the test
+        unsafe {
+            libc::close(fd);
+            libc::close(master);
+        }
+    }
+}